@@ -1,12 +1,20 @@
+use std::fmt;
 use std::ops::Sub;
 use std::time::Instant;
 
-use log::{debug, info};
+use log::{debug, info, warn};
+use rayon::prelude::*;
 use sprs::CsVec;
 
-use crate::elements::base::MatrixTransUpdatable;
+use crate::elements::base::{format_value, MatrixTransUpdatable};
 use crate::elements::companion::CompanionModel;
+use crate::netlist::Unknowns;
+use crate::op_report::OpReport;
+use crate::options::Options;
+use crate::result::{AnalysisKind, AnalysisResult, Signal, SignalKind};
+use crate::solver::base::ConvergenceStats;
 use crate::task::{Task, TaskResult};
+use crate::topology::{check_topology, TopologyError};
 
 use super::netlist::Netlist;
 use super::solver::base::Solver;
@@ -29,10 +37,82 @@ impl From<String> for Mode {
     }
 }
 
+/// `.DC <source> <start> <stop> <step>` sweep of an independent source, or
+/// of the circuit temperature when `source` is `TEMP`.
+#[derive(Debug, Clone)]
+pub struct DcSweep {
+    pub source: String,
+    pub start: f64,
+    pub stop: f64,
+    pub step: f64,
+}
+
+impl fmt::Display for DcSweep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ".DC {} {} {} {}",
+            self.source,
+            format_value(self.start),
+            format_value(self.stop),
+            format_value(self.step)
+        )
+    }
+}
+
+impl DcSweep {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut iter = s.split_whitespace().skip(1);
+        let source = iter
+            .next()
+            .ok_or("Missing source in .DC directive")?
+            .to_string();
+        let mut next_value = |what: &str| {
+            iter.next()
+                .ok_or(format!("Missing {} in .DC directive", what))?
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid {} in .DC directive", what))
+        };
+        let start = next_value("start")?;
+        let stop = next_value("stop")?;
+        let step = next_value("step")?;
+        Self::new(source, start, stop, step)
+    }
+
+    pub fn new(source: String, start: f64, stop: f64, step: f64) -> Result<Self, String> {
+        if step == 0. || (stop - start) * step < 0. {
+            return Err(format!(
+                "Invalid step {} for .DC from {} to {}",
+                step, start, stop
+            ));
+        }
+
+        Ok(Self {
+            source,
+            start,
+            stop,
+            step,
+        })
+    }
+
+    pub fn is_temperature(&self) -> bool {
+        self.source.eq_ignore_ascii_case("TEMP")
+    }
+
+    pub fn get_values(&self) -> Vec<f64> {
+        let n = ((self.stop - self.start) / self.step + 1e-9).floor() as usize + 1;
+        (0..n).map(|i| self.start + i as f64 * self.step).collect()
+    }
+}
+
 struct AnalyzerConfig {
     mode: Mode,
     disp_digits: usize,
     final_time: f64,
+    options: Options,
+    dc_sweep: Option<DcSweep>,
+    /// Don't print the results to stdout.
+    quiet: bool,
 }
 
 impl Default for AnalyzerConfig {
@@ -41,6 +121,9 @@ impl Default for AnalyzerConfig {
             mode: Mode::Unknown,
             disp_digits: 5,
             final_time: 10.,
+            options: Options::default(),
+            dc_sweep: None,
+            quiet: false,
         }
     }
 }
@@ -66,12 +149,30 @@ impl Analyzer {
         self.config.disp_digits = disp_digits;
     }
 
+    pub fn get_disp_digits(&self) -> usize {
+        self.config.disp_digits
+    }
+
     pub fn set_final_time(&mut self, final_time: f64) {
         self.config.final_time = final_time;
     }
 
-    pub fn analyze(&self, tasks: &[Task]) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn set_options(&mut self, options: Options) {
+        self.netlist.set_temperature(options.temp);
+        self.config.options = options;
+    }
+
+    pub fn set_dc_sweep(&mut self, dc_sweep: DcSweep) {
+        self.config.dc_sweep = Some(dc_sweep);
+    }
+
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.config.quiet = quiet;
+    }
+
+    pub fn analyze(&self, tasks: &[Task]) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
         info!("Analysis started");
+        self.check_topology()?;
         match self.config.mode {
             Mode::DC => self.analyze_dc(tasks),
             Mode::Trans => self.analyze_trans(tasks),
@@ -81,56 +182,238 @@ impl Analyzer {
         }
     }
 
-    fn analyze_dc(&self, _tasks: &[Task]) -> Result<(), Box<dyn std::error::Error>> {
+    fn check_topology(&self) -> Result<(), TopologyError> {
+        let (errors, warnings): (Vec<_>, Vec<_>) = check_topology(&self.netlist)
+            .into_iter()
+            .partition(|issue| issue.is_error());
+
+        warnings.iter().for_each(|issue| warn!("{}", issue));
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(TopologyError { issues: errors })
+        }
+    }
+
+    /// Run independent jobs on the thread pool. Results keep the order of
+    /// `inputs` regardless of scheduling.
+    fn run_parallel<T, R, F>(
+        &self,
+        inputs: Vec<T>,
+        job: F,
+    ) -> Result<Vec<R>, Box<dyn std::error::Error>>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> Result<R, String> + Sync + Send,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.config.options.threads)
+            .build()?;
+        let results = pool.install(|| inputs.into_par_iter().map(job).collect::<Result<_, _>>())?;
+        Ok(results)
+    }
+
+    fn analyze_dc(&self, tasks: &[Task]) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+        if let Some(dc_sweep) = &self.config.dc_sweep {
+            return self.analyze_dc_sweep(tasks, dc_sweep);
+        }
+
         let start = Instant::now();
-        let e: crate::netlist::Equation = self.netlist.get_equation_dc();
-        let time_varing_non_linear_elements = &self.netlist.time_varing_non_linear_elements;
-
-        let result = NewtonSolver::solve_dc(
-            &e.mat_a,
-            &e.vec_b,
-            time_varing_non_linear_elements.as_slice(),
-        )?;
         let node_num = self.netlist.node_num.get();
+        let (result, unknowns, stats) = solve_op(&self.netlist, &self.config.options)?;
+
+        if !self.config.quiet {
+            for node_id in 0..(node_num - 1) {
+                println!(
+                    "Node[{}]: {:.width$} V",
+                    node_id + 1,
+                    result[node_id],
+                    width = self.config.disp_digits
+                );
+            }
+            OpReport::new(&self.netlist, &result, &unknowns).print(self.config.disp_digits);
+        }
+        let elapsed = start.elapsed();
+        info!("Elapsed: {:.2?}", elapsed);
 
-        for node_id in 0..(node_num - 1) {
-            println!(
-                "Node[{}]: {:.width$} V",
-                node_id + 1,
-                result[node_id],
-                width = self.config.disp_digits
-            );
+        let mut analysis_result =
+            AnalysisResult::new(AnalysisKind::Op, None, &unknowns, &self.config.options);
+        analysis_result.push(None, &result);
+        analysis_result.elapsed = elapsed;
+        analysis_result.stats = stats;
+        Ok(analysis_result)
+    }
+
+    fn analyze_dc_sweep(
+        &self,
+        tasks: &[Task],
+        dc_sweep: &DcSweep,
+    ) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let values = dc_sweep.get_values();
+
+        // Every point gets its own copy of the netlist, so no state is shared
+        // between the jobs.
+        let jobs = values
+            .iter()
+            .map(|value| {
+                let mut netlist = self.netlist.clone();
+                if dc_sweep.is_temperature() {
+                    netlist.set_temperature(*value);
+                } else {
+                    netlist.set_source_value(&dc_sweep.source, *value)?;
+                }
+                Ok((*value, netlist))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let options = &self.config.options;
+        let results = self.run_parallel(jobs, |(value, netlist)| {
+            solve_op(&netlist, options)
+                .map_err(|e| format!("{} = {}: {}", dc_sweep.source, value, e))
+        })?;
+
+        let mut stats = ConvergenceStats::default();
+        let mut unknowns = Unknowns::default();
+        let results = results
+            .into_iter()
+            .map(|(x, point_unknowns, point_stats)| {
+                stats.merge(&point_stats);
+                unknowns = point_unknowns;
+                x
+            })
+            .collect::<Vec<_>>();
+
+        if !self.config.quiet {
+            self.print_dc_sweep(dc_sweep, &values, &results);
         }
+
         let elapsed = start.elapsed();
         info!("Elapsed: {:.2?}", elapsed);
 
-        Ok(())
+        let mut task_results = tasks.iter().map(TaskResult::new).collect::<Vec<_>>();
+        for x in results.iter() {
+            for task in &mut task_results {
+                task.update(x);
+            }
+        }
+        let unit = if dc_sweep.is_temperature() {
+            "°C"
+        } else {
+            self.netlist.get_source_unit(&dc_sweep.source)
+        };
+        let x_label = format!("{} / {}", dc_sweep.source, unit);
+        task_results.iter().for_each(|task| {
+            task.run(&values, &x_label);
+        });
+
+        let sweep = if dc_sweep.is_temperature() {
+            Signal::new("temp-sweep", SignalKind::Temperature)
+        } else if unit == "A" {
+            Signal::new("i-sweep", SignalKind::Current)
+        } else {
+            Signal::new("v-sweep", SignalKind::Voltage)
+        };
+        let mut analysis_result = AnalysisResult::new(
+            AnalysisKind::DcSweep,
+            Some(sweep),
+            &unknowns,
+            &self.config.options,
+        );
+        for (value, x) in values.iter().zip(results.iter()) {
+            analysis_result.push(Some(*value), x);
+        }
+        analysis_result.elapsed = elapsed;
+        analysis_result.stats = stats;
+        Ok(analysis_result)
+    }
+
+    fn print_dc_sweep(&self, dc_sweep: &DcSweep, values: &[f64], results: &[CsVec<f64>]) {
+        let node_num = self.netlist.node_num.get();
+        let width = self.config.disp_digits;
+        let header = (1..node_num)
+            .map(|node_id| format!("Node[{}]", node_id))
+            .collect::<Vec<_>>();
+        println!("{}\t{}", dc_sweep.source, header.join("\t"));
+        for (value, x) in values.iter().zip(results.iter()) {
+            let row = (0..(node_num - 1))
+                .map(|node_id| format!("{:.width$}", x[node_id], width = width))
+                .collect::<Vec<_>>();
+            println!("{:.width$}\t{}", value, row.join("\t"), width = width);
+        }
     }
 
-    fn analyze_trans(&self, tasks: &[Task]) -> Result<(), Box<dyn std::error::Error>> {
+    fn analyze_trans(&self, tasks: &[Task]) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
         let start = Instant::now();
 
-        let mut delta_t = 1e-2;
         let final_time = self.config.final_time;
+        // Steps are accepted regardless of the solution change once they
+        // fall below 0.001% of the simulated time.
+        let min_step = final_time * 1e-5;
 
         let mut companion_models = self
             .netlist
             .time_varing_linear_elements
             .iter()
             .map(|e| CompanionModel::new_from_linear(e, &self.netlist))
+            .chain(
+                self.netlist
+                    .mutual_inductances
+                    .iter()
+                    .map(|e| CompanionModel::new_from_coupling(e, &self.netlist)),
+            )
+            .chain(
+                self.netlist
+                    .time_varing_non_linear_elements
+                    .iter()
+                    .flat_map(|e| CompanionModel::new_from_non_linear(e, &self.netlist)),
+            )
             .collect::<Vec<_>>();
 
         let basic_eq = self.netlist.get_equation_trans(&companion_models);
+        let mut analysis_result = AnalysisResult::new(
+            AnalysisKind::Transient,
+            Some(Signal::new("time", SignalKind::Time)),
+            &basic_eq.unknowns,
+            &self.config.options,
+        );
+        let mut rejected_steps = 0;
+        let solver = NewtonSolver::new(
+            self.config.options.convergence,
+            self.config.options.linear_solver,
+            basic_eq.unknowns,
+        );
         let (basic_mat_a, basic_vec_b) = (basic_eq.mat_a, basic_eq.vec_b);
 
         let mut x = CsVec::empty(basic_vec_b.dim());
         let mut current_time = 0.;
+        let max_step = companion_models
+            .iter()
+            .filter_map(|m| m.get_max_step())
+            .fold(f64::INFINITY, f64::min);
+        // Start at 1% of the simulated time, or at the shortest line delay so
+        // that the steps stay aligned with the delayed waves.
+        let mut delta_t = if max_step.is_finite() {
+            max_step
+        } else {
+            final_time * 1e-2
+        };
 
         let mut time_stamps = Vec::new();
         let mut task_results = tasks.iter().map(TaskResult::new).collect::<Vec<_>>();
 
+        // Fix the switch states at the initial solution so that the first
+        // step does not flip them between Newton iterations.
+        self.netlist
+            .time_varing_non_linear_elements
+            .iter()
+            .for_each(|e| e.accept(&x));
+
         while current_time < final_time {
             loop {
+                delta_t = delta_t.min(max_step);
                 if current_time + delta_t > final_time {
                     delta_t = final_time - current_time;
                 }
@@ -138,21 +421,33 @@ impl Analyzer {
                 let mut mat_a = basic_mat_a.clone();
                 let mut vec_b = basic_vec_b.clone();
 
+                self.netlist
+                    .time_varing_non_linear_elements
+                    .iter()
+                    .for_each(|e| e.set_time(current_time + delta_t));
                 companion_models.iter_mut().for_each(|m| {
-                    m.update_companion_elements(&x, delta_t);
+                    m.update_companion_elements(&x, current_time, delta_t);
                     m.update_matrix_trans(&mut mat_a, &mut vec_b, &x);
                 });
 
                 debug!("mat_a: {}", mat_a.to_dense());
                 debug!("vec_b: {}", vec_b.to_dense());
 
-                let attemp_x = NewtonSolver::solve_dc(
+                let attemp_x = solver.solve_dc(
                     &mat_a,
                     &vec_b,
                     self.netlist.time_varing_non_linear_elements.as_slice(),
                 )?;
 
-                if attemp_x.sub(&x).l1_norm() < 1e-1 || delta_t < 1e-4 {
+                // Shrink the step onto switching events so that a switch
+                // changes state close to where its control crosses over.
+                let switching = self
+                    .netlist
+                    .time_varing_non_linear_elements
+                    .iter()
+                    .any(|e| e.is_switching(&attemp_x));
+
+                if (attemp_x.sub(&x).l1_norm() < 1e-1 && !switching) || delta_t < min_step {
                     x = attemp_x;
                     current_time += delta_t;
 
@@ -160,6 +455,7 @@ impl Analyzer {
                     break;
                 } else {
                     delta_t /= 2.;
+                    rejected_steps += 1;
                 }
             }
 
@@ -167,22 +463,50 @@ impl Analyzer {
             debug!("x: {}", x.to_dense());
 
             time_stamps.push(current_time);
+            analysis_result.push(Some(current_time), &x);
             for task in &mut task_results {
                 task.update(&x);
             }
 
             companion_models.iter_mut().for_each(|m| {
-                m.update_current(&x);
+                m.update_current(&x, current_time);
             });
+            self.netlist
+                .time_varing_non_linear_elements
+                .iter()
+                .for_each(|e| e.accept(&x));
         }
 
         let elapsed = start.elapsed();
         info!("Elapsed: {:.2?}", elapsed);
 
         task_results.iter().for_each(|task| {
-            task.run(&time_stamps);
+            task.run(&time_stamps, "Time / s");
         });
 
-        Ok(())
+        analysis_result.elapsed = elapsed;
+        analysis_result.stats = solver.get_stats();
+        analysis_result.stats.rejected_steps = rejected_steps;
+        Ok(analysis_result)
     }
 }
+
+/// Solve the operating point of `netlist`, returning the solution together
+/// with the layout of its unknowns and the Newton iteration counts.
+pub fn solve_op(
+    netlist: &Netlist,
+    options: &Options,
+) -> Result<(CsVec<f64>, Unknowns, ConvergenceStats), Box<dyn std::error::Error>> {
+    let e = netlist.get_equation_dc();
+    let solver = NewtonSolver::new(
+        options.convergence,
+        options.linear_solver,
+        e.unknowns.clone(),
+    );
+    let x = solver.solve_dc(
+        &e.mat_a,
+        &e.vec_b,
+        netlist.time_varing_non_linear_elements.as_slice(),
+    )?;
+    Ok((x, e.unknowns, solver.get_stats()))
+}
@@ -0,0 +1,299 @@
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+use crate::elements::basic::{BasicElementType, ResistorValue, SourceType};
+use crate::elements::time_varing_linear::{TimeVaringLinearElementType, TransmissionLine};
+use crate::elements::time_varing_non_linear::mosfet::{MosfetModels, MosfetType};
+use crate::elements::time_varing_non_linear::switch::SwitchModels;
+use crate::elements::{
+    BasicElement, MosfetModel, MutualInductance, TimeVaringLinearElement,
+    TimeVaringNonLinearElement,
+};
+use crate::netlist::{Netlist, NodeId};
+
+/// Fluent construction of a [`Netlist`] with named nodes.
+///
+/// Nodes are numbered in order of first use; `0` and `gnd` are ground.
+/// Analysis results name node voltages after the nodes, e.g. `v(out)`.
+/// The first invalid element is reported by [`NetlistBuilder::build`].
+#[derive(Debug, Clone, Default)]
+pub struct NetlistBuilder {
+    nodes: HashMap<String, NodeId>,
+    element_names: HashSet<String>,
+    model_ids: HashMap<String, usize>,
+    models: MosfetModels,
+    basic_elements: Vec<BasicElement>,
+    time_varing_linear_elements: Vec<TimeVaringLinearElement>,
+    time_varing_non_linear_elements: Vec<TimeVaringNonLinearElement>,
+    mutual_inductances: Vec<MutualInductance>,
+    /// Model names referenced by each MOSFET, checked when building.
+    mosfet_models: Vec<String>,
+    error: Option<String>,
+}
+
+impl NetlistBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resistor(mut self, name: &str, node_in: &str, node_out: &str, value: f64) -> Self {
+        if self.check_name(name) && self.check_positive(name, value, "resistance") {
+            let (node_in, node_out) = (self.get_node(node_in), self.get_node(node_out));
+            self.basic_elements.push(BasicElement::new(
+                name.to_string(),
+                node_in,
+                node_out,
+                BasicElementType::Resistor(ResistorValue::R(value)),
+            ));
+        }
+        self
+    }
+
+    pub fn capacitor(mut self, name: &str, node_in: &str, node_out: &str, value: f64) -> Self {
+        if self.check_name(name) && self.check_positive(name, value, "capacitance") {
+            let (node_in, node_out) = (self.get_node(node_in), self.get_node(node_out));
+            self.time_varing_linear_elements
+                .push(TimeVaringLinearElement::new(
+                    name.to_string(),
+                    node_in,
+                    node_out,
+                    TimeVaringLinearElementType::Capacitor(value),
+                ));
+        }
+        self
+    }
+
+    pub fn inductor(mut self, name: &str, node_in: &str, node_out: &str, value: f64) -> Self {
+        if self.check_name(name) && self.check_positive(name, value, "inductance") {
+            let (node_in, node_out) = (self.get_node(node_in), self.get_node(node_out));
+            self.time_varing_linear_elements
+                .push(TimeVaringLinearElement::new(
+                    name.to_string(),
+                    node_in,
+                    node_out,
+                    TimeVaringLinearElementType::Inductor(value),
+                ));
+        }
+        self
+    }
+
+    /// Lossless line from the port `nodes[0]`-`nodes[1]` to the port
+    /// `nodes[2]`-`nodes[3]` with impedance `z0` and delay `td`.
+    pub fn transmission_line(mut self, name: &str, nodes: [&str; 4], z0: f64, td: f64) -> Self {
+        if self.check_name(name)
+            && self.check_positive(name, z0, "impedance")
+            && self.check_positive(name, td, "delay")
+        {
+            let [node_in, node_out, node_in_2, node_out_2] = nodes.map(|node| self.get_node(node));
+            self.time_varing_linear_elements
+                .push(TimeVaringLinearElement::new(
+                    name.to_string(),
+                    node_in,
+                    node_out,
+                    TimeVaringLinearElementType::TransmissionLine(TransmissionLine {
+                        node_in_2,
+                        node_out_2,
+                        z0,
+                        td,
+                    }),
+                ));
+        }
+        self
+    }
+
+    /// Couple the inductors `inductor_1` and `inductor_2` with coefficient
+    /// `k`. The inductors may be added later.
+    pub fn coupling(mut self, name: &str, inductor_1: &str, inductor_2: &str, k: f64) -> Self {
+        if self.check_name(name) {
+            match MutualInductance::new(
+                name.to_string(),
+                [inductor_1.to_string(), inductor_2.to_string()],
+                k,
+            ) {
+                Ok(mutual_inductance) => self.mutual_inductances.push(mutual_inductance),
+                Err(e) => self.set_error(e),
+            }
+        }
+        self
+    }
+
+    /// DC voltage source with its positive terminal at `node_pos`.
+    pub fn vsource(mut self, name: &str, node_pos: &str, node_neg: &str, value: f64) -> Self {
+        if self.check_name(name) && self.check_finite(name, value, "voltage") {
+            let (node_pos, node_neg) = (self.get_node(node_pos), self.get_node(node_neg));
+            self.basic_elements.push(BasicElement::new(
+                name.to_string(),
+                node_pos,
+                node_neg,
+                BasicElementType::VoltageSource(SourceType::DC, value, Cell::new(0)),
+            ));
+        }
+        self
+    }
+
+    /// DC current source driving `value` from `node_pos` through the source
+    /// to `node_neg`.
+    pub fn isource(mut self, name: &str, node_pos: &str, node_neg: &str, value: f64) -> Self {
+        if self.check_name(name) && self.check_finite(name, value, "current") {
+            let (node_pos, node_neg) = (self.get_node(node_pos), self.get_node(node_neg));
+            self.basic_elements.push(BasicElement::new(
+                name.to_string(),
+                node_pos,
+                node_neg,
+                BasicElementType::CurrentSource(SourceType::DC, value),
+            ));
+        }
+        self
+    }
+
+    /// MOSFET with drain, gate, source and optionally bulk `nodes`, using the
+    /// model added with [`NetlistBuilder::model`] under `model`. Without a
+    /// bulk node the bulk is tied to the source.
+    pub fn mosfet(
+        mut self,
+        name: &str,
+        nodes: &[&str],
+        mos_type: MosfetType,
+        w: f64,
+        l: f64,
+        model: &str,
+    ) -> Self {
+        if self.check_name(name)
+            && self.check_positive(name, w, "width")
+            && self.check_positive(name, l, "length")
+        {
+            let nodes = match *nodes {
+                [d, g, s] => [d, g, s, s],
+                [d, g, s, b] => [d, g, s, b],
+                _ => {
+                    self.set_error(format!("Invalid node count of {}: {}", name, nodes.len()));
+                    return self;
+                }
+            }
+            .map(|node| self.get_node(node));
+            let model_num = self.model_ids.len();
+            let model_id = *self
+                .model_ids
+                .entry(model.to_ascii_uppercase())
+                .or_insert(model_num);
+            self.time_varing_non_linear_elements
+                .push(TimeVaringNonLinearElement::new_mosfet(
+                    name.to_string(),
+                    mos_type,
+                    nodes,
+                    w,
+                    l,
+                    model_id,
+                ));
+            self.mosfet_models.push(model.to_string());
+        }
+        self
+    }
+
+    /// Add the MOSFET model `name`, which may be used before it is added.
+    pub fn model(mut self, name: &str, model: MosfetModel) -> Self {
+        let model_num = self.model_ids.len();
+        let model_id = *self
+            .model_ids
+            .entry(name.to_ascii_uppercase())
+            .or_insert(model_num);
+        if self.models.insert(model_id, model).is_some() {
+            self.set_error(format!("Duplicate MOSFET model: {}", name));
+        }
+        self
+    }
+
+    /// Get the ID of the node `name`, if any element uses it.
+    pub fn get_node_id(&self, name: &str) -> Option<NodeId> {
+        if is_ground(name) {
+            return Some(0);
+        }
+        self.nodes.get(name).copied()
+    }
+
+    pub fn build(&self) -> Result<Netlist, String> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+
+        let mut time_varing_non_linear_elements = self.time_varing_non_linear_elements.clone();
+        for (mosfet, model) in time_varing_non_linear_elements
+            .iter_mut()
+            .zip(self.mosfet_models.iter())
+        {
+            mosfet
+                .bind_model(&self.models, &SwitchModels::new())
+                .map_err(|_| format!("Unknown MOSFET model: {}", model))?;
+        }
+
+        for mutual_inductance in self.mutual_inductances.iter() {
+            mutual_inductance.get_mutual_inductance(&self.time_varing_linear_elements)?;
+        }
+
+        Ok(Netlist {
+            node_num: Cell::new(self.nodes.len() + 1),
+            basic_elements: self.basic_elements.clone(),
+            time_varing_linear_elements: self.time_varing_linear_elements.clone(),
+            time_varing_non_linear_elements,
+            mutual_inductances: self.mutual_inductances.clone(),
+            node_names: self.get_node_names(),
+        })
+    }
+
+    /// Get the node names in order of their IDs.
+    fn get_node_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.nodes.len()];
+        for (name, id) in self.nodes.iter() {
+            names[id - 1] = name.clone();
+        }
+        names
+    }
+
+    fn get_node(&mut self, name: &str) -> NodeId {
+        if is_ground(name) {
+            return 0;
+        }
+        let node_num = self.nodes.len();
+        *self.nodes.entry(name.to_string()).or_insert(node_num + 1)
+    }
+
+    /// Check that no error has occurred and `name` is a new element name.
+    fn check_name(&mut self, name: &str) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            self.set_error(format!("Invalid element name: {:?}", name));
+            return false;
+        }
+        if !self.element_names.insert(name.to_ascii_uppercase()) {
+            self.set_error(format!("Duplicate element: {}", name));
+            return false;
+        }
+        true
+    }
+
+    fn check_finite(&mut self, name: &str, value: f64, what: &str) -> bool {
+        if !value.is_finite() {
+            self.set_error(format!("Invalid {} of {}: {}", what, name, value));
+        }
+        value.is_finite()
+    }
+
+    fn check_positive(&mut self, name: &str, value: f64, what: &str) -> bool {
+        self.check_finite(name, value, what) && {
+            if value <= 0. {
+                self.set_error(format!("Invalid {} of {}: {}", what, name, value));
+            }
+            value > 0.
+        }
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+}
+
+fn is_ground(name: &str) -> bool {
+    name == "0" || name.eq_ignore_ascii_case("gnd")
+}
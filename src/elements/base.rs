@@ -1,6 +1,7 @@
 use crate::{
     matrix::build::{MatrixTriplets, VecItems},
-    netlist::NodeId,
+    netlist::{NodeId, Unknowns},
+    solver::base::ConvergenceOptions,
 };
 
 use sprs::{CsMat, CsVec};
@@ -9,6 +10,23 @@ pub trait Element: MatrixSettable {
     fn get_name(&self) -> &str;
 
     fn get_nodes(&self) -> Vec<NodeId>;
+
+    /// Get the node pairs the element connects and how, for topology checks.
+    fn get_branches(&self) -> Vec<(NodeId, NodeId, BranchKind)>;
+}
+
+/// How an element constrains the pair of nodes it connects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchKind {
+    /// Conducts at DC with a finite conductance, e.g. a resistor.
+    Conductive,
+    /// Fixes the voltage across it at DC: voltage sources and inductors.
+    VoltageDefined,
+    /// Fixes the current through it: current sources.
+    CurrentDefined,
+    /// Open at DC: capacitors, MOSFET gates and the control inputs of
+    /// switches and behavioral sources.
+    Capacitive,
 }
 
 pub trait TwoPortElement: Element {
@@ -35,10 +53,62 @@ pub trait MatrixTransUpdatable {
     fn update_matrix_trans(&self, mat: &mut CsMat<f64>, v: &mut CsVec<f64>, x: &CsVec<f64>);
 }
 
+/// Residual check for nonlinear devices: the device current evaluated at `x`
+/// must agree with its linearization around `x_prev`.
+pub trait ConvergenceCheckable {
+    fn is_converged(
+        &self,
+        x_prev: &CsVec<f64>,
+        x: &CsVec<f64>,
+        options: &ConvergenceOptions,
+    ) -> bool;
+}
+
+/// Current and power of an element at a solved operating point.
+pub trait OpReportable {
+    /// Get the current through the element, flowing from its first node to
+    /// its second one, and the power it absorbs at solution `x`.
+    fn get_current_power(&self, x: &CsVec<f64>, unknowns: &Unknowns) -> (f64, f64);
+}
+
 pub trait NonLinearElement: Element + MatrixDcUpdatable {}
 
 pub trait TimeVaringNonLinearElement: Element + MatrixTransUpdatable + MatrixDcUpdatable {}
 
+/// Temperature in degrees Celsius at which element parameters are given.
+pub const NOMINAL_TEMPERATURE: f64 = 27.;
+
+/// Parse the value of a `KEY=VALUE` parameter of a card or directive.
+pub fn parse_parameter_value(key: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid value for {}: {}", key, value))
+}
+
+/// Parse `KEY=VALUE` instance parameters such as `TEMP=85`, with upper-case
+/// keys. Returns `None` if a token is not such a pair.
+pub fn parse_instance_parameters<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+) -> Option<Vec<(String, f64)>> {
+    tokens
+        .map(|token| {
+            let (key, value) = token.split_once('=')?;
+            let key = key.to_ascii_uppercase();
+            let value = parse_parameter_value(&key, value).ok()?;
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Format `value` for a netlist card so that it parses back exactly.
+pub fn format_value(value: f64) -> String {
+    if value == 0. || (1e-3..1e6).contains(&value.abs()) {
+        format!("{}", value)
+    } else {
+        format!("{:e}", value)
+    }
+}
+
 pub fn general_element_parse(s: &str) -> Option<(String, NodeId, NodeId, f64)> {
     let mut iter = s.split_whitespace();
 
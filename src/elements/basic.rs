@@ -1,11 +1,15 @@
 use std::cell::Cell;
+use std::fmt;
 
 use sprs::{CsMat, CsVec};
 
-use super::base::{Element, MatrixSettable, MatrixTransUpdatable};
+use super::base::{
+    format_value, parse_instance_parameters, BranchKind, Element, MatrixSettable,
+    MatrixTransUpdatable, OpReportable, NOMINAL_TEMPERATURE,
+};
 use crate::matrix::build::VecPushWithNodeId;
 use crate::matrix::ext::{MatExt, VecExt};
-use crate::netlist::NodeId;
+use crate::netlist::{NodeId, Unknowns};
 
 #[derive(Debug, Clone)]
 pub enum SourceType {
@@ -15,11 +19,31 @@ pub enum SourceType {
 
 #[derive(Debug, Clone)]
 pub enum ResistorValue {
-    #[allow(dead_code)]
     R(f64),
     G(f64),
 }
 
+/// Temperature dependence of a resistor,
+/// `R(T) = R(TNOM) * (1 + TC1 * dT + TC2 * dT^2)`.
+#[derive(Debug, Clone)]
+pub struct ResistorTemperature {
+    /// Resistance at the nominal temperature.
+    pub nominal: f64,
+    pub tc1: f64,
+    pub tc2: f64,
+    /// Instance temperature, overriding the circuit temperature.
+    pub temp: Option<f64>,
+}
+
+impl SourceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceType::AC => "AC",
+            SourceType::DC => "DC",
+        }
+    }
+}
+
 impl ResistorValue {
     pub fn get_g(&self) -> f64 {
         match self {
@@ -58,6 +82,8 @@ pub struct BasicElement {
     node_in: NodeId,
     node_out: NodeId,
     element_type: BasicElementType,
+    /// Set for resistors with `TC1`, `TC2` or `TEMP`.
+    temperature: Option<ResistorTemperature>,
 }
 
 impl BasicElement {
@@ -72,6 +98,7 @@ impl BasicElement {
             node_in,
             node_out,
             element_type,
+            temperature: None,
         }
     }
 
@@ -89,6 +116,15 @@ impl Element for BasicElement {
     fn get_nodes(&self) -> Vec<NodeId> {
         vec![self.node_in, self.node_out]
     }
+
+    fn get_branches(&self) -> Vec<(NodeId, NodeId, BranchKind)> {
+        let kind = match self.element_type {
+            BasicElementType::Resistor(_) => BranchKind::Conductive,
+            BasicElementType::VoltageSource(..) => BranchKind::VoltageDefined,
+            BasicElementType::CurrentSource(..) => BranchKind::CurrentDefined,
+        };
+        vec![(self.node_in, self.node_out, kind)]
+    }
 }
 
 impl BasicElement {
@@ -127,6 +163,16 @@ impl BasicElement {
         }
     }
 
+    /// Scale a temperature-dependent resistor to the circuit temperature
+    /// `temp`, unless it has its own.
+    pub fn set_temperature(&mut self, temp: f64) {
+        if let Some(t) = &self.temperature {
+            let delta = t.temp.unwrap_or(temp) - NOMINAL_TEMPERATURE;
+            let r = t.nominal * (1. + t.tc1 * delta + t.tc2 * delta * delta);
+            self.set_resistor_value(ResistorValue::R(r));
+        }
+    }
+
     pub fn set_resistor_value(&mut self, value: ResistorValue) {
         match &mut self.element_type {
             BasicElementType::Resistor(val) => {
@@ -158,8 +204,7 @@ impl BasicElement {
         mat: &mut crate::matrix::build::MatrixTriplets<f64>,
         v: &mut crate::matrix::build::VecItems<f64>,
     ) {
-        let new_pos = mat.size;
-        mat.extend_size(1);
+        let new_pos = mat.append_branch(self.get_name());
 
         self.element_type.set_extra_node(new_pos + 1);
 
@@ -271,15 +316,81 @@ impl MatrixTransUpdatable for BasicElement {
     }
 }
 
+impl OpReportable for BasicElement {
+    fn get_current_power(&self, x: &CsVec<f64>, unknowns: &Unknowns) -> (f64, f64) {
+        let v = x.get_by_node_id(self.node_in) - x.get_by_node_id(self.node_out);
+        let current = match &self.element_type {
+            BasicElementType::Resistor(value) => value.get_g() * v,
+            BasicElementType::VoltageSource(..) => unknowns
+                .get_branch_index(self.get_name())
+                .and_then(|i| x.get(i).copied())
+                .unwrap_or(0.),
+            BasicElementType::CurrentSource(_, value) => *value,
+        };
+        (current, v * current)
+    }
+}
+
+impl fmt::Display for BasicElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.node_in, self.node_out)?;
+        if let Some(t) = &self.temperature {
+            write!(
+                f,
+                " {} TC1={} TC2={}",
+                format_value(t.nominal),
+                format_value(t.tc1),
+                format_value(t.tc2)
+            )?;
+            if let Some(temp) = t.temp {
+                write!(f, " TEMP={}", format_value(temp))?;
+            }
+            return Ok(());
+        }
+        match &self.element_type {
+            BasicElementType::Resistor(ResistorValue::R(r)) => write!(f, " {}", format_value(*r)),
+            BasicElementType::Resistor(ResistorValue::G(g)) => {
+                write!(f, " {}", format_value(1. / g))
+            }
+            BasicElementType::VoltageSource(source_type, value, _)
+            | BasicElementType::CurrentSource(source_type, value) => {
+                write!(f, " {} {}", source_type.as_str(), format_value(*value))
+            }
+        }
+    }
+}
+
 impl BasicElement {
+    /// Parse `R<name> n+ n- value [TC1=..] [TC2=..] [TEMP=..]`.
     pub fn parse_resistor(s: &str) -> Option<Self> {
         let (name, node_in, node_out, val) = super::base::general_element_parse(s)?;
-        Some(Self {
+        let parameters = parse_instance_parameters(s.split_whitespace().skip(4))?;
+
+        let mut temperature = None;
+        for (key, value) in parameters {
+            let t = temperature.get_or_insert(ResistorTemperature {
+                nominal: val,
+                tc1: 0.,
+                tc2: 0.,
+                temp: None,
+            });
+            match key.as_str() {
+                "TC1" => t.tc1 = value,
+                "TC2" => t.tc2 = value,
+                "TEMP" => t.temp = Some(value),
+                _ => return None,
+            }
+        }
+
+        let mut resistor = Self::new(
             name,
             node_in,
             node_out,
-            element_type: BasicElementType::Resistor(ResistorValue::G(1. / val)),
-        })
+            BasicElementType::Resistor(ResistorValue::R(val)),
+        );
+        resistor.temperature = temperature;
+        resistor.set_temperature(NOMINAL_TEMPERATURE);
+        Some(resistor)
     }
 
     pub fn parse_voltage_source(s: &str) -> Option<Self> {
@@ -294,12 +405,12 @@ impl BasicElement {
         };
         let value = iter.next()?.parse::<f64>().unwrap();
 
-        Some(Self {
+        Some(Self::new(
             name,
             node_in,
             node_out,
-            element_type: BasicElementType::VoltageSource(source_type, value, Cell::new(0)),
-        })
+            BasicElementType::VoltageSource(source_type, value, Cell::new(0)),
+        ))
     }
 
     pub fn parse_current_source(s: &str) -> Option<Self> {
@@ -314,11 +425,11 @@ impl BasicElement {
         };
         let value = iter.next()?.parse::<f64>().unwrap();
 
-        Some(Self {
+        Some(Self::new(
             name,
             node_in,
             node_out,
-            element_type: BasicElementType::CurrentSource(source_type, value),
-        })
+            BasicElementType::CurrentSource(source_type, value),
+        ))
     }
 }
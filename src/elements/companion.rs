@@ -1,27 +1,53 @@
 use sprs::CsVec;
 use std::cell::Cell;
+use std::collections::VecDeque;
 
 use crate::elements::base::Element;
 use crate::elements::basic::ResistorValue;
-use crate::matrix::ext::VecExt;
-use crate::netlist::Netlist;
+use crate::matrix::ext::{MatExt, VecExt};
+use crate::netlist::{Netlist, NodeId};
 
 use super::base::{MatrixSettable, MatrixTransUpdatable};
 use super::basic::{BasicElement, BasicElementType, SourceType};
-use super::time_varing_linear::{TimeVaringLinearElement, TimeVaringLinearElementType};
+use super::time_varing_linear::{
+    MutualInductance, TimeVaringLinearElement, TimeVaringLinearElementType, TransmissionLine,
+};
 use super::time_varing_non_linear::TimeVaringNonLinearElement;
 
+/// Smallest capacitance of a nonlinear device, which keeps its companion
+/// resistor finite.
+const MIN_CAPACITANCE: f64 = 1e-18;
+
 #[derive(Debug)]
 pub enum TimeVaringElement<'a> {
     Linear(&'a TimeVaringLinearElement),
-    #[allow(dead_code)]
-    NonLinear(&'a TimeVaringNonLinearElement),
+    /// One capacitance of a nonlinear device, by its index in
+    /// `get_capacitance_nodes`.
+    NonLinear(&'a TimeVaringNonLinearElement, usize),
+    Coupling(CoupledInductors),
+}
+
+/// The mutual inductance of two inductors, stamped between the branch
+/// current rows of their companion models.
+#[derive(Debug)]
+pub struct CoupledInductors {
+    /// Names of the companion voltage sources of the inductors.
+    sources: [String; 2],
+    mutual: f64,
+    /// Branch current positions of the sources, as node IDs.
+    branches: Cell<[NodeId; 2]>,
 }
 
 #[derive(Debug)]
 pub struct CompanionModel<'a> {
     element: TimeVaringElement<'a>,
     current: f64,
+    /// Resistance between branch current rows: `2 L / dt` of an inductor,
+    /// `2 M / dt` of a coupling or `Z0` of a transmission line.
+    resistance: f64,
+    /// Port voltages and currents `[v1, i1, v2, i2]` of a transmission line
+    /// at the accepted time points no older than its delay, plus one.
+    history: VecDeque<(f64, [f64; 4])>,
     companion_elements: Vec<BasicElement>,
 }
 
@@ -29,52 +55,71 @@ pub trait InitCompanionElements {
     fn init_companion_elements(&self, netlist: &Netlist) -> Vec<BasicElement>;
 }
 
+/// A resistor in series with a voltage source, which models a capacitor
+/// named `name` between `node_in` and `node_out`.
+fn init_capacitor_companion_elements(
+    name: &str,
+    node_in: NodeId,
+    node_out: NodeId,
+    netlist: &Netlist,
+) -> Vec<BasicElement> {
+    let new_node = netlist.append_new_node();
+    vec![
+        BasicElement::new(
+            format!("{}-R", name),
+            node_in,
+            new_node,
+            BasicElementType::Resistor(ResistorValue::R(1e10)),
+        ),
+        BasicElement::new(
+            format!("{}-V", name),
+            new_node,
+            node_out,
+            BasicElementType::VoltageSource(SourceType::DC, 0., Cell::new(0)),
+        ),
+    ]
+}
+
 impl InitCompanionElements for TimeVaringLinearElement {
     fn init_companion_elements(&self, netlist: &Netlist) -> Vec<BasicElement> {
         match self.get_element_type() {
-            TimeVaringLinearElementType::Capacitor(_val) => {
-                let new_node = netlist.append_new_node();
-                vec![
-                    BasicElement::new(
-                        format!("{}-R", self.get_name()),
-                        self.get_node_in(),
-                        new_node,
-                        BasicElementType::Resistor(ResistorValue::R(1e10)),
-                    ),
-                    BasicElement::new(
-                        format!("{}-V", self.get_name()),
-                        new_node,
-                        self.get_node_out(),
-                        BasicElementType::VoltageSource(SourceType::DC, 0., Cell::new(0)),
-                    ),
-                ]
-            }
-            TimeVaringLinearElementType::Inductor(_val) => {
-                vec![
-                    BasicElement::new(
-                        format!("{}-R", self.get_name()),
-                        self.get_node_in(),
-                        self.get_node_out(),
-                        BasicElementType::Resistor(ResistorValue::G(0.)),
-                    ),
-                    BasicElement::new(
-                        format!("{}-I", self.get_name()),
-                        self.get_node_in(),
-                        self.get_node_out(),
-                        BasicElementType::CurrentSource(SourceType::DC, 0.),
-                    ),
-                ]
-            }
+            TimeVaringLinearElementType::Capacitor(_val) => init_capacitor_companion_elements(
+                self.get_name(),
+                self.get_node_in(),
+                self.get_node_out(),
+                netlist,
+            ),
+            // A voltage source whose branch row also carries the resistance
+            // of the inductor, so its current stays an unknown that
+            // couplings can refer to. It is named after the inductor, as
+            // the branch of the inductor at DC is.
+            TimeVaringLinearElementType::Inductor(_val) => vec![BasicElement::new(
+                self.get_name().to_string(),
+                self.get_node_in(),
+                self.get_node_out(),
+                BasicElementType::VoltageSource(SourceType::DC, 0., Cell::new(0)),
+            )],
+            // Each port is the characteristic impedance in series with the
+            // wave arriving from the other port, both on the branch row of
+            // a voltage source named like the port branch at DC.
+            TimeVaringLinearElementType::TransmissionLine(line) => vec![
+                BasicElement::new(
+                    format!("{}-1", self.get_name()),
+                    self.get_node_in(),
+                    self.get_node_out(),
+                    BasicElementType::VoltageSource(SourceType::DC, 0., Cell::new(0)),
+                ),
+                BasicElement::new(
+                    format!("{}-2", self.get_name()),
+                    line.node_in_2,
+                    line.node_out_2,
+                    BasicElementType::VoltageSource(SourceType::DC, 0., Cell::new(0)),
+                ),
+            ],
         }
     }
 }
 
-impl InitCompanionElements for TimeVaringNonLinearElement {
-    fn init_companion_elements(&self, _netlist: &Netlist) -> Vec<BasicElement> {
-        todo!()
-    }
-}
-
 impl TimeVaringLinearElement {
     fn is_capacitor(&self) -> bool {
         matches!(
@@ -89,22 +134,150 @@ impl TimeVaringLinearElement {
             TimeVaringLinearElementType::Inductor(_)
         )
     }
+
+    fn get_transmission_line(&self) -> Option<&TransmissionLine> {
+        match self.get_element_type() {
+            TimeVaringLinearElementType::TransmissionLine(line) => Some(line),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> CompanionModel<'a> {
     pub fn new_from_linear(element: &'a TimeVaringLinearElement, netlist: &Netlist) -> Self {
         let companion_elements = element.init_companion_elements(netlist);
+        // The analysis starts from zero, which is also the past of a line.
+        let (resistance, history) = match element.get_transmission_line() {
+            Some(line) => (line.z0, VecDeque::from([(0., [0.; 4])])),
+            None => (0., VecDeque::new()),
+        };
         Self {
             element: TimeVaringElement::Linear(element),
             current: 0.,
+            resistance,
+            history,
             companion_elements,
         }
     }
 
+    /// Create one companion model per capacitance of `element` between two
+    /// distinct nodes.
+    pub fn new_from_non_linear(
+        element: &'a TimeVaringNonLinearElement,
+        netlist: &Netlist,
+    ) -> Vec<Self> {
+        element
+            .get_capacitance_nodes()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (_, node_in, node_out))| node_in != node_out)
+            .map(|(index, (name, node_in, node_out))| Self {
+                element: TimeVaringElement::NonLinear(element, index),
+                current: 0.,
+                resistance: 0.,
+                history: VecDeque::new(),
+                companion_elements: init_capacitor_companion_elements(
+                    &format!("{}-{}", element.get_name(), name),
+                    node_in,
+                    node_out,
+                    netlist,
+                ),
+            })
+            .collect()
+    }
+
+    /// Create the coupling of the companion models of two inductors in
+    /// `netlist`, which must have been checked to exist.
+    pub fn new_from_coupling(element: &MutualInductance, netlist: &Netlist) -> Self {
+        let linear_elements = &netlist.time_varing_linear_elements;
+        let mutual = element
+            .get_mutual_inductance(linear_elements)
+            .expect("Invalid mutual inductance");
+        let sources = element.get_inductors().each_ref().map(|name| {
+            let inductor = linear_elements
+                .iter()
+                .find(|e| e.is_inductor() && e.get_name().eq_ignore_ascii_case(name))
+                .expect("Unknown inductor");
+            inductor.get_name().to_string()
+        });
+        Self {
+            element: TimeVaringElement::Coupling(CoupledInductors {
+                sources,
+                mutual,
+                branches: Cell::new([0; 2]),
+            }),
+            current: 0.,
+            resistance: 0.,
+            history: VecDeque::new(),
+            companion_elements: Vec::new(),
+        }
+    }
+
+    /// Get the longest time step the model allows: the delay of a
+    /// transmission line, so that the waves arriving within a step have
+    /// already been computed.
+    pub fn get_max_step(&self) -> Option<f64> {
+        match self.element {
+            TimeVaringElement::Linear(element) => {
+                element.get_transmission_line().map(|line| line.td)
+            }
+            _ => None,
+        }
+    }
+
+    fn get_transmission_line(&self) -> Option<&TransmissionLine> {
+        match self.element {
+            TimeVaringElement::Linear(element) => element.get_transmission_line(),
+            _ => None,
+        }
+    }
+
+    /// Get the port voltages and currents of a transmission line at `time`,
+    /// interpolated between the accepted time points.
+    fn get_history(&self, time: f64) -> [f64; 4] {
+        let next = self.history.partition_point(|(t, _)| *t < time);
+        if next == 0 {
+            return self.history[0].1;
+        }
+        let (t_prev, prev) = self.history[next - 1];
+        let Some(&(t_next, next)) = self.history.get(next) else {
+            return prev;
+        };
+        let ratio = (time - t_prev) / (t_next - t_prev);
+        std::array::from_fn(|i| prev[i] + ratio * (next[i] - prev[i]))
+    }
+
+    /// Get the positions of the port currents of a transmission line, as
+    /// node IDs.
+    fn get_port_branches(&self) -> [NodeId; 2] {
+        assert!(self.get_transmission_line().is_some());
+        [0, 1].map(|i| {
+            self.companion_elements[i]
+                .get_element_type()
+                .get_extra_node()
+        })
+    }
+
+    /// Get the node between the resistor and the source of a capacitor
+    /// model and the position of the source current, as node IDs. Neither
+    /// is part of the netlist.
+    pub fn get_internal_unknowns(&self) -> Option<[NodeId; 2]> {
+        if !self.is_capacitor() {
+            return None;
+        }
+        let node = self.get_companion_resistor().get_nodes()[1];
+        let branch = self
+            .get_companion_voltage_source()
+            .get_element_type()
+            .get_extra_node();
+        Some([node, branch])
+    }
+
     fn is_capacitor(&self) -> bool {
         match self.element {
             TimeVaringElement::Linear(element) => element.is_capacitor(),
-            _ => false,
+            TimeVaringElement::NonLinear(..) => true,
+            TimeVaringElement::Coupling(_) => false,
         }
     }
 
@@ -115,97 +288,76 @@ impl<'a> CompanionModel<'a> {
         }
     }
 
-    fn get_time_varing_element(&self) -> &TimeVaringElement {
+    fn get_time_varing_element(&self) -> &TimeVaringElement<'_> {
         &self.element
     }
 
     fn get_companion_resistor_mut(&mut self) -> &mut BasicElement {
-        assert!(self.is_capacitor() || self.is_inductor());
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Capacitor(_) => &mut self.companion_elements[0],
-                TimeVaringLinearElementType::Inductor(_) => &mut self.companion_elements[0],
-            },
-            _ => panic!("Not a linear element"),
-        }
+        assert!(self.is_capacitor());
+        &mut self.companion_elements[0]
     }
 
     fn get_companion_resistor(&self) -> &BasicElement {
-        assert!(self.is_capacitor() || self.is_inductor());
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Capacitor(_) => &self.companion_elements[0],
-                TimeVaringLinearElementType::Inductor(_) => &self.companion_elements[0],
-            },
-            _ => panic!("Not a linear element"),
-        }
+        assert!(self.is_capacitor());
+        &self.companion_elements[0]
     }
 
-    fn get_base_value(&self) -> f64 {
+    /// Get the capacitance or inductance, evaluated at solution `x` for
+    /// nonlinear devices, or the mutual inductance of a coupling.
+    fn get_base_value(&self, x: &CsVec<f64>) -> f64 {
         match self.get_time_varing_element() {
             TimeVaringElement::Linear(element) => element.get_base_value(),
-            _ => todo!(),
-        }
-    }
-
-    fn get_companion_current_source_mut(&mut self) -> &mut BasicElement {
-        assert!(self.is_capacitor() || self.is_inductor());
-
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Inductor(_) => &mut self.companion_elements[1],
-                TimeVaringLinearElementType::Capacitor(_) => {
-                    panic!("The companion model of an capacitor does not have a current source")
-                }
-            },
-            _ => panic!("Not a linear element"),
+            TimeVaringElement::NonLinear(element, index) => {
+                element.get_capacitances(x)[*index].max(MIN_CAPACITANCE)
+            }
+            TimeVaringElement::Coupling(coupling) => coupling.mutual,
         }
     }
 
-    fn get_companion_current_source(&self) -> &BasicElement {
-        assert!(self.is_capacitor() || self.is_inductor());
-
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Inductor(_) => &self.companion_elements[1],
-                TimeVaringLinearElementType::Capacitor(_) => {
-                    panic!("The companion model of an capacitor does not have a current source")
-                }
-            },
-            _ => panic!("Not a linear element"),
+    fn get_nodes(&self) -> (NodeId, NodeId) {
+        match self.get_time_varing_element() {
+            TimeVaringElement::Linear(element) => (element.get_node_in(), element.get_node_out()),
+            TimeVaringElement::NonLinear(element, index) => {
+                let (_, node_in, node_out) = element.get_capacitance_nodes()[*index];
+                (node_in, node_out)
+            }
+            TimeVaringElement::Coupling(_) => panic!("A coupling has no terminals"),
         }
     }
 
+    /// The voltage source is the last companion element.
     fn get_companion_voltage_source_mut(&mut self) -> &mut BasicElement {
         assert!(self.is_capacitor() || self.is_inductor());
-
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Inductor(_) => {
-                    panic!("The companion model of a inductor does not have a voltage source")
-                }
-                TimeVaringLinearElementType::Capacitor(_) => &mut self.companion_elements[1],
-            },
-            _ => panic!("Not a linear element"),
-        }
+        self.companion_elements.last_mut().unwrap()
     }
 
     fn get_companion_voltage_source(&self) -> &BasicElement {
         assert!(self.is_capacitor() || self.is_inductor());
+        self.companion_elements.last().unwrap()
+    }
 
-        match self.element {
-            TimeVaringElement::Linear(element) => match element.get_element_type() {
-                TimeVaringLinearElementType::Inductor(_) => {
-                    panic!("The companion model of a inductor does not have a voltage source")
-                }
-                TimeVaringLinearElementType::Capacitor(_) => &self.companion_elements[1],
-            },
-            _ => panic!("Not a linear element"),
-        }
+    /// Get the position of the inductor current, as a node ID.
+    fn get_branch(&self) -> NodeId {
+        assert!(self.is_inductor());
+        self.get_companion_voltage_source()
+            .get_element_type()
+            .get_extra_node()
     }
 
-    pub fn update_companion_elements(&mut self, x: &CsVec<f64>, delta_t: f64) {
-        let base_value = self.get_base_value();
+    /// Update the companion elements for a step of `delta_t` from the
+    /// solution `x` at `time`.
+    pub fn update_companion_elements(&mut self, x: &CsVec<f64>, time: f64, delta_t: f64) {
+        if let Some(line) = self.get_transmission_line() {
+            // The wave leaving one port arrives at the other after the
+            // delay.
+            let [v1, i1, v2, i2] = self.get_history(time + delta_t - line.td);
+            let z0 = line.z0;
+            self.companion_elements[0].set_base_value(v2 + z0 * i2);
+            self.companion_elements[1].set_base_value(v1 + z0 * i1);
+            return;
+        }
+
+        let base_value = self.get_base_value(x);
         let current = self.current;
 
         match self.get_time_varing_element() {
@@ -223,21 +375,56 @@ impl<'a> CompanionModel<'a> {
                     let v_diff = x.get_by_node_id(element.get_node_in())
                         - x.get_by_node_id(element.get_node_out());
 
-                    let resistor = self.get_companion_resistor_mut();
-                    resistor.set_resistor_value(ResistorValue::G(delta_t / (2. * base_value)));
-                    let current_source = self.get_companion_current_source_mut();
-                    current_source.set_base_value(current + delta_t * v_diff / (2. * base_value));
+                    self.resistance = 2. * base_value / delta_t;
+                    let resistance = self.resistance;
+                    let voltage_source = self.get_companion_voltage_source_mut();
+                    voltage_source.set_base_value(-v_diff - resistance * current);
                 }
+                TimeVaringLinearElementType::TransmissionLine(_) => unreachable!(),
             },
-            _ => todo!(),
+            TimeVaringElement::NonLinear(..) => {
+                let (node_in, node_out) = self.get_nodes();
+                let v_diff = x.get_by_node_id(node_in) - x.get_by_node_id(node_out);
+
+                // Backward Euler with the capacitance at the last accepted
+                // time point. Unlike the trapezoidal rule, it carries no
+                // current over from a step with a different capacitance.
+                let resistor = self.get_companion_resistor_mut();
+                resistor.set_resistor_value(ResistorValue::R(delta_t / base_value));
+                let voltage_source = self.get_companion_voltage_source_mut();
+                voltage_source.set_base_value(v_diff);
+            }
+            TimeVaringElement::Coupling(_) => {
+                self.resistance = 2. * base_value / delta_t;
+            }
         }
     }
 
-    pub fn update_current(&mut self, x: &CsVec<f64>) {
-        let (node_in, node_out) = match self.get_time_varing_element() {
-            TimeVaringElement::Linear(element) => (element.get_node_in(), element.get_node_out()),
-            _ => todo!(),
-        };
+    /// Keep the state of the accepted solution `x` at `time`.
+    pub fn update_current(&mut self, x: &CsVec<f64>, time: f64) {
+        if let TimeVaringElement::Coupling(_) = self.element {
+            return;
+        }
+        if let Some(&line) = self.get_transmission_line() {
+            let [branch_1, branch_2] = self.get_port_branches();
+            let ports = [self.get_nodes(), (line.node_in_2, line.node_out_2)]
+                .map(|(node_in, node_out)| x.get_by_node_id(node_in) - x.get_by_node_id(node_out));
+            self.history.push_back((
+                time,
+                [
+                    ports[0],
+                    x.get_by_node_id(branch_1),
+                    ports[1],
+                    x.get_by_node_id(branch_2),
+                ],
+            ));
+            // Later steps only look back to `time - td` or after.
+            while self.history.len() > 2 && self.history[1].0 <= time - line.td {
+                self.history.pop_front();
+            }
+            return;
+        }
+        let (node_in, node_out) = self.get_nodes();
 
         let v_diff = x.get_by_node_id(node_in) - x.get_by_node_id(node_out);
         let new_current;
@@ -250,14 +437,15 @@ impl<'a> CompanionModel<'a> {
                     new_current = resistor.get_base_value() * v_r;
                 }
                 TimeVaringLinearElementType::Inductor(_val) => {
-                    let resistor = self.get_companion_resistor();
-                    let current_source = self.get_companion_current_source();
-
-                    new_current =
-                        resistor.get_base_value() * v_diff + current_source.get_base_value();
+                    new_current = x.get_by_node_id(self.get_branch());
                 }
+                TimeVaringLinearElementType::TransmissionLine(_) => unreachable!(),
             },
-            _ => todo!(),
+            TimeVaringElement::NonLinear(..) => {
+                let v_r = v_diff - self.get_companion_voltage_source().get_base_value();
+                new_current = self.get_companion_resistor().get_base_value() * v_r;
+            }
+            TimeVaringElement::Coupling(_) => unreachable!(),
         }
         self.current = new_current;
     }
@@ -273,6 +461,26 @@ impl<'a> MatrixTransUpdatable for CompanionModel<'a> {
         for element in &self.companion_elements {
             element.update_matrix_trans(mat, v, x);
         }
+
+        if self.is_inductor() {
+            let branch = self.get_branch();
+            mat.add_by_node_id(branch, branch, -self.resistance);
+        }
+        if self.get_transmission_line().is_some() {
+            for branch in self.get_port_branches() {
+                mat.add_by_node_id(branch, branch, -self.resistance);
+            }
+        }
+        if let TimeVaringElement::Coupling(coupling) = &self.element {
+            // Each inductor voltage gains 2 M / dt times the current step of
+            // the other inductor.
+            let [branch_1, branch_2] = coupling.branches.get();
+            let r = self.resistance;
+            mat.add_by_node_id(branch_1, branch_2, -r);
+            mat.add_by_node_id(branch_2, branch_1, -r);
+            v.add_by_node_id(branch_1, -r * x.get_by_node_id(branch_2));
+            v.add_by_node_id(branch_2, -r * x.get_by_node_id(branch_1));
+        }
     }
 }
 
@@ -295,5 +503,26 @@ impl<'a> MatrixSettable for CompanionModel<'a> {
         for element in &self.companion_elements {
             element.set_matrix_trans(mat, v);
         }
+
+        if self.is_inductor() {
+            let branch = self.get_branch();
+            mat.push_with_node_id(branch, branch, 0.);
+        }
+        if self.get_transmission_line().is_some() {
+            for branch in self.get_port_branches() {
+                mat.push_with_node_id(branch, branch, 0.);
+            }
+        }
+        // The companion models of the inductors come first.
+        if let TimeVaringElement::Coupling(coupling) = &self.element {
+            let branches = coupling.sources.each_ref().map(|source| {
+                mat.get_branch_node_id(source)
+                    .expect("Coupled inductor without a companion model")
+            });
+            let [branch_1, branch_2] = branches;
+            mat.push_with_node_id(branch_1, branch_2, 0.);
+            mat.push_with_node_id(branch_2, branch_1, 0.);
+            coupling.branches.set(branches);
+        }
     }
 }
@@ -3,7 +3,7 @@ pub mod base;
 pub mod basic;
 pub use basic::BasicElement;
 pub mod time_varing_linear;
-pub use time_varing_linear::TimeVaringLinearElement;
+pub use time_varing_linear::{MutualInductance, TimeVaringLinearElement};
 pub mod time_varing_non_linear;
 pub use time_varing_non_linear::mosfet::MosfetModel;
 pub use time_varing_non_linear::TimeVaringNonLinearElement;
@@ -1,11 +1,39 @@
-use crate::netlist::NodeId;
+use std::fmt;
 
-use super::base::{Element, MatrixSettable};
+use sprs::CsVec;
+
+use crate::netlist::{NodeId, Unknowns};
+
+use super::base::{format_value, BranchKind, Element, MatrixSettable, OpReportable};
 
 #[derive(Debug, Clone)]
 pub enum TimeVaringLinearElementType {
     Capacitor(f64),
     Inductor(f64),
+    TransmissionLine(TransmissionLine),
+}
+
+/// Lossless line `T<name> n1+ n1- n2+ n2- Z0=z TD=t`, whose delay may also
+/// be given as `F=f [NL=n]` for a length of `n` wavelengths at `f`
+/// (`NL` defaults to 0.25). The element nodes are those of port 1.
+#[derive(Debug, Clone, Copy)]
+pub struct TransmissionLine {
+    pub node_in_2: NodeId,
+    pub node_out_2: NodeId,
+    /// Characteristic impedance.
+    pub z0: f64,
+    /// Delay from one port to the other.
+    pub td: f64,
+}
+
+/// Magnetic coupling `K<name> L1 L2 k` of two inductors, with a mutual
+/// inductance of `k * sqrt(L1 * L2)`. The dotted terminal of each inductor
+/// is its first node.
+#[derive(Debug, Clone)]
+pub struct MutualInductance {
+    name: String,
+    inductors: [String; 2],
+    k: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -43,7 +71,29 @@ impl Element for TimeVaringLinearElement {
     }
 
     fn get_nodes(&self) -> Vec<NodeId> {
-        vec![self.node_in, self.node_out]
+        match self.element_type {
+            TimeVaringLinearElementType::TransmissionLine(line) => {
+                vec![self.node_in, self.node_out, line.node_in_2, line.node_out_2]
+            }
+            _ => vec![self.node_in, self.node_out],
+        }
+    }
+
+    fn get_branches(&self) -> Vec<(NodeId, NodeId, BranchKind)> {
+        let kind = match self.element_type {
+            TimeVaringLinearElementType::Capacitor(_) => BranchKind::Capacitive,
+            TimeVaringLinearElementType::Inductor(_) => BranchKind::VoltageDefined,
+            // At DC the line connects each terminal of port 1 to the same
+            // terminal of port 2.
+            TimeVaringLinearElementType::TransmissionLine(line) => {
+                let mut branches = vec![(self.node_in, line.node_in_2, BranchKind::VoltageDefined)];
+                if self.node_out != line.node_out_2 {
+                    branches.push((self.node_out, line.node_out_2, BranchKind::VoltageDefined));
+                }
+                return branches;
+            }
+        };
+        vec![(self.node_in, self.node_out, kind)]
     }
 }
 
@@ -56,11 +106,12 @@ impl TimeVaringLinearElement {
         self.node_out
     }
 
-    #[allow(dead_code)]
+    /// Get the capacitance, the inductance or the characteristic impedance.
     pub(super) fn get_base_value(&self) -> f64 {
         match self.element_type {
             TimeVaringLinearElementType::Capacitor(value) => value,
             TimeVaringLinearElementType::Inductor(value) => value,
+            TimeVaringLinearElementType::TransmissionLine(line) => line.z0,
         }
     }
 }
@@ -78,8 +129,7 @@ impl TimeVaringLinearElement {
         mat: &mut crate::matrix::build::MatrixTriplets<f64>,
         _v: &mut crate::matrix::build::VecItems<f64>,
     ) {
-        let new_pos = mat.size;
-        mat.extend_size(1);
+        let new_pos = mat.append_branch(self.get_name());
 
         let (node_in, node_out) = (self.get_node_in(), self.get_node_out());
 
@@ -88,6 +138,148 @@ impl TimeVaringLinearElement {
         mat.push_with_node_id(node_in, new_pos + 1, 1.);
         mat.push_with_node_id(node_out, new_pos + 1, -1.);
     }
+
+    /// At DC both ports have the same voltage and opposite currents. The
+    /// current of each port flows from its first terminal through the line
+    /// to its second one.
+    fn set_matrix_dc_transmission_line(
+        &self,
+        mat: &mut crate::matrix::build::MatrixTriplets<f64>,
+        _v: &mut crate::matrix::build::VecItems<f64>,
+        line: &TransmissionLine,
+    ) {
+        let branch_1 = mat.append_branch(&format!("{}-1", self.get_name())) + 1;
+        let branch_2 = mat.append_branch(&format!("{}-2", self.get_name())) + 1;
+
+        let ports = [
+            (branch_1, self.get_node_in(), self.get_node_out()),
+            (branch_2, line.node_in_2, line.node_out_2),
+        ];
+        for (branch, node_in, node_out) in ports {
+            mat.push_with_node_id(node_in, branch, 1.);
+            mat.push_with_node_id(node_out, branch, -1.);
+        }
+
+        mat.push_with_node_id(branch_1, self.get_node_in(), 1.);
+        mat.push_with_node_id(branch_1, self.get_node_out(), -1.);
+        mat.push_with_node_id(branch_1, line.node_in_2, -1.);
+        mat.push_with_node_id(branch_1, line.node_out_2, 1.);
+        mat.push_with_node_id(branch_2, branch_1, 1.);
+        mat.push_with_node_id(branch_2, branch_2, 1.);
+    }
+}
+
+impl MutualInductance {
+    pub fn new(name: String, inductors: [String; 2], k: f64) -> Result<Self, String> {
+        if !(k > 0. && k <= 1.) {
+            return Err(format!("Invalid coupling coefficient of {}: {}", name, k));
+        }
+        Ok(Self { name, inductors, k })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the names of the coupled inductors as they appear in the netlist.
+    pub fn get_inductors(&self) -> &[String; 2] {
+        &self.inductors
+    }
+
+    /// Get the mutual inductance between the inductors in `elements`, which
+    /// must both exist and differ.
+    pub fn get_mutual_inductance(
+        &self,
+        elements: &[TimeVaringLinearElement],
+    ) -> Result<f64, String> {
+        let [l1, l2] = self.inductors.each_ref().map(|name| {
+            elements
+                .iter()
+                .find(|e| {
+                    e.get_name().eq_ignore_ascii_case(name)
+                        && matches!(e.element_type, TimeVaringLinearElementType::Inductor(_))
+                })
+                .ok_or_else(|| format!("Unknown inductor in {}: {}", self.name, name))
+        });
+        let (l1, l2) = (l1?, l2?);
+        if l1.get_name() == l2.get_name() {
+            return Err(format!(
+                "Inductor coupled to itself in {}: {}",
+                self.name,
+                l1.get_name()
+            ));
+        }
+        Ok(self.k * (l1.get_base_value() * l2.get_base_value()).sqrt())
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut iter = s.split_whitespace();
+        let name = iter.next()?.to_string();
+        let inductors = [iter.next()?.to_string(), iter.next()?.to_string()];
+        let k = iter.next()?.parse::<f64>().ok()?;
+        Self::new(name, inductors, k).ok()
+    }
+}
+
+impl fmt::Display for MutualInductance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.name,
+            self.inductors[0],
+            self.inductors[1],
+            format_value(self.k)
+        )
+    }
+}
+
+impl OpReportable for TimeVaringLinearElement {
+    /// Capacitors are open and inductors shorted at DC, so neither absorbs
+    /// power. A lossless line carries the current of port 1 through to
+    /// port 2.
+    fn get_current_power(&self, x: &CsVec<f64>, unknowns: &Unknowns) -> (f64, f64) {
+        let branch = match self.element_type {
+            TimeVaringLinearElementType::Capacitor(_) => return (0., 0.),
+            TimeVaringLinearElementType::Inductor(_) => self.get_name().to_string(),
+            TimeVaringLinearElementType::TransmissionLine(_) => format!("{}-1", self.get_name()),
+        };
+        let current = unknowns
+            .get_branch_index(&branch)
+            .and_then(|i| x.get(i).copied())
+            .unwrap_or(0.);
+        (current, 0.)
+    }
+}
+
+impl fmt::Display for TimeVaringLinearElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match self.element_type {
+            TimeVaringLinearElementType::Capacitor(value)
+            | TimeVaringLinearElementType::Inductor(value) => value,
+            TimeVaringLinearElementType::TransmissionLine(line) => {
+                return write!(
+                    f,
+                    "{} {} {} {} {} Z0={} TD={}",
+                    self.name,
+                    self.node_in,
+                    self.node_out,
+                    line.node_in_2,
+                    line.node_out_2,
+                    format_value(line.z0),
+                    format_value(line.td)
+                );
+            }
+        };
+        write!(
+            f,
+            "{} {} {} {}",
+            self.name,
+            self.node_in,
+            self.node_out,
+            format_value(value)
+        )
+    }
 }
 
 impl TimeVaringLinearElement {
@@ -101,6 +293,48 @@ impl TimeVaringLinearElement {
         })
     }
 
+    pub fn parse_transmission_line(s: &str) -> Option<Self> {
+        let mut iter = s.split_whitespace();
+        let name = iter.next()?.to_string();
+        let mut nodes = [0; 4];
+        for node in nodes.iter_mut() {
+            *node = iter.next()?.parse::<NodeId>().ok()?;
+        }
+
+        let (mut z0, mut td, mut freq, mut nl) = (None, None, None, 0.25);
+        for (key, value) in super::base::parse_instance_parameters(iter)? {
+            match key.as_str() {
+                "Z0" => z0 = Some(value),
+                "TD" => td = Some(value),
+                "F" => freq = Some(value),
+                "NL" => nl = value,
+                _ => return None,
+            }
+        }
+        let td = match (td, freq) {
+            (Some(td), None) => td,
+            (None, Some(freq)) if freq > 0. => nl / freq,
+            _ => return None,
+        };
+        let z0 = z0?;
+        if !(z0 > 0. && td > 0.) {
+            return None;
+        }
+
+        let [node_in, node_out, node_in_2, node_out_2] = nodes;
+        Some(Self {
+            name,
+            node_in,
+            node_out,
+            element_type: TimeVaringLinearElementType::TransmissionLine(TransmissionLine {
+                node_in_2,
+                node_out_2,
+                z0,
+                td,
+            }),
+        })
+    }
+
     pub fn parse_inductor(s: &str) -> Option<Self> {
         let (name, node_in, node_out, value) = super::base::general_element_parse(s)?;
         Some(Self {
@@ -125,6 +359,9 @@ impl MatrixSettable for TimeVaringLinearElement {
             TimeVaringLinearElementType::Inductor(_) => {
                 self.set_matrix_dc_inductor(mat, v);
             }
+            TimeVaringLinearElementType::TransmissionLine(ref line) => {
+                self.set_matrix_dc_transmission_line(mat, v, line);
+            }
         }
     }
 }
@@ -0,0 +1,545 @@
+use std::cell::Cell;
+use std::f64::consts::PI;
+
+use crate::netlist::NodeId;
+
+/// A quantity of the circuit read by an expression.
+#[derive(Debug, Clone)]
+pub enum Variable {
+    Voltage(NodeId),
+    /// The current through a voltage source, with the position of its
+    /// branch current once the matrix is set up.
+    Current(String, Cell<NodeId>),
+}
+
+impl Variable {
+    /// Get the position of the variable in a solution, as a node ID.
+    pub fn get_node_id(&self) -> NodeId {
+        match self {
+            Variable::Voltage(node) => *node,
+            Variable::Current(_, branch) => branch.get(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    /// Unit step, 1 for positive arguments.
+    U,
+    Min,
+    Max,
+    Pow,
+    /// `limit(x, lo, hi)` clamps `x` between `lo` and `hi`.
+    Limit,
+}
+
+impl Function {
+    fn parse(name: &str) -> Option<Self> {
+        let function = match name {
+            "ABS" => Function::Abs,
+            "SQRT" => Function::Sqrt,
+            "EXP" => Function::Exp,
+            "LN" | "LOG" => Function::Ln,
+            "LOG10" => Function::Log10,
+            "SIN" => Function::Sin,
+            "COS" => Function::Cos,
+            "TAN" => Function::Tan,
+            "ASIN" => Function::Asin,
+            "ACOS" => Function::Acos,
+            "ATAN" => Function::Atan,
+            "SINH" => Function::Sinh,
+            "COSH" => Function::Cosh,
+            "TANH" => Function::Tanh,
+            "U" => Function::U,
+            "MIN" => Function::Min,
+            "MAX" => Function::Max,
+            "POW" => Function::Pow,
+            "LIMIT" => Function::Limit,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    fn get_arity(&self) -> usize {
+        match self {
+            Function::Min | Function::Max | Function::Pow => 2,
+            Function::Limit => 3,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Constant(f64),
+    /// A variable by its index in `Expression::variables`.
+    Variable(usize),
+    Time,
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+/// A value with its derivatives with respect to the variables of an
+/// expression.
+#[derive(Debug, Clone)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: Vec<f64>,
+}
+
+impl Dual {
+    fn constant(value: f64, n: usize) -> Self {
+        Self {
+            value,
+            grad: vec![0.; n],
+        }
+    }
+
+    /// Apply a function whose derivative at the value is `derivative`.
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        Self {
+            value,
+            grad: self.grad.iter().map(|g| scale(*g, derivative)).collect(),
+        }
+    }
+
+    /// Combine two values with the partial derivatives `da` and `db`.
+    fn combine(self, other: Self, value: f64, da: f64, db: f64) -> Self {
+        Self {
+            value,
+            grad: self
+                .grad
+                .iter()
+                .zip(other.grad.iter())
+                .map(|(a, b)| scale(*a, da) + scale(*b, db))
+                .collect(),
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        self.grad.iter().all(|g| *g == 0.)
+    }
+}
+
+/// An arithmetic expression of node voltages `V(a)` or `V(a,b)`, source
+/// currents `I(Vname)` and `TIME`, differentiated exactly in forward mode.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+    variables: Vec<Variable>,
+}
+
+impl Expression {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = ExpressionParser {
+            tokens,
+            pos: 0,
+            variables: Vec::new(),
+        };
+        let root = parser.parse_sum()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("Unexpected {} in expression: {}", token, s));
+        }
+        Ok(Self {
+            root,
+            variables: parser.variables,
+        })
+    }
+
+    pub fn get_variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// Evaluate the expression and its derivatives with respect to the
+    /// variables at solution `x` and `time`.
+    pub fn eval(&self, x: &sprs::CsVec<f64>, time: f64) -> Dual {
+        use crate::matrix::ext::VecExt;
+
+        let values = self
+            .variables
+            .iter()
+            .map(|variable| x.get_by_node_id(variable.get_node_id()))
+            .collect::<Vec<_>>();
+        eval_node(&self.root, &values, time)
+    }
+}
+
+fn eval_node(node: &Node, values: &[f64], time: f64) -> Dual {
+    let n = values.len();
+    match node {
+        Node::Constant(value) => Dual::constant(*value, n),
+        Node::Variable(index) => {
+            let mut dual = Dual::constant(values[*index], n);
+            dual.grad[*index] = 1.;
+            dual
+        }
+        Node::Time => Dual::constant(time, n),
+        Node::Neg(a) => {
+            let a = eval_node(a, values, time);
+            let value = -a.value;
+            a.chain(value, -1.)
+        }
+        Node::Binary(op, a, b) => {
+            let (a, b) = (eval_node(a, values, time), eval_node(b, values, time));
+            let (u, v) = (a.value, b.value);
+            match op {
+                BinaryOp::Add => a.combine(b, u + v, 1., 1.),
+                BinaryOp::Sub => a.combine(b, u - v, 1., -1.),
+                BinaryOp::Mul => a.combine(b, u * v, v, u),
+                BinaryOp::Div => a.combine(b, u / v, 1. / v, -u / (v * v)),
+                BinaryOp::Pow => pow(a, b),
+            }
+        }
+        Node::Call(function, args) => {
+            let mut args = args.iter().map(|arg| eval_node(arg, values, time));
+            let a = args.next().unwrap();
+            let u = a.value;
+            match function {
+                Function::Abs => a.chain(u.abs(), sign(u)),
+                Function::Sqrt => a.chain(u.sqrt(), 0.5 / u.sqrt()),
+                Function::Exp => a.chain(u.exp(), u.exp()),
+                Function::Ln => a.chain(u.ln(), 1. / u),
+                Function::Log10 => a.chain(u.log10(), 1. / (u * std::f64::consts::LN_10)),
+                Function::Sin => a.chain(u.sin(), u.cos()),
+                Function::Cos => a.chain(u.cos(), -u.sin()),
+                Function::Tan => a.chain(u.tan(), 1. / (u.cos() * u.cos())),
+                Function::Asin => a.chain(u.asin(), 1. / (1. - u * u).sqrt()),
+                Function::Acos => a.chain(u.acos(), -1. / (1. - u * u).sqrt()),
+                Function::Atan => a.chain(u.atan(), 1. / (1. + u * u)),
+                Function::Sinh => a.chain(u.sinh(), u.cosh()),
+                Function::Cosh => a.chain(u.cosh(), u.sinh()),
+                Function::Tanh => a.chain(u.tanh(), 1. - u.tanh() * u.tanh()),
+                Function::U => a.chain(if u > 0. { 1. } else { 0. }, 0.),
+                Function::Min | Function::Max => {
+                    let b = args.next().unwrap();
+                    if (*function == Function::Min) == (u <= b.value) {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                Function::Pow => pow(a, args.next().unwrap()),
+                Function::Limit => {
+                    let (lo, hi) = (args.next().unwrap(), args.next().unwrap());
+                    if u < lo.value {
+                        lo
+                    } else if u > hi.value {
+                        hi
+                    } else {
+                        a
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `g * derivative`, zero where `g` is, so that a variable the operand does
+/// not depend on stays independent where the derivative is infinite, e.g.
+/// `sqrt` at zero.
+fn scale(g: f64, derivative: f64) -> f64 {
+    if g == 0. {
+        0.
+    } else {
+        g * derivative
+    }
+}
+
+fn sign(u: f64) -> f64 {
+    if u > 0. {
+        1.
+    } else if u < 0. {
+        -1.
+    } else {
+        0.
+    }
+}
+
+/// `a ^ b`, differentiated with respect to the exponent only if it varies,
+/// so that constant powers of negative values stay defined.
+fn pow(a: Dual, b: Dual) -> Dual {
+    let (u, v) = (a.value, b.value);
+    let value = u.powf(v);
+    let db = if b.is_constant() { 0. } else { value * u.ln() };
+    a.combine(b, value, v * u.powf(v - 1.), db)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "'{}'", value),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // An exponent, if followed by digits.
+            if i < chars.len() && chars[i].eq_ignore_ascii_case(&'e') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text = chars[start..i].iter().collect::<String>();
+            let value = text
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid number in expression: {}", text))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let token = match c {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 1;
+                    Token::Op('^')
+                }
+                '+' | '-' | '*' | '/' | '^' => Token::Op(c),
+                '(' => Token::LParen,
+                ')' => Token::RParen,
+                ',' => Token::Comma,
+                _ => return Err(format!("Invalid character in expression: {}", c)),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct ExpressionParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: Vec<Variable>,
+}
+
+impl ExpressionParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "Unexpected end of expression".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(format!(
+                "Expected {} in expression, found {}",
+                expected, token
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_sum(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_product()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            let op = if *op == '+' {
+                BinaryOp::Add
+            } else {
+                BinaryOp::Sub
+            };
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.parse_product()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_product(&mut self) -> Result<Node, String> {
+        let mut node = self.parse_unary()?;
+        while let Some(Token::Op(op @ ('*' | '/'))) = self.peek() {
+            let op = if *op == '*' {
+                BinaryOp::Mul
+            } else {
+                BinaryOp::Div
+            };
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    /// Signs bind more loosely than `^`, so `-x^2` is `-(x^2)`.
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(Node::Neg(Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.parse_unary()
+            }
+            _ => self.parse_power(),
+        }
+    }
+
+    /// `^` is right associative.
+    fn parse_power(&mut self) -> Result<Node, String> {
+        let base = self.parse_primary()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let exponent = self.parse_unary()?;
+            return Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Number(value) => Ok(Node::Constant(value)),
+            Token::LParen => {
+                let node = self.parse_sum()?;
+                self.expect(Token::RParen)?;
+                Ok(node)
+            }
+            Token::Ident(name) => self.parse_ident(&name.to_ascii_uppercase()),
+            token => Err(format!("Unexpected {} in expression", token)),
+        }
+    }
+
+    fn parse_ident(&mut self, name: &str) -> Result<Node, String> {
+        match name {
+            "TIME" => return Ok(Node::Time),
+            "PI" => return Ok(Node::Constant(PI)),
+            _ => {}
+        }
+
+        self.expect(Token::LParen)?;
+        let node = match name {
+            "V" => {
+                let node_p = self.parse_node()?;
+                if self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    let node_n = self.parse_node()?;
+                    Node::Binary(BinaryOp::Sub, Box::new(node_p), Box::new(node_n))
+                } else {
+                    node_p
+                }
+            }
+            "I" => {
+                let source = match self.next()? {
+                    Token::Ident(source) => source,
+                    token => return Err(format!("Expected a source name in I(), found {}", token)),
+                };
+                self.add_variable(Variable::Current(source, Cell::new(0)))
+            }
+            _ => {
+                let function = Function::parse(name)
+                    .ok_or_else(|| format!("Unknown function in expression: {}", name))?;
+                let mut args = vec![self.parse_sum()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    args.push(self.parse_sum()?);
+                }
+                if args.len() != function.get_arity() {
+                    return Err(format!(
+                        "{} takes {} arguments, found {}",
+                        name,
+                        function.get_arity(),
+                        args.len()
+                    ));
+                }
+                Node::Call(function, args)
+            }
+        };
+        self.expect(Token::RParen)?;
+        Ok(node)
+    }
+
+    fn parse_node(&mut self) -> Result<Node, String> {
+        match self.next()? {
+            Token::Number(value) if value.fract() == 0. && value >= 0. => {
+                Ok(self.add_variable(Variable::Voltage(value as NodeId)))
+            }
+            token => Err(format!("Expected a node in V(), found {}", token)),
+        }
+    }
+
+    /// Reuse the index of a variable read more than once.
+    fn add_variable(&mut self, variable: Variable) -> Node {
+        let position = self.variables.iter().position(|v| match (v, &variable) {
+            (Variable::Voltage(a), Variable::Voltage(b)) => a == b,
+            (Variable::Current(a, _), Variable::Current(b, _)) => a.eq_ignore_ascii_case(b),
+            _ => false,
+        });
+        let index = position.unwrap_or_else(|| {
+            self.variables.push(variable);
+            self.variables.len() - 1
+        });
+        Node::Variable(index)
+    }
+}
@@ -0,0 +1,243 @@
+use std::cell::Cell;
+use std::fmt;
+
+use super::super::base::{ConvergenceCheckable, MatrixDcUpdatable};
+use crate::matrix::build::VecPushWithNodeId;
+use crate::matrix::ext::{MatExt, VecExt};
+use crate::netlist::NodeId;
+use crate::solver::base::ConvergenceOptions;
+
+pub mod expression;
+use expression::{Dual, Expression, Variable};
+
+/// Bound on the derivatives stamped in the matrix, which are infinite where
+/// e.g. `sqrt` or `ln` of a variable is evaluated at zero.
+const MAX_DERIVATIVE: f64 = 1e12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BehavioralKind {
+    /// `V=expr`, the voltage from `node_p` to `node_n`.
+    Voltage,
+    /// `I=expr`, the current from `node_p` through the source to `node_n`.
+    Current,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct BehavioralElementType {
+    pub(super) node_p: NodeId,
+    pub(super) node_n: NodeId,
+    pub(super) kind: BehavioralKind,
+    pub(super) expression: Expression,
+    /// The expression as written, for writing the netlist back.
+    pub(super) text: String,
+    /// Position of the branch current of a voltage source, as a node ID.
+    pub(super) branch: Cell<NodeId>,
+    /// Time of the solution being solved for, zero outside a transient
+    /// analysis.
+    pub(super) time: Cell<f64>,
+}
+
+impl fmt::Display for BehavioralElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            BehavioralKind::Voltage => "V",
+            BehavioralKind::Current => "I",
+        };
+        write!(f, "{} {} {}={}", self.node_p, self.node_n, kind, self.text)
+    }
+}
+
+impl BehavioralElementType {
+    /// Parse `n+ n- V=expr` or `n+ n- I=expr`.
+    pub(super) fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid behavioral source: {}", s);
+        let mut iter = s.trim_start().splitn(3, char::is_whitespace);
+        let node_p = iter
+            .next()
+            .and_then(|node| node.parse::<NodeId>().ok())
+            .ok_or_else(invalid)?;
+        let node_n = iter
+            .next()
+            .and_then(|node| node.trim().parse::<NodeId>().ok())
+            .ok_or_else(invalid)?;
+        let (kind, text) = iter
+            .next()
+            .and_then(|rest| rest.split_once('='))
+            .ok_or_else(invalid)?;
+        let kind = match kind.trim().to_ascii_uppercase().as_str() {
+            "V" => BehavioralKind::Voltage,
+            "I" => BehavioralKind::Current,
+            _ => return Err(invalid()),
+        };
+        let text = text.trim().to_string();
+
+        Ok(Self {
+            node_p,
+            node_n,
+            kind,
+            expression: Expression::parse(&text)?,
+            text,
+            branch: Cell::new(0),
+            time: Cell::new(0.),
+        })
+    }
+
+    /// Get the nodes whose voltages the expression reads.
+    pub(super) fn get_sensed_nodes(&self) -> Vec<NodeId> {
+        self.expression
+            .get_variables()
+            .iter()
+            .filter_map(|variable| match variable {
+                Variable::Voltage(node) => Some(*node),
+                Variable::Current(..) => None,
+            })
+            .collect()
+    }
+
+    /// Get the names of the voltage sources whose currents the expression
+    /// reads.
+    pub(super) fn get_control_sources(&self) -> Vec<&str> {
+        self.expression
+            .get_variables()
+            .iter()
+            .filter_map(|variable| match variable {
+                Variable::Voltage(_) => None,
+                Variable::Current(source, _) => Some(source.as_str()),
+            })
+            .collect()
+    }
+
+    /// Get the value of the expression at solution `x`.
+    pub(super) fn get_value(&self, x: &sprs::CsVec<f64>) -> f64 {
+        self.expression.eval(x, self.time.get()).value
+    }
+
+    /// Get the current from `node_p` through the source to `node_n`.
+    pub(super) fn get_current(&self, x: &sprs::CsVec<f64>) -> f64 {
+        match self.kind {
+            BehavioralKind::Voltage => x.get_by_node_id(self.branch.get()),
+            BehavioralKind::Current => self.get_value(x),
+        }
+    }
+
+    /// Evaluate the expression at `x` with its derivatives made finite for
+    /// stamping.
+    fn linearize(&self, x: &sprs::CsVec<f64>) -> Dual {
+        let mut dual = self.expression.eval(x, self.time.get());
+        for g in dual.grad.iter_mut() {
+            *g = if g.is_nan() {
+                0.
+            } else {
+                g.clamp(-MAX_DERIVATIVE, MAX_DERIVATIVE)
+            };
+        }
+        dual
+    }
+
+    /// Get the row the expression is stamped in: the branch row of a
+    /// voltage source or the KCL row of `node_p`, mirrored in `node_n`.
+    fn get_rows(&self) -> Vec<(NodeId, f64)> {
+        match self.kind {
+            BehavioralKind::Voltage => vec![(self.branch.get(), -1.)],
+            BehavioralKind::Current => vec![(self.node_p, 1.), (self.node_n, -1.)],
+        }
+    }
+}
+
+impl MatrixDcUpdatable for BehavioralElementType {
+    /// Stamp the expression linearized around `x`: its derivatives in the
+    /// matrix and the rest in the right-hand side.
+    fn update_matrix_dc(
+        &self,
+        mat: &mut sprs::CsMat<f64>,
+        v: &mut sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+    ) {
+        let dual = self.linearize(x);
+        let columns = self
+            .expression
+            .get_variables()
+            .iter()
+            .map(|variable| variable.get_node_id())
+            .collect::<Vec<_>>();
+        let linear = columns
+            .iter()
+            .zip(dual.grad.iter())
+            .map(|(column, g)| g * x.get_by_node_id(*column))
+            .sum::<f64>();
+        let equivalent = dual.value - linear;
+
+        for (row, sign) in self.get_rows() {
+            for (column, g) in columns.iter().zip(dual.grad.iter()) {
+                mat.add_by_node_id(row, *column, sign * g);
+            }
+            v.add_by_node_id(row, -sign * equivalent);
+        }
+    }
+}
+
+impl ConvergenceCheckable for BehavioralElementType {
+    /// The value at `x` must agree with the linearization around `x_prev`.
+    fn is_converged(
+        &self,
+        x_prev: &sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+        options: &ConvergenceOptions,
+    ) -> bool {
+        let dual = self.linearize(x_prev);
+        let predicted = dual.value
+            + self
+                .expression
+                .get_variables()
+                .iter()
+                .zip(dual.grad.iter())
+                .map(|(variable, g)| {
+                    let node = variable.get_node_id();
+                    g * (x.get_by_node_id(node) - x_prev.get_by_node_id(node))
+                })
+                .sum::<f64>();
+        let actual = self.get_value(x);
+
+        let tol = match self.kind {
+            BehavioralKind::Voltage => options.voltage_tol(predicted, actual),
+            BehavioralKind::Current => options.current_tol(predicted, actual),
+        };
+        (actual - predicted).abs() <= tol
+    }
+}
+
+impl BehavioralElementType {
+    /// Set up the matrix like `MatrixSettable::set_matrix_dc`, naming the
+    /// branch of a voltage source after the element `name`.
+    pub(super) fn set_matrix_dc_with_name(
+        &self,
+        name: &str,
+        mat: &mut crate::matrix::build::MatrixTriplets<f64>,
+        v: &mut crate::matrix::build::VecItems<f64>,
+    ) {
+        for variable in self.expression.get_variables() {
+            if let Variable::Current(source, branch) = variable {
+                branch.set(
+                    mat.get_branch_node_id(source)
+                        .expect("Unknown controlling source"),
+                );
+            }
+        }
+
+        if self.kind == BehavioralKind::Voltage {
+            let branch = mat.append_branch(name) + 1;
+            self.branch.set(branch);
+            mat.push_with_node_id(self.node_p, branch, 1.);
+            mat.push_with_node_id(self.node_n, branch, -1.);
+            mat.push_with_node_id(branch, self.node_p, 1.);
+            mat.push_with_node_id(branch, self.node_n, -1.);
+        }
+
+        for (row, _) in self.get_rows() {
+            for variable in self.expression.get_variables() {
+                mat.push_with_node_id(row, variable.get_node_id(), 0.);
+            }
+            v.push_with_node_id(row, 0.);
+        }
+    }
+}
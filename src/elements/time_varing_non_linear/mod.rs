@@ -1,13 +1,27 @@
+use std::cell::Cell;
+use std::fmt;
+
 use crate::netlist::NodeId;
 
-use super::base::{Element, MatrixDcUpdatable, MatrixSettable};
+use super::base::{
+    parse_instance_parameters, BranchKind, ConvergenceCheckable, Element, MatrixDcUpdatable,
+    MatrixSettable, OpReportable, NOMINAL_TEMPERATURE,
+};
+use crate::netlist::Unknowns;
+use crate::solver::base::ConvergenceOptions;
 
+pub mod behavioral;
+use behavioral::{BehavioralElementType, BehavioralKind};
 pub mod mosfet;
-use mosfet::{MosfetElementType, MosfetType};
+use mosfet::{MosfetElementType, MosfetModel, MosfetOpInfo, MosfetType};
+pub mod switch;
+use switch::{SwitchControl, SwitchElementType, SwitchModel, SwitchModels};
 
 #[derive(Debug, Clone)]
 enum TimeVaringNonLinearElementType {
     Mosfet(mosfet::MosfetElementType),
+    Switch(switch::SwitchElementType),
+    Behavioral(behavioral::BehavioralElementType),
 }
 
 #[derive(Debug, Clone)]
@@ -17,22 +31,17 @@ pub struct TimeVaringNonLinearElement {
 }
 
 impl TimeVaringNonLinearElement {
-    pub fn parse_mosfet(s: &str) -> Self {
-        let mut iter = s.split_whitespace();
-        let name = iter.next().unwrap().to_string();
-        let node_d = iter.next().unwrap().parse::<NodeId>().unwrap();
-        let node_g = iter.next().unwrap().parse::<NodeId>().unwrap();
-        let node_s = iter.next().unwrap().parse::<NodeId>().unwrap();
-        let mos_type = match iter.next().unwrap() {
-            "N" | "n" => MosfetType::Nmos,
-            "P" | "p" => MosfetType::Pmos,
-            _ => panic!("Invalid mosfet type"),
-        };
-        let w = iter.next().unwrap().parse::<f64>().unwrap();
-        let l = iter.next().unwrap().parse::<f64>().unwrap();
-
-        let model_id = iter.next().unwrap().parse::<usize>().unwrap();
-
+    /// Create a MOSFET with drain, gate, source and bulk `nodes`, using the
+    /// `.MODEL` card `model_id`.
+    pub fn new_mosfet(
+        name: String,
+        mos_type: MosfetType,
+        nodes: [NodeId; 4],
+        w: f64,
+        l: f64,
+        model_id: usize,
+    ) -> Self {
+        let [node_d, node_g, node_s, node_b] = nodes;
         Self {
             name,
             element_type: TimeVaringNonLinearElementType::Mosfet(MosfetElementType {
@@ -40,12 +49,296 @@ impl TimeVaringNonLinearElement {
                 node_d,
                 node_g,
                 node_s,
+                node_b,
                 l,
                 w,
                 model_id,
+                model: None,
+                instance_temp: None,
+                temp: NOMINAL_TEMPERATURE,
             }),
         }
     }
+
+    /// Parse `M<name> d g s [b] n|p w l model_id [TEMP=..]`. Without a bulk
+    /// node the bulk is tied to the source.
+    pub fn parse_mosfet(s: &str) -> Option<Self> {
+        let mut iter = s.split_whitespace();
+        let name = iter.next()?.to_string();
+        let node_d = iter.next()?.parse::<NodeId>().ok()?;
+        let node_g = iter.next()?.parse::<NodeId>().ok()?;
+        let node_s = iter.next()?.parse::<NodeId>().ok()?;
+        let mut token = iter.next()?;
+        let node_b = match token.parse::<NodeId>() {
+            Ok(node_b) => {
+                token = iter.next()?;
+                node_b
+            }
+            Err(_) => node_s,
+        };
+        let mos_type = match token {
+            "N" | "n" => MosfetType::Nmos,
+            "P" | "p" => MosfetType::Pmos,
+            _ => return None,
+        };
+        let w = iter.next()?.parse::<f64>().ok()?;
+        let l = iter.next()?.parse::<f64>().ok()?;
+
+        let model_id = iter.next()?.parse::<usize>().ok()?;
+
+        let mut mosfet = Self::new_mosfet(
+            name,
+            mos_type,
+            [node_d, node_g, node_s, node_b],
+            w,
+            l,
+            model_id,
+        );
+        for (key, value) in parse_instance_parameters(iter)? {
+            match key.as_str() {
+                "TEMP" => mosfet.set_instance_temperature(value),
+                _ => return None,
+            }
+        }
+        Some(mosfet)
+    }
+
+    fn set_instance_temperature(&mut self, temp: f64) {
+        if let TimeVaringNonLinearElementType::Mosfet(ref mut mosfet) = self.element_type {
+            mosfet.instance_temp = Some(temp);
+            mosfet.temp = temp;
+        }
+    }
+
+    /// Set the circuit temperature, in degrees Celsius.
+    pub fn set_temperature(&mut self, temp: f64) {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mut mosfet) => mosfet.set_temperature(temp),
+            TimeVaringNonLinearElementType::Switch(_)
+            | TimeVaringNonLinearElementType::Behavioral(_) => {}
+        }
+    }
+
+    /// Parse `B<name> n+ n- V=expr` or `B<name> n+ n- I=expr`.
+    pub fn parse_behavioral(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (name, rest) = s
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("Invalid behavioral source: {}", s))?;
+        let behavioral =
+            BehavioralElementType::parse(rest).map_err(|e| format!("{}: {}", name, e))?;
+        Ok(Self {
+            name: name.to_string(),
+            element_type: TimeVaringNonLinearElementType::Behavioral(behavioral),
+        })
+    }
+
+    /// Set the time of the solution being solved for, read by behavioral
+    /// sources.
+    pub fn set_time(&self, time: f64) {
+        if let TimeVaringNonLinearElementType::Behavioral(ref behavioral) = self.element_type {
+            behavioral.time.set(time);
+        }
+    }
+
+    /// Parse `S<name> n+ n- nc+ nc- model_id [ON|OFF]` or
+    /// `W<name> n+ n- vname model_id [ON|OFF]`. Switches are off initially
+    /// unless given `ON`.
+    pub fn parse_switch(s: &str) -> Option<Self> {
+        let mut iter = s.split_whitespace();
+        let name = iter.next()?.to_string();
+        let node_p = iter.next()?.parse::<NodeId>().ok()?;
+        let node_n = iter.next()?.parse::<NodeId>().ok()?;
+        let control = if name.to_ascii_uppercase().starts_with('W') {
+            SwitchControl::Current(iter.next()?.to_string(), Cell::new(0))
+        } else {
+            let node_cp = iter.next()?.parse::<NodeId>().ok()?;
+            let node_cn = iter.next()?.parse::<NodeId>().ok()?;
+            SwitchControl::Voltage(node_cp, node_cn)
+        };
+        let model_id = iter.next()?.parse::<usize>().ok()?;
+        let initial_on = match iter.next().map(|s| s.to_ascii_uppercase()).as_deref() {
+            None | Some("OFF") => false,
+            Some("ON") => true,
+            Some(_) => return None,
+        };
+        if iter.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            name,
+            element_type: TimeVaringNonLinearElementType::Switch(SwitchElementType {
+                node_p,
+                node_n,
+                control,
+                model_id,
+                model: None,
+                initial_on,
+                on: Cell::new(initial_on),
+                latched: Cell::new(false),
+            }),
+        })
+    }
+
+    /// Get the names of the voltage sources whose currents control the
+    /// element.
+    pub fn get_control_sources(&self) -> Vec<&str> {
+        match &self.element_type {
+            TimeVaringNonLinearElementType::Switch(SwitchElementType {
+                control: SwitchControl::Current(source, _),
+                ..
+            }) => vec![source],
+            TimeVaringNonLinearElementType::Behavioral(behavioral) => {
+                behavioral.get_control_sources()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Check whether a switch changes state at solution `x`.
+    pub fn is_switching(&self, x: &sprs::CsVec<f64>) -> bool {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                switch.is_on(x) != switch.on.get()
+            }
+            _ => false,
+        }
+    }
+
+    /// Keep the state of a switch at the accepted solution `x`.
+    pub fn accept(&self, x: &sprs::CsVec<f64>) {
+        if let TimeVaringNonLinearElementType::Switch(ref switch) = self.element_type {
+            switch.accept(x);
+        }
+    }
+}
+
+impl TimeVaringNonLinearElement {
+    /// Attach the device model referenced by the element.
+    pub fn bind_model(
+        &mut self,
+        models: &mosfet::MosfetModels,
+        switch_models: &SwitchModels,
+    ) -> Result<(), String> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mut mosfet) => mosfet.bind_model(models),
+            TimeVaringNonLinearElementType::Switch(ref mut switch) => {
+                switch.bind_model(switch_models)
+            }
+            TimeVaringNonLinearElementType::Behavioral(_) => Ok(()),
+        }
+        .map_err(|e| format!("{}: {}", self.name, e))
+    }
+
+    /// Get the ID and parameters of the bound MOSFET model.
+    pub fn get_model(&self) -> Option<(usize, MosfetModel)> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                mosfet.model.map(|model| (mosfet.model_id, model))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the ID and parameters of the bound switch model.
+    pub fn get_switch_model(&self) -> Option<(usize, SwitchModel)> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                switch.model.map(|model| (switch.model_id, model))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the names and terminals of the device capacitances.
+    pub fn get_capacitance_nodes(&self) -> Vec<(&'static str, NodeId, NodeId)> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                mosfet.get_capacitance_nodes().to_vec()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the device capacitances at solution `x`, in the order of
+    /// `get_capacitance_nodes`.
+    pub fn get_capacitances(&self, x: &sprs::CsVec<f64>) -> Vec<f64> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                mosfet.get_capacitances(x).to_vec()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the name of the operating region of the device at solution `x`.
+    pub fn get_operating_region(&self, x: &sprs::CsVec<f64>) -> &'static str {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => mosfet.get_operating_region(x),
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                if switch.get_state(x) {
+                    "on"
+                } else {
+                    "off"
+                }
+            }
+            TimeVaringNonLinearElementType::Behavioral(_) => "-",
+        }
+    }
+}
+
+impl TimeVaringNonLinearElement {
+    /// Get the small-signal parameters of a MOSFET at solution `x`.
+    pub fn get_op_info(&self, x: &sprs::CsVec<f64>) -> Option<MosfetOpInfo> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => Some(mosfet.get_op_info(x)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TimeVaringNonLinearElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                write!(f, "{} {}", self.name, mosfet)
+            }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                write!(f, "{} {}", self.name, switch)
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                write!(f, "{} {}", self.name, behavioral)
+            }
+        }
+    }
+}
+
+impl OpReportable for TimeVaringNonLinearElement {
+    /// The drain current of a MOSFET, whose gate draws none, or the current
+    /// through a switch or a behavioral source.
+    fn get_current_power(&self, x: &sprs::CsVec<f64>, _unknowns: &Unknowns) -> (f64, f64) {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                let info = mosfet.get_op_info(x);
+                (info.id, info.id * info.vds)
+            }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                use crate::matrix::ext::VecExt;
+
+                let current = switch.get_current(x);
+                let v = x.get_by_node_id(switch.node_p) - x.get_by_node_id(switch.node_n);
+                (current, current * v)
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                use crate::matrix::ext::VecExt;
+
+                let current = behavioral.get_current(x);
+                let v = x.get_by_node_id(behavioral.node_p) - x.get_by_node_id(behavioral.node_n);
+                (current, current * v)
+            }
+        }
+    }
 }
 
 impl Element for TimeVaringNonLinearElement {
@@ -59,8 +352,73 @@ impl Element for TimeVaringNonLinearElement {
                 node_d,
                 node_g,
                 node_s,
+                node_b,
+                ..
+            }) => {
+                if node_b == node_s {
+                    vec![node_d, node_g, node_s]
+                } else {
+                    vec![node_d, node_g, node_s, node_b]
+                }
+            }
+            TimeVaringNonLinearElementType::Switch(ref switch) => match switch.control {
+                SwitchControl::Voltage(node_cp, node_cn) => {
+                    vec![switch.node_p, switch.node_n, node_cp, node_cn]
+                }
+                SwitchControl::Current(..) => vec![switch.node_p, switch.node_n],
+            },
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                let mut nodes = vec![behavioral.node_p, behavioral.node_n];
+                nodes.extend(behavioral.get_sensed_nodes());
+                nodes
+            }
+        }
+    }
+
+    fn get_branches(&self) -> Vec<(NodeId, NodeId, BranchKind)> {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(MosfetElementType {
+                node_d,
+                node_g,
+                node_s,
+                node_b,
                 ..
-            }) => vec![node_d, node_g, node_s],
+            }) => {
+                let mut branches = vec![
+                    (node_d, node_s, BranchKind::Conductive),
+                    (node_g, node_s, BranchKind::Capacitive),
+                    (node_g, node_d, BranchKind::Capacitive),
+                ];
+                if node_b != node_s {
+                    // The bulk only conducts through reverse-biased junctions.
+                    branches.push((node_b, node_s, BranchKind::Capacitive));
+                    branches.push((node_b, node_d, BranchKind::Capacitive));
+                }
+                branches
+            }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                let mut branches = vec![(switch.node_p, switch.node_n, BranchKind::Conductive)];
+                if let SwitchControl::Voltage(node_cp, node_cn) = switch.control {
+                    // The control input only senses its voltage.
+                    branches.push((node_cp, node_cn, BranchKind::Capacitive));
+                }
+                branches
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                let kind = match behavioral.kind {
+                    BehavioralKind::Voltage => BranchKind::VoltageDefined,
+                    BehavioralKind::Current => BranchKind::CurrentDefined,
+                };
+                let mut branches = vec![(behavioral.node_p, behavioral.node_n, kind)];
+                // The expression only senses the voltages it reads.
+                branches.extend(
+                    behavioral
+                        .get_sensed_nodes()
+                        .into_iter()
+                        .map(|node| (node, 0, BranchKind::Capacitive)),
+                );
+                branches
+            }
         }
     }
 }
@@ -76,6 +434,33 @@ impl MatrixDcUpdatable for TimeVaringNonLinearElement {
             TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
                 mosfet.update_matrix_dc(mat, v, x);
             }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                switch.update_matrix_dc(mat, v, x);
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                behavioral.update_matrix_dc(mat, v, x);
+            }
+        }
+    }
+}
+
+impl ConvergenceCheckable for TimeVaringNonLinearElement {
+    fn is_converged(
+        &self,
+        x_prev: &sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+        options: &ConvergenceOptions,
+    ) -> bool {
+        match self.element_type {
+            TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
+                mosfet.is_converged(x_prev, x, options)
+            }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                switch.is_converged(x_prev, x, options)
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                behavioral.is_converged(x_prev, x, options)
+            }
         }
     }
 }
@@ -90,6 +475,12 @@ impl MatrixSettable for TimeVaringNonLinearElement {
             TimeVaringNonLinearElementType::Mosfet(ref mosfet) => {
                 mosfet.set_matrix_dc(mat, v);
             }
+            TimeVaringNonLinearElementType::Switch(ref switch) => {
+                switch.set_matrix_dc(mat, v);
+            }
+            TimeVaringNonLinearElementType::Behavioral(ref behavioral) => {
+                behavioral.set_matrix_dc_with_name(&self.name, mat, v);
+            }
         }
     }
 }
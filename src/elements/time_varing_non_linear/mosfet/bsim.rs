@@ -0,0 +1,60 @@
+use super::equations::{
+    get_body_effect, get_effective_overdrive, get_velocity_saturated_ids,
+    get_velocity_saturated_vdsat, MosfetBias, MosfetEquations,
+};
+use super::{MosfetElementType, MosfetModel, EPS_OX};
+
+/// Subset of BSIM3/BSIM4 (`LEVEL 8`, `49`, `14` or `54`): threshold voltage
+/// with `K1`, `K2` and `ETA0`, vertical-field mobility degradation with `UA`
+/// and `UB`, velocity saturation at `VSAT` and subthreshold conduction.
+pub(super) struct Bsim;
+
+impl Bsim {
+    /// Get the effective overdrive, the gain factor reduced by mobility
+    /// degradation and the drain-source voltage at which the carriers
+    /// saturate.
+    fn get_channel(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> (f64, f64, f64) {
+        let model = mosfet.get_model();
+        let v_th = self.get_vth(mosfet, bias);
+        let (v_gst, _) =
+            get_effective_overdrive(mosfet, self.get_slope_factor(model), bias.v_gs - v_th);
+        let e_eff = (v_gst.max(0.) + 2. * v_th) * model.cox / EPS_OX;
+        let degradation = 1. + (model.ua + model.ub * e_eff) * e_eff;
+        let beta = mosfet.get_mu_cox() / degradation * mosfet.w / mosfet.l;
+        // A card with KP but no U0 leaves the carriers unsaturated.
+        let v_c = if model.vmax > 0. && model.mu > 0. {
+            2. * model.vmax * mosfet.l * degradation / mosfet.get_mu()
+        } else {
+            f64::INFINITY
+        };
+        (v_gst, beta, v_c)
+    }
+}
+
+impl MosfetEquations for Bsim {
+    /// BSIM always conducts below the threshold, with `NFACTOR` 1 by default.
+    fn get_slope_factor(&self, model: &MosfetModel) -> f64 {
+        if model.n > 0. {
+            model.n
+        } else {
+            1.
+        }
+    }
+
+    fn get_vth(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let model = mosfet.get_model();
+        mosfet.get_vt0() + get_body_effect(model.gamma, model.phi, bias.v_bs)
+            - model.k2 * bias.v_bs
+            - model.eta * bias.v_ds
+    }
+
+    fn get_vdsat(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let (v_gst, _, v_c) = self.get_channel(mosfet, bias);
+        get_velocity_saturated_vdsat(v_gst, v_c)
+    }
+
+    fn get_ids(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let (v_gst, beta, v_c) = self.get_channel(mosfet, bias);
+        get_velocity_saturated_ids(beta, v_gst, bias.v_ds, v_c, mosfet.get_model().lambda)
+    }
+}
@@ -0,0 +1,134 @@
+use super::{MosfetElementType, MosfetMode, MosfetModel};
+
+/// Step of the central differences in the default conductances.
+const DELTA_V: f64 = 1e-6;
+
+/// Terminal voltages of a MOSFET in the frame of an NMOS device: PMOS
+/// voltages are negated, as is the returned drain current.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct MosfetBias {
+    pub(super) v_gs: f64,
+    pub(super) v_ds: f64,
+    pub(super) v_bs: f64,
+}
+
+/// Drain current equations of one `.MODEL` level.
+///
+/// Levels provide the threshold voltage, the saturation voltage and the
+/// drain current of `mosfet`; the conductances default to central
+/// differences of the drain current.
+pub(super) trait MosfetEquations {
+    fn get_vth(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64;
+
+    fn get_vdsat(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64;
+
+    fn get_ids(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64;
+
+    /// Get the subthreshold slope factor, or 0 if the device cuts off at
+    /// the threshold.
+    fn get_slope_factor(&self, model: &MosfetModel) -> f64 {
+        model.n
+    }
+
+    fn get_mode(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> MosfetMode {
+        if bias.v_gs < self.get_vth(mosfet, bias) {
+            if self.get_slope_factor(mosfet.get_model()) > 0. {
+                MosfetMode::Subthreshold
+            } else {
+                MosfetMode::CutOff
+            }
+        } else if bias.v_ds < self.get_vdsat(mosfet, bias) {
+            MosfetMode::Linear
+        } else {
+            MosfetMode::Saturation
+        }
+    }
+
+    fn get_gm(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let ids = |v_gs| self.get_ids(mosfet, MosfetBias { v_gs, ..bias });
+        (ids(bias.v_gs + DELTA_V) - ids(bias.v_gs - DELTA_V)) / (2. * DELTA_V)
+    }
+
+    fn get_gds(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let ids = |v_ds| self.get_ids(mosfet, MosfetBias { v_ds, ..bias });
+        (ids(bias.v_ds + DELTA_V) - ids(bias.v_ds - DELTA_V)) / (2. * DELTA_V)
+    }
+
+    fn get_gmb(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let ids = |v_bs| self.get_ids(mosfet, MosfetBias { v_bs, ..bias });
+        (ids(bias.v_bs + DELTA_V) - ids(bias.v_bs - DELTA_V)) / (2. * DELTA_V)
+    }
+}
+
+/// Get the body effect term of the threshold voltage.
+pub(super) fn get_body_effect(gamma: f64, phi: f64, v_bs: f64) -> f64 {
+    gamma * ((phi - v_bs).max(0.).sqrt() - phi.sqrt())
+}
+
+/// Get the effective gate overdrive for the overdrive `v_gst` and its
+/// derivative with respect to `v_gst`.
+///
+/// With a slope factor `n` the overdrive follows `v_gst` in strong inversion
+/// and decays as `exp(v_gst / (2 n vt))` below the threshold, so the square
+/// law turns into a drain current of `I0 * W / L * exp(v_gst / (n vt))`.
+/// Without one the overdrive is `v_gst`.
+pub(super) fn get_effective_overdrive(
+    mosfet: &MosfetElementType,
+    n: f64,
+    v_gst: f64,
+) -> (f64, f64) {
+    if n <= 0. {
+        return (v_gst, 1.);
+    }
+    let model = mosfet.get_model();
+    let v_t = mosfet.get_thermal_voltage();
+    let n_vt = 2. * n * v_t;
+    // Scale of the weak inversion current; 1 gives I0 = 2 KP (n vt)^2.
+    let scale = if model.i0 > 0. {
+        (model.i0 / (2. * mosfet.get_mu_cox())).sqrt() / (n * v_t)
+    } else {
+        1.
+    };
+    let u = v_gst / n_vt;
+    if u > 40. {
+        return (v_gst, 1.);
+    }
+    // Softplus of `u` whose exponential is scaled by `scale` below the
+    // threshold only, so that I0 leaves the strong inversion current alone.
+    let z = u.exp();
+    let w = z * (scale + z) / (1. + z);
+    let dw = w * (1. + z / (scale + z) - z / (1. + z));
+    (n_vt * w.ln_1p(), dw / (1. + w))
+}
+
+/// Get the saturation voltage of a channel whose carriers saturate at the
+/// drain-source voltage `v_c`, for a gate overdrive `v_gst`. This is where
+/// [`get_velocity_saturated_ids`] peaks.
+pub(super) fn get_velocity_saturated_vdsat(v_gst: f64, v_c: f64) -> f64 {
+    if v_c.is_infinite() {
+        v_gst
+    } else {
+        v_c * ((1. + 2. * v_gst / v_c).sqrt() - 1.)
+    }
+}
+
+/// Square-law drain current divided by `1 + v_ds / v_c`, continued past the
+/// saturation voltage with channel length modulation `lambda`.
+pub(super) fn get_velocity_saturated_ids(
+    beta: f64,
+    v_gst: f64,
+    v_ds: f64,
+    v_c: f64,
+    lambda: f64,
+) -> f64 {
+    if v_gst <= 0. {
+        return 0.;
+    }
+    let v_dsat = get_velocity_saturated_vdsat(v_gst, v_c);
+    let linear = |v_ds: f64| beta * (v_gst - 0.5 * v_ds) * v_ds / (1. + v_ds / v_c);
+    if v_ds < v_dsat {
+        linear(v_ds)
+    } else {
+        linear(v_dsat) * (1. + lambda * (v_ds - v_dsat))
+    }
+}
@@ -0,0 +1,86 @@
+use super::equations::{get_body_effect, get_effective_overdrive, MosfetBias, MosfetEquations};
+use super::MosfetElementType;
+
+/// Square-law model with channel length modulation (`LEVEL 1`), conducting
+/// below the threshold when the model has a slope factor `N`.
+pub(super) struct Level1;
+
+impl Level1 {
+    fn get_k(mosfet: &MosfetElementType) -> f64 {
+        mosfet.get_mu_cox() * mosfet.w / mosfet.l
+    }
+
+    /// Get the effective overdrive and its derivative with respect to `v_gs`.
+    fn get_overdrive(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> (f64, f64) {
+        get_effective_overdrive(
+            mosfet,
+            self.get_slope_factor(mosfet.get_model()),
+            bias.v_gs - self.get_vth(mosfet, bias),
+        )
+    }
+}
+
+impl MosfetEquations for Level1 {
+    fn get_vth(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let model = mosfet.get_model();
+        mosfet.get_vt0() + get_body_effect(model.gamma, model.phi, bias.v_bs)
+    }
+
+    fn get_vdsat(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        self.get_overdrive(mosfet, bias).0
+    }
+
+    fn get_ids(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let v_ds = bias.v_ds;
+        let k = Self::get_k(mosfet);
+        let clm = 1. + mosfet.get_model().lambda * v_ds;
+        let (v_ov, _) = self.get_overdrive(mosfet, bias);
+
+        if v_ov <= 0. {
+            0.
+        } else if v_ds < v_ov {
+            k * (v_ov - v_ds * 0.5) * v_ds * clm
+        } else {
+            0.5 * k * v_ov.powi(2) * clm
+        }
+    }
+
+    fn get_gm(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let v_ds = bias.v_ds;
+        let k = Self::get_k(mosfet);
+        let clm = 1. + mosfet.get_model().lambda * v_ds;
+        let (v_ov, d_v_ov) = self.get_overdrive(mosfet, bias);
+
+        if v_ov <= 0. {
+            0.
+        } else if v_ds < v_ov {
+            k * v_ds * clm * d_v_ov
+        } else {
+            k * v_ov * clm * d_v_ov
+        }
+    }
+
+    fn get_gds(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let v_ds = bias.v_ds;
+        let k = Self::get_k(mosfet);
+        let lambda = mosfet.get_model().lambda;
+        let (v_ov, _) = self.get_overdrive(mosfet, bias);
+
+        if v_ov <= 0. {
+            0.
+        } else if v_ds < v_ov {
+            k * (v_ov - v_ds) * (1. + lambda * v_ds) + k * (v_ov - v_ds * 0.5) * v_ds * lambda
+        } else {
+            0.5 * k * v_ov.powi(2) * lambda
+        }
+    }
+
+    fn get_gmb(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let model = mosfet.get_model();
+        let sqrt_phi_sb = (model.phi - bias.v_bs).max(0.).sqrt();
+        if model.gamma == 0. || sqrt_phi_sb == 0. {
+            return 0.;
+        }
+        self.get_gm(mosfet, bias) * model.gamma / (2. * sqrt_phi_sb)
+    }
+}
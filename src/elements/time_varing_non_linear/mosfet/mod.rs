@@ -0,0 +1,745 @@
+use super::super::base::{
+    format_value, ConvergenceCheckable, MatrixDcUpdatable, MatrixSettable, NOMINAL_TEMPERATURE,
+};
+use crate::matrix::build::VecPushWithNodeId;
+use crate::netlist::NodeId;
+use crate::solver::base::ConvergenceOptions;
+
+use std::collections::BTreeMap as Map;
+use std::fmt;
+
+mod bsim;
+mod equations;
+mod level1;
+mod short_channel;
+
+use equations::{MosfetBias, MosfetEquations};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MosfetType {
+    Nmos,
+    Pmos,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MosfetModel {
+    vth: f64,
+    mu: f64,
+    lambda: f64,
+    cox: f64,
+    /// Transconductance parameter `MU * COX`, or 0 to derive it from `MU`
+    /// and `COX`.
+    kp: f64,
+    cj0: f64,
+    /// Built-in potential of the drain and source junctions.
+    pb: f64,
+    /// Grading coefficient of the junctions.
+    mj: f64,
+    /// Fraction of `PB` above which the forward-biased junction capacitance
+    /// is linearized.
+    fc: f64,
+    /// Gate-source and gate-drain overlap capacitances per channel width,
+    /// and gate-bulk overlap capacitance per channel length.
+    cgso: f64,
+    cgdo: f64,
+    cgbo: f64,
+    /// Body effect coefficient.
+    gamma: f64,
+    /// Surface potential.
+    phi: f64,
+    /// Selects the drain current equations.
+    level: u32,
+    /// Mobility degradation coefficient.
+    theta: f64,
+    /// Saturated carrier velocity, or 0 for no velocity saturation.
+    vmax: f64,
+    /// Drain-induced barrier lowering coefficient.
+    eta: f64,
+    /// Second-order body effect coefficient.
+    k2: f64,
+    /// First- and second-order vertical field mobility degradation.
+    ua: f64,
+    ub: f64,
+    /// Subthreshold slope factor, or 0 for a hard cut-off.
+    n: f64,
+    /// Weak inversion current per square at the threshold, or 0 to derive it
+    /// from the strong inversion parameters.
+    i0: f64,
+    /// Temperature coefficient of the threshold voltage magnitude, in V/K.
+    tcv: f64,
+    /// BSIM threshold voltage temperature coefficient, applied as
+    /// `KT1 * (T / TNOM - 1)`.
+    kt1: f64,
+    /// Mobility temperature exponent.
+    bex: f64,
+    /// Temperature in degrees Celsius at which the parameters are given.
+    tnom: f64,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct MosfetElementType {
+    pub(super) mos_type: MosfetType,
+    pub(super) node_d: NodeId,
+    pub(super) node_g: NodeId,
+    pub(super) node_s: NodeId,
+    /// Tied to the source for three-terminal devices.
+    pub(super) node_b: NodeId,
+    pub(super) l: f64,
+    pub(super) w: f64,
+    pub(super) model_id: usize,
+    /// Bound from `.MODEL` cards once the whole netlist has been read.
+    pub(super) model: Option<MosfetModel>,
+    /// Instance temperature, overriding the circuit temperature.
+    pub(super) instance_temp: Option<f64>,
+    /// Device temperature in degrees Celsius.
+    pub(super) temp: f64,
+}
+
+pub type MosfetModels = Map<usize, MosfetModel>;
+
+/// Default mobility temperature exponent.
+const BEX: f64 = -1.5;
+/// Offset of the Kelvin scale from degrees Celsius.
+const KELVIN: f64 = 273.15;
+/// Boltzmann constant divided by the elementary charge, in V/K.
+const BOLTZMANN_PER_CHARGE: f64 = 8.617333e-5;
+/// Default surface potential.
+const PHI: f64 = 0.6;
+/// Permittivity of the gate oxide.
+const EPS_OX: f64 = 3.453e-11;
+/// Default built-in potential of the drain and source junctions.
+const PB: f64 = 0.8;
+/// Default grading coefficient of the junctions.
+const MJ: f64 = 0.5;
+/// Default fraction of `PB` above which the forward-biased junction
+/// capacitance is linearized.
+const FC: f64 = 0.5;
+
+/// Parameters of foundry model cards that no level uses, ignored on
+/// purpose. Anything else unknown is most likely a typo.
+const IGNORED_PARAMETERS: &[&str] = &[
+    "VERSION", "BINUNIT", "PARAMCHK", "MOBMOD", "CAPMOD", "NQSMOD", "NOIMOD", "ACM", "TOXM", "XJ",
+    "NCH", "NSUB", "NGATE", "NFS", "NSS", "NEFF", "VBM", "K3", "K3B", "W0", "NLX", "DVT0", "DVT1",
+    "DVT2", "DVT0W", "DVT1W", "DVT2W", "UC", "A0", "AGS", "A1", "A2", "B0", "B1", "KETA", "KAPPA",
+    "DELTA", "RDSW", "PRWG", "PRWB", "WR", "WINT", "LINT", "DWG", "DWB", "VOFF", "CIT", "CDSC",
+    "CDSCB", "CDSCD", "DSUB", "PCLM", "PDIBLC1", "PDIBLC2", "PDIBLCB", "DROUT", "PSCBE1", "PSCBE2",
+    "PVAG", "ALPHA0", "BETA0", "KT1L", "KT2", "UA1", "UB1", "UC1", "AT", "PRT", "UCRIT", "UEXP",
+    "LD", "XQC", "CJSW", "MJSW", "CJSWG", "MJSWG", "PBSW", "PBSWG", "IS", "JS", "JSW", "NJ", "XTI",
+    "CF", "CKAPPA", "CLC", "CLE", "DLC", "DWC", "VFBCV", "NOFF", "VOFFCV", "ACDE", "MOIN", "TPB",
+    "TPBSW", "TPBSWG", "TCJ", "TCJSW", "TCJSWG", "TPG", "LL", "WL", "LLN", "WLN", "LW", "WW",
+    "LWN", "WWN", "LWL", "WWL", "XL", "XW", "HDIF", "LDIF", "RSH", "RD", "RS", "KF", "AF", "EF",
+    "NOIA", "NOIB", "NOIC", "EM", "ELM", "XPART", "LMIN", "LMAX", "WMIN", "WMAX",
+];
+
+/// Parameters of foundry model cards with length, width and area binning
+/// variants, e.g. `LVTH0`, which are ignored.
+const BINNED_PARAMETERS: &[&str] = &[
+    "VTH0", "K1", "K2", "U0", "UA", "UB", "VSAT", "ETA0", "NFACTOR", "KT1", "UTE",
+];
+
+impl MosfetModel {
+    pub fn new(vth: f64, mu: f64, lambda: f64, cox: f64, cj0: f64) -> Self {
+        Self {
+            vth,
+            mu,
+            lambda,
+            cox,
+            kp: 0.,
+            cj0,
+            pb: PB,
+            mj: MJ,
+            fc: FC,
+            cgso: 0.,
+            cgdo: 0.,
+            cgbo: 0.,
+            gamma: 0.,
+            phi: PHI,
+            level: 1,
+            theta: 0.,
+            vmax: 0.,
+            eta: 0.,
+            k2: 0.,
+            ua: 0.,
+            ub: 0.,
+            n: 0.,
+            i0: 0.,
+            tcv: 0.,
+            kt1: 0.,
+            bex: BEX,
+            tnom: NOMINAL_TEMPERATURE,
+        }
+    }
+
+    /// Set the body effect coefficient `gamma` and the surface potential
+    /// `phi`.
+    pub fn with_body_effect(mut self, gamma: f64, phi: f64) -> Self {
+        self.gamma = gamma;
+        self.phi = phi;
+        self
+    }
+
+    /// Parse `.MODEL id [NMOS|PMOS] [(]KEY VALUE ...[)]`, where pairs may
+    /// also be written `KEY=VALUE`. Foundry parameters that no level uses
+    /// are ignored, and other unknown parameters are an error, as is a model
+    /// without `KP` or `MU` and `COX`.
+    pub fn parse(s: &str) -> Result<(usize, Self), String> {
+        let line = s.replace(['(', ')', '='], " ");
+        let mut iter = line.split_whitespace().skip(1);
+
+        let model_id = iter
+            .next()
+            .and_then(|id| id.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid model: {}", s))?;
+
+        let mut model = Self::new(0., 0., 0., 0., 0.);
+
+        while let Some(key) = iter.next() {
+            let key = key.to_ascii_uppercase();
+            if key == "NMOS" || key == "PMOS" {
+                continue;
+            }
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("Missing value of model parameter {}", key))?;
+            let value = value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value of model parameter {}: {}", key, value))?;
+
+            match key.as_str() {
+                "LEVEL" => {
+                    model.level = value as u32;
+                    if value != model.level as f64 || Self::get_level(model.level).is_none() {
+                        return Err(format!("Unsupported MOSFET model level: {}", value));
+                    }
+                }
+                "VT" | "VTO" | "VTH0" => model.vth = value,
+                "MU" => model.mu = value,
+                // BSIM mobilities above 1 are in cm^2/Vs.
+                "U0" => model.mu = if value > 1. { value * 1e-4 } else { value },
+                "COX" => model.cox = value,
+                "KP" => model.kp = value,
+                "TOX" => model.cox = EPS_OX / value,
+                "LAMBDA" => model.lambda = value,
+                "CJ0" => model.cj0 = value,
+                "PB" if value > 0. => model.pb = value,
+                "MJ" => model.mj = value,
+                "FC" if (0. ..1.).contains(&value) => model.fc = value,
+                "PB" | "FC" => {
+                    return Err(format!(
+                        "Invalid value of model parameter {}: {}",
+                        key, value
+                    ))
+                }
+                "CGSO" => model.cgso = value,
+                "CGDO" => model.cgdo = value,
+                "CGBO" => model.cgbo = value,
+                // Without drain and source areas there is nothing to scale.
+                "CJ" | "CBD" | "CBS" => {
+                    return Err(format!(
+                        "Unsupported MOSFET model parameter {}, use CJ0",
+                        key
+                    ))
+                }
+                "GAMMA" | "K1" => model.gamma = value,
+                "PHI" => model.phi = value,
+                "THETA" => model.theta = value,
+                "VMAX" | "VSAT" => model.vmax = value,
+                "ETA" | "ETA0" => model.eta = value,
+                "K2" => model.k2 = value,
+                "UA" => model.ua = value,
+                "UB" => model.ub = value,
+                "N" | "NFACTOR" => model.n = value,
+                "I0" => model.i0 = value,
+                "TCV" => model.tcv = value,
+                "KT1" => model.kt1 = value,
+                "BEX" | "UTE" => model.bex = value,
+                "TNOM" => model.tnom = value,
+                key if Self::is_ignored_parameter(key) => {}
+                _ => return Err(format!("Unknown MOSFET model parameter: {}", key)),
+            }
+        }
+
+        if model.get_mu_cox() <= 0. {
+            return Err(format!(
+                "MOSFET model {} needs KP, or MU and COX (or TOX)",
+                model_id
+            ));
+        }
+
+        Ok((model_id, model))
+    }
+
+    /// Get the transconductance parameter at the nominal temperature.
+    fn get_mu_cox(&self) -> f64 {
+        if self.kp > 0. {
+            self.kp
+        } else {
+            self.mu * self.cox
+        }
+    }
+
+    fn is_ignored_parameter(key: &str) -> bool {
+        let binned = key.strip_prefix(['L', 'W', 'P']).is_some_and(|key| {
+            BINNED_PARAMETERS.contains(&key) || IGNORED_PARAMETERS.contains(&key)
+        });
+        binned || IGNORED_PARAMETERS.contains(&key)
+    }
+
+    fn get_level(level: u32) -> Option<&'static dyn MosfetEquations> {
+        match level {
+            1 => Some(&level1::Level1),
+            2 | 3 => Some(&short_channel::ShortChannel),
+            8 | 49 | 14 | 54 => Some(&bsim::Bsim),
+            _ => None,
+        }
+    }
+
+    fn get_equations(&self) -> &'static dyn MosfetEquations {
+        Self::get_level(self.level).expect("Unsupported MOSFET model level")
+    }
+}
+
+/// Small-signal parameters of a MOSFET at an operating point.
+#[derive(Debug, Clone, Copy)]
+pub struct MosfetOpInfo {
+    pub region: &'static str,
+    pub id: f64,
+    pub vgs: f64,
+    pub vds: f64,
+    pub vdsat: f64,
+    pub gm: f64,
+    pub gds: f64,
+    pub gmb: f64,
+}
+
+pub(super) enum MosfetMode {
+    CutOff,
+    Subthreshold,
+    Linear,
+    Saturation,
+}
+
+/// The parameters of a `.MODEL` card, without the model ID.
+impl fmt::Display for MosfetModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "VT {} MU {} COX {} LAMBDA {} CJ0 {} GAMMA {} PHI {}",
+            format_value(self.vth),
+            format_value(self.mu),
+            format_value(self.cox),
+            format_value(self.lambda),
+            format_value(self.cj0),
+            format_value(self.gamma),
+            format_value(self.phi)
+        )?;
+        if self.kp != 0. {
+            write!(f, " KP {}", format_value(self.kp))?;
+        }
+        if self.pb != PB || self.mj != MJ || self.fc != FC {
+            write!(
+                f,
+                " PB {} MJ {} FC {}",
+                format_value(self.pb),
+                format_value(self.mj),
+                format_value(self.fc)
+            )?;
+        }
+        if self.cgso != 0. || self.cgdo != 0. || self.cgbo != 0. {
+            write!(
+                f,
+                " CGSO {} CGDO {} CGBO {}",
+                format_value(self.cgso),
+                format_value(self.cgdo),
+                format_value(self.cgbo)
+            )?;
+        }
+        if self.n != 0. || self.i0 != 0. {
+            write!(
+                f,
+                " N {} I0 {}",
+                format_value(self.n),
+                format_value(self.i0)
+            )?;
+        }
+        if self.tcv != 0. || self.kt1 != 0. || self.bex != BEX || self.tnom != NOMINAL_TEMPERATURE {
+            write!(
+                f,
+                " TCV {} KT1 {} BEX {} TNOM {}",
+                format_value(self.tcv),
+                format_value(self.kt1),
+                format_value(self.bex),
+                format_value(self.tnom)
+            )?;
+        }
+        if self.level != 1 {
+            write!(
+                f,
+                " LEVEL {} THETA {} VMAX {} ETA {} K2 {} UA {} UB {}",
+                self.level,
+                format_value(self.theta),
+                format_value(self.vmax),
+                format_value(self.eta),
+                format_value(self.k2),
+                format_value(self.ua),
+                format_value(self.ub)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MosfetElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mos_type = match self.mos_type {
+            MosfetType::Nmos => "n",
+            MosfetType::Pmos => "p",
+        };
+        write!(f, "{} {} {} ", self.node_d, self.node_g, self.node_s)?;
+        if self.node_b != self.node_s {
+            write!(f, "{} ", self.node_b)?;
+        }
+        write!(
+            f,
+            "{} {} {} {}",
+            mos_type,
+            format_value(self.w),
+            format_value(self.l),
+            self.model_id
+        )?;
+        if let Some(temp) = self.instance_temp {
+            write!(f, " TEMP={}", format_value(temp))?;
+        }
+        Ok(())
+    }
+}
+
+impl MosfetMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MosfetMode::CutOff => "cutoff",
+            MosfetMode::Subthreshold => "subthreshold",
+            MosfetMode::Linear => "linear",
+            MosfetMode::Saturation => "saturation",
+        }
+    }
+}
+
+impl MosfetElementType {
+    pub(super) fn bind_model(&mut self, models: &MosfetModels) -> Result<(), String> {
+        let model = models
+            .get(&self.model_id)
+            .ok_or_else(|| format!("Unknown MOSFET model: {}", self.model_id))?;
+        self.model = Some(*model);
+        Ok(())
+    }
+
+    fn get_model(&self) -> &MosfetModel {
+        self.model.as_ref().expect("MOSFET model is not bound")
+    }
+
+    fn get_equations(&self) -> &'static dyn MosfetEquations {
+        self.get_model().get_equations()
+    }
+
+    /// Set the device temperature to the circuit temperature `temp`, unless
+    /// the instance has its own.
+    pub(super) fn set_temperature(&mut self, temp: f64) {
+        self.temp = self.instance_temp.unwrap_or(temp);
+    }
+
+    /// Get the ratio of the device temperature to the nominal temperature of
+    /// the model, both in kelvin.
+    fn get_temperature_ratio(&self) -> f64 {
+        (self.temp + KELVIN) / (self.get_model().tnom + KELVIN)
+    }
+
+    /// Get the zero-bias threshold voltage at the device temperature, in the
+    /// frame of an NMOS device.
+    fn get_vt0(&self) -> f64 {
+        let model = self.get_model();
+        self.get_polarity() * model.vth - model.tcv * (self.temp - model.tnom)
+            + model.kt1 * (self.get_temperature_ratio() - 1.)
+    }
+
+    /// Get the low-field mobility at the device temperature.
+    fn get_mu(&self) -> f64 {
+        let model = self.get_model();
+        model.mu * self.get_temperature_ratio().powf(model.bex)
+    }
+
+    /// Get the transconductance parameter `MU * COX` at the device
+    /// temperature.
+    fn get_mu_cox(&self) -> f64 {
+        let model = self.get_model();
+        model.get_mu_cox() * self.get_temperature_ratio().powf(model.bex)
+    }
+
+    fn get_thermal_voltage(&self) -> f64 {
+        BOLTZMANN_PER_CHARGE * (self.temp + KELVIN)
+    }
+
+    fn get_polarity(&self) -> f64 {
+        match self.mos_type {
+            MosfetType::Nmos => 1.,
+            MosfetType::Pmos => -1.,
+        }
+    }
+
+    /// Get the terminal voltages in the frame of an NMOS device, with the
+    /// drain and source exchanged when the device conducts in reverse.
+    fn get_bias(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> (MosfetBias, bool) {
+        let polarity = self.get_polarity();
+        let (v_gs, v_ds, v_bs) = (polarity * v_gs, polarity * v_ds, polarity * v_bs);
+        if v_ds >= 0. {
+            (MosfetBias { v_gs, v_ds, v_bs }, false)
+        } else {
+            let bias = MosfetBias {
+                v_gs: v_gs - v_ds,
+                v_ds: -v_ds,
+                v_bs: v_bs - v_ds,
+            };
+            (bias, true)
+        }
+    }
+
+    fn get_mode(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> MosfetMode {
+        let (bias, _) = self.get_bias(v_gs, v_ds, v_bs);
+        self.get_equations().get_mode(self, bias)
+    }
+
+    /// Get `(gm, gds, gmb)`, the derivatives of the drain current with
+    /// respect to `v_gs`, `v_ds` and `v_bs`.
+    fn get_conductances(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> (f64, f64, f64) {
+        let (bias, reversed) = self.get_bias(v_gs, v_ds, v_bs);
+        let equations = self.get_equations();
+        let gm = equations.get_gm(self, bias);
+        let gds = equations.get_gds(self, bias);
+        let gmb = equations.get_gmb(self, bias);
+        if reversed {
+            // The current of the reversed device is -Ids(v_gd, v_sd, v_bd).
+            (-gm, gm + gds + gmb, -gmb)
+        } else {
+            (gm, gds, gmb)
+        }
+    }
+
+    fn get_ids(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> f64 {
+        let (bias, reversed) = self.get_bias(v_gs, v_ds, v_bs);
+        let ids = self.get_polarity() * self.get_equations().get_ids(self, bias);
+        if reversed {
+            -ids
+        } else {
+            ids
+        }
+    }
+
+    fn get_vdsat(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> f64 {
+        let (bias, _) = self.get_bias(v_gs, v_ds, v_bs);
+        self.get_polarity() * self.get_equations().get_vdsat(self, bias)
+    }
+
+    fn get_ieq(&self, v_gs: f64, v_ds: f64, v_bs: f64) -> f64 {
+        let (gm, gds, gmb) = self.get_conductances(v_gs, v_ds, v_bs);
+        self.get_ids(v_gs, v_ds, v_bs) - gds * v_ds - gm * v_gs - gmb * v_bs
+    }
+
+    pub(super) fn get_operating_region(&self, x: &sprs::CsVec<f64>) -> &'static str {
+        let (v_gs, v_ds, v_bs) = self.get_voltages(x);
+        self.get_mode(v_gs, v_ds, v_bs).as_str()
+    }
+
+    pub(super) fn get_op_info(&self, x: &sprs::CsVec<f64>) -> MosfetOpInfo {
+        let (v_gs, v_ds, v_bs) = self.get_voltages(x);
+        let (gm, gds, gmb) = self.get_conductances(v_gs, v_ds, v_bs);
+        MosfetOpInfo {
+            region: self.get_mode(v_gs, v_ds, v_bs).as_str(),
+            id: self.get_ids(v_gs, v_ds, v_bs),
+            vgs: v_gs,
+            vds: v_ds,
+            vdsat: self.get_vdsat(v_gs, v_ds, v_bs),
+            gm,
+            gds,
+            gmb,
+        }
+    }
+
+    /// Get the names and terminals of the capacitances returned by
+    /// `get_capacitances`: gate-source, gate-drain, gate-bulk, drain-bulk and
+    /// source-bulk.
+    pub(super) fn get_capacitance_nodes(&self) -> [(&'static str, NodeId, NodeId); 5] {
+        let node_b = self.node_b;
+        [
+            ("CGS", self.node_g, self.node_s),
+            ("CGD", self.node_g, self.node_d),
+            ("CGB", self.node_g, node_b),
+            ("CDB", self.node_d, node_b),
+            ("CSB", self.node_s, node_b),
+        ]
+    }
+
+    /// Get the Meyer gate capacitances with the overlap capacitances in
+    /// parallel, and the junction capacitances at solution `x`.
+    pub(super) fn get_capacitances(&self, x: &sprs::CsVec<f64>) -> [f64; 5] {
+        let (v_gs, v_ds, v_bs) = self.get_voltages(x);
+        let (bias, reversed) = self.get_bias(v_gs, v_ds, v_bs);
+        let v_th = self.get_equations().get_vth(self, bias);
+        let model = self.get_model();
+        let cox = model.cox * self.w * self.l;
+        let (c_gs, c_gd, c_gb) =
+            get_meyer_capacitances(bias.v_gs - v_th, bias.v_ds, cox, model.phi);
+
+        // Forward bias of the bulk junctions of the acting drain and source.
+        let v_bd = bias.v_bs - bias.v_ds;
+        let c_bd = get_junction_capacitance(model, v_bd);
+        let c_bs = get_junction_capacitance(model, bias.v_bs);
+
+        let [c_gs, c_gd, c_gb, c_db, c_sb] = if reversed {
+            [c_gd, c_gs, c_gb, c_bs, c_bd]
+        } else {
+            [c_gs, c_gd, c_gb, c_bd, c_bs]
+        };
+        [
+            c_gs + model.cgso * self.w,
+            c_gd + model.cgdo * self.w,
+            c_gb + model.cgbo * self.l,
+            c_db,
+            c_sb,
+        ]
+    }
+
+    /// Get `(v_gs, v_ds, v_bs)` at solution `x`.
+    fn get_voltages(&self, x: &sprs::CsVec<f64>) -> (f64, f64, f64) {
+        use crate::matrix::ext::VecExt;
+
+        let v_g = x.get_by_node_id(self.node_g);
+        let v_d = x.get_by_node_id(self.node_d);
+        let v_s = x.get_by_node_id(self.node_s);
+        let v_b = x.get_by_node_id(self.node_b);
+
+        (v_g - v_s, v_d - v_s, v_b - v_s)
+    }
+}
+
+/// Meyer gate-source, gate-drain and gate-bulk capacitances for a gate
+/// overdrive `v_gst`, a non-negative `v_ds` and surface potential `phi`.
+fn get_meyer_capacitances(v_gst: f64, v_ds: f64, cox: f64, phi: f64) -> (f64, f64, f64) {
+    if v_gst <= -phi {
+        (0., 0., cox)
+    } else if v_gst <= 0. {
+        (2. / 3. * cox * (1. + v_gst / phi), 0., -cox * v_gst / phi)
+    } else if v_ds >= v_gst {
+        (2. / 3. * cox, 0., 0.)
+    } else {
+        let v_diff = 2. * v_gst - v_ds;
+        (
+            2. / 3. * cox * (1. - ((v_gst - v_ds) / v_diff).powi(2)),
+            2. / 3. * cox * (1. - (v_gst / v_diff).powi(2)),
+            0.,
+        )
+    }
+}
+
+/// Depletion capacitance of a drain or source junction of `model` at
+/// forward bias `v`.
+fn get_junction_capacitance(model: &MosfetModel, v: f64) -> f64 {
+    let MosfetModel {
+        cj0, pb, mj, fc, ..
+    } = *model;
+    if v < fc * pb {
+        cj0 / (1. - v / pb).powf(mj)
+    } else {
+        cj0 / (1. - fc).powf(1. + mj) * (1. - fc * (1. + mj) + mj * v / pb)
+    }
+}
+
+impl ConvergenceCheckable for MosfetElementType {
+    fn is_converged(
+        &self,
+        x_prev: &sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+        options: &ConvergenceOptions,
+    ) -> bool {
+        let (v_gs_prev, v_ds_prev, v_bs_prev) = self.get_voltages(x_prev);
+        let (v_gs, v_ds, v_bs) = self.get_voltages(x);
+
+        let (gm, gds, gmb) = self.get_conductances(v_gs_prev, v_ds_prev, v_bs_prev);
+        let predicted = self.get_ids(v_gs_prev, v_ds_prev, v_bs_prev)
+            + gm * (v_gs - v_gs_prev)
+            + gds * (v_ds - v_ds_prev)
+            + gmb * (v_bs - v_bs_prev);
+        let actual = self.get_ids(v_gs, v_ds, v_bs);
+
+        (actual - predicted).abs() <= options.current_tol(predicted, actual)
+    }
+}
+
+impl MatrixSettable for MosfetElementType {
+    fn set_matrix_dc(
+        &self,
+        mat: &mut crate::matrix::build::MatrixTriplets<f64>,
+        v: &mut crate::matrix::build::VecItems<f64>,
+    ) {
+        mat.push_with_node_id(self.node_d, self.node_d, 0.);
+        mat.push_with_node_id(self.node_d, self.node_s, 0.);
+        mat.push_with_node_id(self.node_s, self.node_d, 0.);
+        mat.push_with_node_id(self.node_s, self.node_s, 0.);
+
+        v.push_with_node_id(self.node_d, 0.);
+        v.push_with_node_id(self.node_s, 0.);
+        v.push_with_node_id(self.node_g, 0.);
+
+        mat.push_with_node_id(self.node_d, self.node_g, 0.);
+        mat.push_with_node_id(self.node_s, self.node_s, 0.);
+        mat.push_with_node_id(self.node_d, self.node_s, 0.);
+        mat.push_with_node_id(self.node_s, self.node_g, 0.);
+
+        mat.push_with_node_id(self.node_d, self.node_b, 0.);
+        mat.push_with_node_id(self.node_s, self.node_b, 0.);
+    }
+}
+
+impl MatrixDcUpdatable for MosfetElementType {
+    fn update_matrix_dc(
+        &self,
+        mat: &mut sprs::CsMat<f64>,
+        v: &mut sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+    ) {
+        use crate::matrix::ext::{MatExt, VecExt};
+
+        let (v_gs, v_ds, v_bs) = self.get_voltages(x);
+        let (gm, gds, gmb) = self.get_conductances(v_gs, v_ds, v_bs);
+
+        {
+            // Update gds
+            mat.add_by_node_id(self.node_d, self.node_d, gds);
+            mat.add_by_node_id(self.node_d, self.node_s, -gds);
+            mat.add_by_node_id(self.node_s, self.node_d, -gds);
+            mat.add_by_node_id(self.node_s, self.node_s, gds);
+        }
+
+        {
+            // Update ieq
+            let ieq = self.get_ieq(v_gs, v_ds, v_bs);
+            v.add_by_node_id(self.node_d, -ieq);
+            v.add_by_node_id(self.node_s, ieq);
+        }
+
+        {
+            // Update gm
+            mat.add_by_node_id(self.node_d, self.node_g, gm);
+            mat.add_by_node_id(self.node_s, self.node_s, gm);
+            mat.add_by_node_id(self.node_d, self.node_s, -gm);
+            mat.add_by_node_id(self.node_s, self.node_g, -gm);
+        }
+
+        {
+            // Update gmb
+            mat.add_by_node_id(self.node_d, self.node_b, gmb);
+            mat.add_by_node_id(self.node_s, self.node_s, gmb);
+            mat.add_by_node_id(self.node_d, self.node_s, -gmb);
+            mat.add_by_node_id(self.node_s, self.node_b, -gmb);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use super::equations::{
+    get_body_effect, get_effective_overdrive, get_velocity_saturated_ids,
+    get_velocity_saturated_vdsat, MosfetBias, MosfetEquations,
+};
+use super::MosfetElementType;
+
+/// Short-channel model (`LEVEL 2` or `3`): the square law with mobility
+/// degradation `THETA`, velocity saturation at `VMAX` and drain-induced
+/// barrier lowering `ETA`, which lowers the threshold by `ETA * Vds`. Like
+/// `LEVEL 1` it conducts below the threshold when the model has `N`.
+pub(super) struct ShortChannel;
+
+impl ShortChannel {
+    /// Get the effective overdrive, the gain factor reduced by mobility
+    /// degradation and the drain-source voltage at which the carriers
+    /// saturate.
+    fn get_channel(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> (f64, f64, f64) {
+        let model = mosfet.get_model();
+        let (v_gst, _) = get_effective_overdrive(
+            mosfet,
+            self.get_slope_factor(model),
+            bias.v_gs - self.get_vth(mosfet, bias),
+        );
+        let degradation = 1. + model.theta * v_gst.max(0.);
+        let beta = mosfet.get_mu_cox() / degradation * mosfet.w / mosfet.l;
+        // A card with KP but no MU leaves the carriers unsaturated.
+        let v_c = if model.vmax > 0. && model.mu > 0. {
+            model.vmax * mosfet.l * degradation / mosfet.get_mu()
+        } else {
+            f64::INFINITY
+        };
+        (v_gst, beta, v_c)
+    }
+}
+
+impl MosfetEquations for ShortChannel {
+    fn get_vth(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let model = mosfet.get_model();
+        mosfet.get_vt0() + get_body_effect(model.gamma, model.phi, bias.v_bs)
+            - model.eta * bias.v_ds
+    }
+
+    fn get_vdsat(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let (v_gst, _, v_c) = self.get_channel(mosfet, bias);
+        get_velocity_saturated_vdsat(v_gst, v_c)
+    }
+
+    fn get_ids(&self, mosfet: &MosfetElementType, bias: MosfetBias) -> f64 {
+        let (v_gst, beta, v_c) = self.get_channel(mosfet, bias);
+        get_velocity_saturated_ids(beta, v_gst, bias.v_ds, v_c, mosfet.get_model().lambda)
+    }
+}
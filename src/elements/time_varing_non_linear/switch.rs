@@ -0,0 +1,277 @@
+use std::cell::Cell;
+use std::collections::BTreeMap as Map;
+use std::fmt;
+
+use super::super::base::{format_value, ConvergenceCheckable, MatrixDcUpdatable, MatrixSettable};
+use crate::matrix::ext::{MatExt, VecExt};
+use crate::netlist::NodeId;
+use crate::solver::base::ConvergenceOptions;
+
+/// Parameters of a `.MODEL id SW` or `.MODEL id CSW` card.
+#[derive(Debug, Clone, Copy)]
+pub struct SwitchModel {
+    /// Controlled by the current through a voltage source (`CSW`) rather
+    /// than by a voltage (`SW`).
+    current_controlled: bool,
+    ron: f64,
+    roff: f64,
+    /// Control threshold `VT` or `IT`.
+    threshold: f64,
+    /// Hysteresis `VH` or `IH`: the switch turns on above
+    /// `threshold + hysteresis` and off below `threshold - hysteresis`.
+    hysteresis: f64,
+}
+
+pub type SwitchModels = Map<usize, SwitchModel>;
+
+/// Default off resistance, the inverse of the smallest conductance.
+const ROFF: f64 = 1e12;
+
+impl SwitchModel {
+    pub fn new(
+        current_controlled: bool,
+        ron: f64,
+        roff: f64,
+        threshold: f64,
+        hysteresis: f64,
+    ) -> Self {
+        Self {
+            current_controlled,
+            ron,
+            roff,
+            threshold,
+            hysteresis,
+        }
+    }
+
+    /// Check whether `s` is a `.MODEL` card of a switch.
+    pub fn is_switch_model(s: &str) -> bool {
+        s.split_whitespace()
+            .nth(2)
+            .map(|kind| kind.split('(').next().unwrap().to_ascii_uppercase())
+            .is_some_and(|kind| kind == "SW" || kind == "CSW")
+    }
+
+    /// Parse `.MODEL id SW|CSW [(]KEY VALUE ...[)]`, where pairs may also be
+    /// written `KEY=VALUE`.
+    pub fn parse(s: &str) -> Result<(usize, Self), String> {
+        let line = s.replace(['(', ')', '='], " ");
+        let mut iter = line.split_whitespace().skip(1);
+
+        let model_id = iter
+            .next()
+            .and_then(|id| id.parse::<usize>().ok())
+            .ok_or_else(|| format!("Invalid model: {}", s))?;
+        let current_controlled = iter.next().unwrap().eq_ignore_ascii_case("CSW");
+        let mut model = Self::new(current_controlled, 1., ROFF, 0., 0.);
+
+        while let Some(key) = iter.next() {
+            let key = key.to_ascii_uppercase();
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("Missing value of model parameter {}", key))?;
+            let value = value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value of model parameter {}: {}", key, value))?;
+
+            match (key.as_str(), current_controlled) {
+                ("RON", _) => model.ron = value,
+                ("ROFF", _) => model.roff = value,
+                ("VT", false) | ("IT", true) => model.threshold = value,
+                ("VH", false) | ("IH", true) => model.hysteresis = value,
+                _ => return Err(format!("Invalid switch model parameter: {}", key)),
+            }
+        }
+
+        if !(model.ron > 0. && model.roff > 0.) {
+            return Err(format!("Invalid switch resistance in model {}", model_id));
+        }
+        Ok((model_id, model))
+    }
+}
+
+/// The parameters of a `.MODEL` card, without the model ID.
+impl fmt::Display for SwitchModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, unit) = if self.current_controlled {
+            ("CSW", "I")
+        } else {
+            ("SW", "V")
+        };
+        write!(
+            f,
+            "{} RON {} ROFF {} {}T {} {}H {}",
+            kind,
+            format_value(self.ron),
+            format_value(self.roff),
+            unit,
+            format_value(self.threshold),
+            unit,
+            format_value(self.hysteresis)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SwitchControl {
+    /// The voltage between two nodes (`S` elements).
+    Voltage(NodeId, NodeId),
+    /// The current through a voltage source (`W` elements), with the
+    /// position of its branch current once the matrix is set up.
+    Current(String, Cell<NodeId>),
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct SwitchElementType {
+    pub(super) node_p: NodeId,
+    pub(super) node_n: NodeId,
+    pub(super) control: SwitchControl,
+    pub(super) model_id: usize,
+    /// Bound from `.MODEL` cards once the whole netlist has been read.
+    pub(super) model: Option<SwitchModel>,
+    /// Initial state given with `ON` on the instance line.
+    pub(super) initial_on: bool,
+    /// State at the last accepted solution, from which the hysteresis
+    /// decides the next one.
+    pub(super) on: Cell<bool>,
+    /// Set once a transient step has been accepted. From then on the state
+    /// only changes between steps, so that Newton iterations within a step
+    /// see a fixed resistance.
+    pub(super) latched: Cell<bool>,
+}
+
+impl fmt::Display for SwitchElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.node_p, self.node_n)?;
+        match &self.control {
+            SwitchControl::Voltage(node_cp, node_cn) => write!(f, "{} {} ", node_cp, node_cn)?,
+            SwitchControl::Current(source, _) => write!(f, "{} ", source)?,
+        }
+        write!(f, "{}", self.model_id)?;
+        if self.initial_on {
+            write!(f, " ON")?;
+        }
+        Ok(())
+    }
+}
+
+impl SwitchElementType {
+    pub(super) fn bind_model(&mut self, models: &SwitchModels) -> Result<(), String> {
+        let model = models
+            .get(&self.model_id)
+            .ok_or_else(|| format!("Unknown switch model: {}", self.model_id))?;
+        let current_controlled = matches!(self.control, SwitchControl::Current(..));
+        if model.current_controlled != current_controlled {
+            return Err(format!(
+                "Switch model {} is not {}",
+                self.model_id,
+                if current_controlled { "CSW" } else { "SW" }
+            ));
+        }
+        self.model = Some(*model);
+        Ok(())
+    }
+
+    fn get_model(&self) -> &SwitchModel {
+        self.model.as_ref().expect("Switch model is not bound")
+    }
+
+    fn get_control(&self, x: &sprs::CsVec<f64>) -> f64 {
+        match &self.control {
+            SwitchControl::Voltage(node_cp, node_cn) => {
+                x.get_by_node_id(*node_cp) - x.get_by_node_id(*node_cn)
+            }
+            SwitchControl::Current(_, branch) => x.get_by_node_id(branch.get()),
+        }
+    }
+
+    /// Get whether the switch is on at solution `x`, starting from the
+    /// state at the last accepted solution.
+    pub(super) fn is_on(&self, x: &sprs::CsVec<f64>) -> bool {
+        let model = self.get_model();
+        let control = self.get_control(x);
+        if self.on.get() {
+            control >= model.threshold - model.hysteresis
+        } else {
+            control > model.threshold + model.hysteresis
+        }
+    }
+
+    /// Get the state stamped at solution `x`: the accepted state during a
+    /// transient analysis, otherwise the one decided by `x`.
+    pub(super) fn get_state(&self, x: &sprs::CsVec<f64>) -> bool {
+        if self.latched.get() {
+            self.on.get()
+        } else {
+            self.is_on(x)
+        }
+    }
+
+    fn get_conductance(&self, on: bool) -> f64 {
+        let model = self.get_model();
+        if on {
+            1. / model.ron
+        } else {
+            1. / model.roff
+        }
+    }
+
+    /// Get the current from `node_p` to `node_n` at solution `x`.
+    pub(super) fn get_current(&self, x: &sprs::CsVec<f64>) -> f64 {
+        let v = x.get_by_node_id(self.node_p) - x.get_by_node_id(self.node_n);
+        self.get_conductance(self.get_state(x)) * v
+    }
+
+    /// Keep the state at the accepted solution `x` for the next step.
+    pub(super) fn accept(&self, x: &sprs::CsVec<f64>) {
+        self.on.set(self.is_on(x));
+        self.latched.set(true);
+    }
+}
+
+impl MatrixDcUpdatable for SwitchElementType {
+    fn update_matrix_dc(
+        &self,
+        mat: &mut sprs::CsMat<f64>,
+        _v: &mut sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+    ) {
+        let g = self.get_conductance(self.get_state(x));
+        mat.add_by_node_id(self.node_p, self.node_p, g);
+        mat.add_by_node_id(self.node_p, self.node_n, -g);
+        mat.add_by_node_id(self.node_n, self.node_p, -g);
+        mat.add_by_node_id(self.node_n, self.node_n, g);
+    }
+}
+
+impl ConvergenceCheckable for SwitchElementType {
+    /// The stamped resistance holds when the state no longer changes.
+    fn is_converged(
+        &self,
+        x_prev: &sprs::CsVec<f64>,
+        x: &sprs::CsVec<f64>,
+        _options: &ConvergenceOptions,
+    ) -> bool {
+        self.get_state(x_prev) == self.get_state(x)
+    }
+}
+
+impl MatrixSettable for SwitchElementType {
+    fn set_matrix_dc(
+        &self,
+        mat: &mut crate::matrix::build::MatrixTriplets<f64>,
+        _v: &mut crate::matrix::build::VecItems<f64>,
+    ) {
+        mat.push_with_node_id(self.node_p, self.node_p, 0.);
+        mat.push_with_node_id(self.node_p, self.node_n, 0.);
+        mat.push_with_node_id(self.node_n, self.node_p, 0.);
+        mat.push_with_node_id(self.node_n, self.node_n, 0.);
+
+        if let SwitchControl::Current(source, branch) = &self.control {
+            branch.set(
+                mat.get_branch_node_id(source)
+                    .expect("Unknown controlling source"),
+            );
+        }
+    }
+}
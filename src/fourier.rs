@@ -0,0 +1,507 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::elements::base::{format_value, parse_parameter_value};
+use crate::plot::{plot_panels, Panel, PlotData, Trace};
+use crate::result::{AnalysisResult, SignalExpr};
+
+/// Default number of harmonics reported by `.FOUR`, the DC component
+/// included.
+const DEFAULT_HARMONIC_NUM: usize = 10;
+
+/// Points per period used to resample the waveform for `.FOUR`.
+const FOUR_GRID_SIZE: usize = 200;
+
+const DEFAULT_FFT_POINTS: usize = 1024;
+
+/// Resample `(x, y)` onto `n` points spaced `step` apart from `start` by
+/// linear interpolation. `x` must be increasing and cover the grid.
+fn resample(x: &[f64], y: &[f64], start: f64, step: f64, n: usize) -> Vec<f64> {
+    let mut j = 0;
+    (0..n)
+        .map(|i| {
+            let t = start + i as f64 * step;
+            while j + 2 < x.len() && x[j + 1] < t {
+                j += 1;
+            }
+            let (x0, x1) = (x[j], x[j + 1]);
+            if x1 == x0 {
+                y[j]
+            } else {
+                y[j] + (y[j + 1] - y[j]) * (t - x0) / (x1 - x0)
+            }
+        })
+        .collect()
+}
+
+/// Get the time points and values of `expr`.
+fn get_waveform(
+    result: &AnalysisResult,
+    expr: &SignalExpr,
+) -> Result<(Vec<f64>, Vec<f64>), String> {
+    if result.sweep_values.len() < 2 {
+        return Err("Fourier analysis needs a transient result".to_string());
+    }
+    Ok((result.sweep_values.clone(), expr.evaluate(result)?))
+}
+
+/// One harmonic of a `.FOUR` analysis.
+#[derive(Debug, Clone, Copy)]
+pub struct Harmonic {
+    pub frequency: f64,
+    pub magnitude: f64,
+    /// Phase in degrees, relative to a sine starting at the window start.
+    pub phase: f64,
+}
+
+/// A `.FOUR <freq> [NH=<n>] <signal>...` directive.
+#[derive(Debug, Clone)]
+pub struct FourSpec {
+    pub frequency: f64,
+    /// Harmonics to report, the DC component included.
+    pub harmonic_num: usize,
+    pub signals: Vec<SignalExpr>,
+}
+
+impl fmt::Display for FourSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ".FOUR {}", format_value(self.frequency))?;
+        if self.harmonic_num != DEFAULT_HARMONIC_NUM {
+            write!(f, " NH={}", self.harmonic_num)?;
+        }
+        for signal in &self.signals {
+            write!(f, " {}", signal)?;
+        }
+        Ok(())
+    }
+}
+
+impl FourSpec {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut iter = s.split_whitespace().skip(1);
+        let frequency = iter
+            .next()
+            .ok_or("Missing frequency in .FOUR")?
+            .parse::<f64>()
+            .map_err(|_| "Invalid frequency in .FOUR".to_string())?;
+        if frequency <= 0. {
+            return Err("Frequency in .FOUR must be positive".to_string());
+        }
+        let mut harmonic_num = DEFAULT_HARMONIC_NUM;
+        let mut signals = Vec::new();
+        for item in iter {
+            match item.split_once('=') {
+                Some((key, value)) if key.eq_ignore_ascii_case("NH") => {
+                    // The fundamental is needed for THD, and the grid
+                    // resolves harmonics up to half its size.
+                    harmonic_num = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| (2..=FOUR_GRID_SIZE / 2).contains(n))
+                        .ok_or_else(|| {
+                            format!("NH must be between 2 and {}: {}", FOUR_GRID_SIZE / 2, value)
+                        })?
+                }
+                Some((key, _)) => return Err(format!("Unknown .FOUR parameter: {}", key)),
+                None => signals.push(SignalExpr::parse(item)?),
+            }
+        }
+        if signals.is_empty() {
+            return Err("No signals in .FOUR".to_string());
+        }
+        Ok(Self {
+            frequency,
+            harmonic_num,
+            signals,
+        })
+    }
+
+    /// Fourier coefficients of the last period of `expr`.
+    pub fn get_harmonics(
+        &self,
+        result: &AnalysisResult,
+        expr: &SignalExpr,
+    ) -> Result<Vec<Harmonic>, String> {
+        let (x, y) = get_waveform(result, expr)?;
+        let period = 1. / self.frequency;
+        let end = x[x.len() - 1];
+        let start = end - period;
+        if start < x[0] {
+            return Err(format!(
+                "Transient of {} s is shorter than one period of {} Hz",
+                end - x[0],
+                self.frequency
+            ));
+        }
+
+        let n = FOUR_GRID_SIZE;
+        let samples = resample(&x, &y, start, period / n as f64, n);
+
+        Ok((0..self.harmonic_num)
+            .map(|k| {
+                let (mut sin_part, mut cos_part) = (0., 0.);
+                for (i, value) in samples.iter().enumerate() {
+                    let angle = 2. * PI * (k * i) as f64 / n as f64;
+                    sin_part += value * angle.sin();
+                    cos_part += value * angle.cos();
+                }
+                let (magnitude, phase) = if k == 0 {
+                    (cos_part / n as f64, 0.)
+                } else {
+                    let (s, c) = (2. * sin_part / n as f64, 2. * cos_part / n as f64);
+                    (s.hypot(c), c.atan2(s).to_degrees())
+                };
+                Harmonic {
+                    frequency: k as f64 * self.frequency,
+                    magnitude,
+                    phase,
+                }
+            })
+            .collect())
+    }
+
+    /// Print the harmonic table and THD of every signal, as in SPICE.
+    pub fn print(&self, result: &AnalysisResult, disp_digits: usize) -> Result<(), String> {
+        for expr in &self.signals {
+            let harmonics = self.get_harmonics(result, expr)?;
+            let fundamental = harmonics[1];
+
+            println!("Fourier analysis for {}:", expr.label);
+            println!(
+                "  No. Harmonics: {}, THD: {:.width$} %, Gridsize: {}",
+                self.harmonic_num,
+                get_thd(&harmonics),
+                FOUR_GRID_SIZE,
+                width = disp_digits
+            );
+            println!(
+                "{:>8} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$}",
+                "Harmonic",
+                "Frequency",
+                "Magnitude",
+                "Phase",
+                "Norm. Mag",
+                "Norm. Phase",
+                w = disp_digits + 8
+            );
+            for (k, h) in harmonics.iter().enumerate() {
+                println!(
+                    "{:>8} {:>w$.p$e} {:>w$.p$e} {:>w$.p$} {:>w$.p$e} {:>w$.p$}",
+                    k,
+                    h.frequency,
+                    h.magnitude,
+                    h.phase,
+                    h.magnitude / fundamental.magnitude,
+                    h.phase - fundamental.phase,
+                    w = disp_digits + 8,
+                    p = disp_digits
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Total harmonic distortion in percent: the RMS of harmonics 2 and above
+/// relative to the fundamental.
+pub fn get_thd(harmonics: &[Harmonic]) -> f64 {
+    let distortion = harmonics[2..]
+        .iter()
+        .map(|h| h.magnitude * h.magnitude)
+        .sum::<f64>()
+        .sqrt();
+    100. * distortion / harmonics[1].magnitude
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Blackman,
+}
+
+impl Window {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Window::Rectangular => "RECT",
+            Window::Hann => "HANN",
+            Window::Blackman => "BLACKMAN",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_uppercase().as_str() {
+            "RECT" | "RECTANGULAR" | "NONE" => Ok(Window::Rectangular),
+            "HANN" | "HANNING" => Ok(Window::Hann),
+            "BLACKMAN" => Ok(Window::Blackman),
+            _ => Err(format!("Unknown window: {}", s)),
+        }
+    }
+
+    /// Periodic window of length `n`.
+    fn get_weights(&self, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|i| {
+                let phase = 2. * PI * i as f64 / n as f64;
+                match self {
+                    Window::Rectangular => 1.,
+                    Window::Hann => 0.5 - 0.5 * phase.cos(),
+                    Window::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// In-place radix-2 FFT of `(re, im)` pairs. The length must be a power of two.
+fn fft(data: &mut [(f64, f64)]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2. * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = ((angle * k as f64).cos(), (angle * k as f64).sin());
+                let (ar, ai) = data[start + k];
+                let (br, bi) = data[start + k + len / 2];
+                let (tr, ti) = (br * wr - bi * wi, br * wi + bi * wr);
+                data[start + k] = (ar + tr, ai + ti);
+                data[start + k + len / 2] = (ar - tr, ai - ti);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// An `.FFT` directive: the spectrum of a transient waveform, resampled
+/// onto a uniform grid and windowed.
+#[derive(Debug, Clone)]
+pub struct FftSpec {
+    pub signal: SignalExpr,
+    pub points: usize,
+    pub window: Window,
+    pub start: Option<f64>,
+    pub stop: Option<f64>,
+    pub file: Option<PathBuf>,
+}
+
+impl fmt::Display for FftSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ".FFT {} NP={} WINDOW={}",
+            self.signal,
+            self.points,
+            self.window.as_str()
+        )?;
+        if let Some(start) = self.start {
+            write!(f, " START={}", format_value(start))?;
+        }
+        if let Some(stop) = self.stop {
+            write!(f, " STOP={}", format_value(stop))?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " FILE={}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl FftSpec {
+    /// Parse a line like
+    /// `.FFT V(2) NP=1024 WINDOW=HANN START=1e-3 STOP=2e-3 FILE=spectrum.svg`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut iter = s.split_whitespace().skip(1);
+        let signal = SignalExpr::parse(iter.next().ok_or("Missing signal in .FFT")?)?;
+        let mut spec = Self {
+            signal,
+            points: DEFAULT_FFT_POINTS,
+            window: Window::Hann,
+            start: None,
+            stop: None,
+            file: None,
+        };
+
+        for item in iter {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid .FFT parameter: {}", item))?;
+            match key.to_ascii_uppercase().as_str() {
+                "NP" => {
+                    spec.points = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|n| n.is_power_of_two() && *n >= 2)
+                        .ok_or_else(|| format!("NP must be a power of two: {}", value))?
+                }
+                "WINDOW" => spec.window = Window::parse(value)?,
+                "START" => spec.start = Some(parse_parameter_value(key, value)?),
+                "STOP" => spec.stop = Some(parse_parameter_value(key, value)?),
+                "FILE" => spec.file = Some(PathBuf::from(value)),
+                _ => return Err(format!("Unknown .FFT parameter: {}", key)),
+            }
+        }
+        Ok(spec)
+    }
+
+    pub fn get_file(&self, index: usize) -> PathBuf {
+        self.file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("fft_{}.svg", index + 1)))
+    }
+
+    /// Single-sided amplitude spectrum: frequencies and magnitudes, scaled so
+    /// that a sine of amplitude `A` at a bin frequency shows up as `A`.
+    pub fn get_spectrum(&self, result: &AnalysisResult) -> Result<(Vec<f64>, Vec<f64>), String> {
+        let (x, y) = get_waveform(result, &self.signal)?;
+        let start = self.start.unwrap_or(x[0]).max(x[0]);
+        let stop = self.stop.unwrap_or(x[x.len() - 1]).min(x[x.len() - 1]);
+        if stop <= start {
+            return Err(format!("Empty .FFT window from {} to {}", start, stop));
+        }
+
+        let n = self.points;
+        let step = (stop - start) / n as f64;
+        let samples = resample(&x, &y, start, step, n);
+        let weights = self.window.get_weights(n);
+        let weight_sum = weights.iter().sum::<f64>();
+
+        let mut data = samples
+            .iter()
+            .zip(weights.iter())
+            .map(|(s, w)| (s * w, 0.))
+            .collect::<Vec<_>>();
+        fft(&mut data);
+
+        let frequencies = (0..=n / 2).map(|k| k as f64 / (n as f64 * step)).collect();
+        let magnitudes = data[..=n / 2]
+            .iter()
+            .enumerate()
+            .map(|(k, (re, im))| {
+                let scale = if k == 0 { 1. } else { 2. };
+                scale * re.hypot(*im) / weight_sum
+            })
+            .collect();
+        Ok((frequencies, magnitudes))
+    }
+
+    /// Plot the spectrum in dB into `file`.
+    pub fn plot(
+        &self,
+        result: &AnalysisResult,
+        file: &std::path::Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (frequencies, magnitudes) = self.get_spectrum(result)?;
+        let db = magnitudes
+            .iter()
+            .map(|m| 20. * m.max(1e-15).log10())
+            .collect::<Vec<_>>();
+        let panels = [Panel {
+            traces: vec![Trace {
+                name: self.signal.label.clone(),
+                values: db,
+            }],
+            y_label: "Magnitude / dB",
+        }];
+        let data = PlotData {
+            x_values: &frequencies,
+            x_label: "Frequency / Hz",
+            panels: &panels,
+            x_log: false,
+            y_log: false,
+            size: None,
+        };
+        plot_panels(&data, file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlist::Unknowns;
+    use crate::options::Options;
+    use crate::result::{AnalysisKind, Signal, SignalKind};
+    use sprs::CsVec;
+
+    /// Transient result of `v(1) = 1 + sin(wt) + 0.1 sin(3wt)` at 1 kHz.
+    fn get_test_result() -> AnalysisResult {
+        let unknowns = Unknowns {
+            voltage_num: 1,
+            branch_names: Vec::new(),
+            internal: Vec::new(),
+            node_names: Vec::new(),
+        };
+        let mut result = AnalysisResult::new(
+            AnalysisKind::Transient,
+            Some(Signal::new("time", SignalKind::Time)),
+            &unknowns,
+            &Options::default(),
+        );
+        let w = 2. * PI * 1e3;
+        for i in 0..=4000 {
+            let t = i as f64 * 1e-6;
+            let v = 1. + (w * t).sin() + 0.1 * (3. * w * t).sin();
+            result.push(Some(t), &CsVec::new(1, vec![0], vec![v]));
+        }
+        result
+    }
+
+    #[test]
+    fn test_four() -> Result<(), String> {
+        let result = get_test_result();
+        let spec = FourSpec::parse(".FOUR 1000 V(1)")?;
+        let harmonics = spec.get_harmonics(&result, &spec.signals[0])?;
+        assert!((harmonics[0].magnitude - 1.).abs() < 1e-3);
+        assert!((harmonics[1].magnitude - 1.).abs() < 1e-3);
+        assert!(harmonics[1].phase.abs() < 0.1);
+        assert!((harmonics[3].magnitude - 0.1).abs() < 1e-3);
+        assert!((get_thd(&harmonics) - 10.).abs() < 0.1);
+        assert_eq!(harmonics.len(), 10);
+
+        let spec = FourSpec::parse(".FOUR 1000 NH=4 V(1)")?;
+        let harmonics = spec.get_harmonics(&result, &spec.signals[0])?;
+        assert_eq!(harmonics.len(), 4);
+        assert!((harmonics[3].frequency - 3e3).abs() < 1e-6);
+        assert!((get_thd(&harmonics) - 10.).abs() < 0.1);
+        assert!(FourSpec::parse(".FOUR 1000 NH=1 V(1)").is_err());
+        assert!(FourSpec::parse(".FOUR 1000 NP=4 V(1)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fft() -> Result<(), String> {
+        let result = get_test_result();
+        for window in ["RECT", "HANN", "BLACKMAN"] {
+            let line = format!(".FFT V(1) NP=256 WINDOW={} STOP=4e-3", window);
+            let spec = FftSpec::parse(&line)?;
+            let (frequencies, magnitudes) = spec.get_spectrum(&result)?;
+            // 4 periods in the window put the fundamental in bin 4.
+            assert!((frequencies[4] - 1e3).abs() < 1e-6);
+            // Skip the bins the windows smear the DC component into.
+            let peak = (3..magnitudes.len())
+                .max_by(|a, b| magnitudes[*a].total_cmp(&magnitudes[*b]))
+                .unwrap();
+            assert_eq!(peak, 4);
+            assert!((magnitudes[4] - 1.).abs() < 0.05);
+            assert!((magnitudes[12] - 0.1).abs() < 0.01);
+        }
+
+        let spec = FftSpec::parse(".FFT V(2) NP=1024 WINDOW=HANN START=1e-3 STOP=2e-3")?;
+        assert_eq!((spec.start, spec.stop), (Some(1e-3), Some(2e-3)));
+        Ok(())
+    }
+}
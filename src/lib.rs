@@ -0,0 +1,50 @@
+//! A small SPICE-like circuit simulator.
+//!
+//! Decks can be simulated in-process with [`Simulator`]:
+//!
+//! ```
+//! use tiny_spice::Simulator;
+//!
+//! let simulator = Simulator::parse("V1 1 0 DC 5\nR1 1 2 1000\nR2 2 0 1000\n")?;
+//! let op = simulator.op()?;
+//! let v2 = op.get_signal("v(2)").unwrap();
+//! assert!((v2[0] - 2.5).abs() < 1e-9);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod builder;
+pub mod elements;
+pub mod netlist;
+pub mod options;
+pub mod result;
+pub mod simulator;
+
+// Used by the command-line driver, but not part of the library API.
+#[doc(hidden)]
+pub mod analyze;
+#[doc(hidden)]
+pub mod op_report;
+#[doc(hidden)]
+pub mod output;
+#[doc(hidden)]
+pub mod parser;
+#[doc(hidden)]
+pub mod plot;
+#[doc(hidden)]
+pub mod raw;
+#[doc(hidden)]
+pub mod writer;
+
+mod fourier;
+mod matrix;
+mod measure;
+mod print;
+mod solver;
+mod task;
+mod topology;
+
+pub use builder::NetlistBuilder;
+pub use netlist::Netlist;
+pub use options::Options;
+pub use result::{AnalysisKind, AnalysisResult, Signal, SignalKind};
+pub use simulator::Simulator;
@@ -1,20 +1,10 @@
 use clap::Parser;
-use std::cell::Cell;
+use std::io::Write;
 use std::path::PathBuf;
 
-use log::{error, info};
+use log::{error, info, warn};
 
-#[macro_use]
-extern crate lazy_static;
-
-mod analyze;
-mod elements;
-mod matrix;
-mod netlist;
-mod parser;
-mod plot;
-mod solver;
-mod task;
+use tiny_spice::{analyze, netlist, output, parser, plot, raw, result, writer};
 
 #[derive(Parser, Debug)]
 #[clap(author = "0xtaruhi", version, about)]
@@ -28,24 +18,52 @@ struct Opts {
     #[clap(short, long)]
     final_time: Option<f64>,
 
+    /// Write all node voltages and branch currents to a raw file
+    #[clap(long)]
+    raw: Option<PathBuf>,
+
+    /// Encoding of the raw file: ascii or binary
+    #[clap(long, default_value = "ascii")]
+    raw_format: String,
+
+    /// Write the analysis result to a CSV or JSON file
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Format of the output file: csv or json, guessed from the extension if omitted
+    #[clap(long)]
+    format: Option<String>,
+
+    /// Write the parsed netlist back as SPICE text
+    #[clap(long)]
+    netlist: Option<PathBuf>,
+
     file: PathBuf,
 }
 
 fn run(opts: Opts) -> Result<(), Box<dyn std::error::Error>> {
+    let title = opts.file.display().to_string();
     let parser = parser::Parser::new(opts.file);
-    let parsed_info = parser.parse().map_err(|e| {
+    let mut parsed_info = parser.parse().map_err(|e| {
         error!("Failed to parse file: {}", e);
         e
     })?;
     info!("Parse successful");
 
+    let netlist = netlist::Netlist::from_parsed(&mut parsed_info);
+    if let Some(path) = &opts.netlist {
+        let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writer::write_netlist(&mut w, &title, &netlist, &parsed_info)
+            .and_then(|_| w.flush())
+            .map_err(|e| format!("Failed to write netlist {}: {}", path.display(), e))?;
+        info!("Netlist written to {}", path.display());
+    }
     let tasks = parsed_info.tasks;
-    let netlist = netlist::Netlist {
-        node_num: Cell::new(parsed_info.node_num),
-        basic_elements: parsed_info.basic_elements,
-        time_varing_linear_elements: parsed_info.time_varing_linear_elements,
-        time_varing_non_linear_elements: parsed_info.time_varing_non_linear_elements,
-    };
+    let plots = parsed_info.plots;
+    let prints = parsed_info.prints;
+    let measurements = parsed_info.measurements;
+    let fours = parsed_info.fours;
+    let ffts = parsed_info.ffts;
 
     let mode: analyze::Mode = {
         if let Some(m) = opts.mode {
@@ -57,6 +75,28 @@ fn run(opts: Opts) -> Result<(), Box<dyn std::error::Error>> {
 
     let mut analyzer = analyze::Analyzer::new(netlist);
     analyzer.set_mode(mode);
+    analyzer.set_options(parsed_info.options);
+    if let Some(dc_sweep) = parsed_info.dc_sweep {
+        analyzer.set_dc_sweep(dc_sweep);
+    }
+    let raw_output = match opts.raw {
+        Some(path) => Some(raw::RawOutput {
+            path,
+            format: raw::RawFormat::parse(&opts.raw_format)?,
+            title,
+        }),
+        None => None,
+    };
+    let result_output = match opts.output {
+        Some(path) => Some(output::ResultOutput {
+            format: match &opts.format {
+                Some(f) => output::OutputFormat::parse(f)?,
+                None => output::OutputFormat::from_path(&path),
+            },
+            path,
+        }),
+        None => None,
+    };
     if let Some(d) = opts.disp {
         analyzer.set_disp_digits(d);
     }
@@ -68,11 +108,84 @@ fn run(opts: Opts) -> Result<(), Box<dyn std::error::Error>> {
         analyzer.set_final_time(t);
     }
 
-    analyzer.analyze(&tasks).map_err(|e| {
+    let mut result = analyzer.analyze(&tasks).map_err(|e| {
         error!("Failed to analyze: {}", e);
         e
     })?;
     info!("Analysis successful");
+
+    for spec in measurements.iter().filter(|m| m.analysis == result.kind) {
+        let measurement = spec.evaluate(&result)?;
+        match measurement.value {
+            Some(value) => println!(
+                "{} = {:.width$e}",
+                measurement.name,
+                value,
+                width = analyzer.get_disp_digits()
+            ),
+            None => {
+                warn!("Measurement {} failed", measurement.name);
+                println!("{} = failed", measurement.name);
+            }
+        }
+        result.measurements.push(measurement);
+    }
+
+    if result.kind == result::AnalysisKind::Transient {
+        for spec in fours.iter() {
+            spec.print(&result, analyzer.get_disp_digits())
+                .map_err(|e| format!("Fourier analysis failed: {}", e))?;
+        }
+        for (i, spec) in ffts.iter().enumerate() {
+            let file = spec.get_file(i);
+            spec.plot(&result, &file)
+                .map_err(|e| format!("Failed to plot {}: {}", file.display(), e))?;
+            info!("Spectrum written to {}", file.display());
+        }
+    }
+
+    for spec in prints.iter() {
+        if spec.analysis.is_some_and(|kind| kind != result.kind) {
+            continue;
+        }
+        spec.print(&result, analyzer.get_disp_digits())
+            .map_err(|e| format!("Failed to print: {}", e))?;
+    }
+
+    for (i, spec) in plots.iter().enumerate() {
+        if spec.analysis.is_some_and(|kind| kind != result.kind) {
+            continue;
+        }
+        if result.sweep.is_none() {
+            warn!("Nothing to plot for an operating point, skipping .PLOT");
+            continue;
+        }
+        let file = spec.get_file(i);
+        plot::plot_result(&result, spec, &file)
+            .map_err(|e| format!("Failed to plot {}: {}", file.display(), e))?;
+        info!("Plot written to {}", file.display());
+    }
+
+    if let Some(raw_output) = raw_output {
+        raw::write_raw(&result, &raw_output).map_err(|e| {
+            format!(
+                "Failed to write raw file {}: {}",
+                raw_output.path.display(),
+                e
+            )
+        })?;
+        info!("Raw file written to {}", raw_output.path.display());
+    }
+    if let Some(result_output) = result_output {
+        output::write_result(&result, &result_output).map_err(|e| {
+            format!(
+                "Failed to write output file {}: {}",
+                result_output.path.display(),
+                e
+            )
+        })?;
+        info!("Output written to {}", result_output.path.display());
+    }
     Ok(())
 }
 
@@ -88,24 +201,39 @@ fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tiny_spice::{op_report, NetlistBuilder, Simulator};
 
     fn dc_test(file: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
+        let _ =
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
+                .try_init();
         let opts = Opts {
             mode: Some("dc".to_string()),
             disp: None,
             final_time: None,
+            raw: None,
+            raw_format: "ascii".to_string(),
+            output: None,
+            format: None,
+            netlist: None,
             file,
         };
         run(opts)
     }
 
     fn trans_test(file: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
+        let _ =
+            env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug"))
+                .try_init();
         let opts = Opts {
             mode: Some("trans".to_string()),
             disp: None,
             final_time: Some(1.),
+            raw: None,
+            raw_format: "ascii".to_string(),
+            output: None,
+            format: None,
+            netlist: None,
             file,
         };
         run(opts)
@@ -135,6 +263,1032 @@ mod tests {
         dc_test(file)
     }
 
+    #[test]
+    fn test_options() -> Result<(), Box<dyn std::error::Error>> {
+        let file = PathBuf::from("examples/options.sp");
+        dc_test(file)
+    }
+
+    #[test]
+    fn test_dc_sweep() -> Result<(), Box<dyn std::error::Error>> {
+        let file = PathBuf::from("examples/dc_sweep.sp");
+        dc_test(file)
+    }
+
+    #[test]
+    fn test_raw_output() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir();
+        let ascii = dir.join("tiny_spice_trans.raw");
+        let binary = dir.join("tiny_spice_trans_binary.raw");
+        for (path, format) in [(&ascii, "ascii"), (&binary, "binary")] {
+            let opts = Opts {
+                mode: Some("trans".to_string()),
+                disp: None,
+                final_time: Some(1.),
+                raw: Some(path.clone()),
+                raw_format: format.to_string(),
+                output: None,
+                format: None,
+                netlist: None,
+                file: PathBuf::from("examples/trans_test1.sp"),
+            };
+            run(opts)?;
+        }
+
+        let content = std::fs::read_to_string(&ascii)?;
+        assert!(content.contains("Plotname: Transient Analysis"));
+        assert!(content.contains("\t0\ttime\ttime"));
+        assert!(content.contains("\t1\tv(1)\tvoltage"));
+
+        let bytes = std::fs::read(&binary)?;
+        let header_end = bytes.windows(8).position(|w| w == b"Binary:\n").unwrap() + 8;
+        let header = String::from_utf8_lossy(&bytes[..header_end]);
+        let count = |key: &str| -> usize {
+            header
+                .lines()
+                .find_map(|l| l.strip_prefix(key))
+                .unwrap()
+                .trim()
+                .parse()
+                .unwrap()
+        };
+        let size = count("No. Variables:") * count("No. Points:") * 8;
+        assert_eq!(bytes.len() - header_end, size);
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_output() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir();
+        let csv = dir.join("tiny_spice_dc_sweep.csv");
+        let json = dir.join("tiny_spice_dc_sweep.json");
+        for path in [&csv, &json] {
+            let opts = Opts {
+                mode: Some("dc".to_string()),
+                disp: None,
+                final_time: None,
+                raw: None,
+                raw_format: "ascii".to_string(),
+                output: Some(path.clone()),
+                format: None,
+                netlist: None,
+                file: PathBuf::from("examples/dc_sweep.sp"),
+            };
+            run(opts)?;
+        }
+
+        let content = std::fs::read_to_string(&csv)?;
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some("v-sweep,v(1),v(2),v(3),i(vdd),i(vin)"));
+        assert_eq!(lines.count(), 31);
+
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json)?)?;
+        assert_eq!(value["analysis"], "dc");
+        assert_eq!(value["options"]["threads"], 2);
+        assert_eq!(value["convergence"]["solves"], 31);
+        assert_eq!(value["sweep"]["values"].as_array().unwrap().len(), 31);
+        assert_eq!(value["signals"][1]["name"], "v(2)");
+        Ok(())
+    }
+
+    #[test]
+    fn test_plot() -> Result<(), Box<dyn std::error::Error>> {
+        let spec = plot::PlotSpec::parse(".PLOT DC V(1) V(1,2) | I(VDD) XLOG FILE=a.png")?;
+        let labels = spec
+            .panels
+            .iter()
+            .map(|exprs| exprs.iter().map(|e| e.label.as_str()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![vec!["v(1)", "v(1,2)"], vec!["i(vdd)"]]);
+        assert_eq!(spec.analysis, Some(result::AnalysisKind::DcSweep));
+        assert!(spec.x_log && !spec.y_log);
+        assert!(plot::PlotSpec::parse(".PLOT TRAN V(1) X(2)").is_err());
+
+        let dir = std::env::temp_dir();
+        let mut deck = std::fs::read_to_string("examples/plot.sp")?;
+        deck.push_str(".PLOT TRAN V(1,2) VDB(3) FILE=rc_drop.svg\n");
+        let files = [
+            "rc_step.svg",
+            "rc_step.png",
+            "rc_spectrum.svg",
+            "rc_drop.svg",
+        ];
+        for file in files {
+            let path = dir.join(format!("tiny_spice_{}", file));
+            deck = deck.replace(
+                &format!("FILE={}", file),
+                &format!("FILE={}", path.display()),
+            );
+        }
+        let path = dir.join("tiny_spice_plot.sp");
+        std::fs::write(&path, deck)?;
+        trans_test(path)?;
+        for file in files {
+            let path = dir.join(format!("tiny_spice_{}", file));
+            assert!(std::fs::metadata(&path)?.len() > 0);
+            std::fs::remove_file(&path)?;
+        }
+
+        // An operating point has nothing to plot, but still writes its
+        // other outputs.
+        let svg = dir.join("tiny_spice_op_plot.svg");
+        let csv = dir.join("tiny_spice_op_plot.csv");
+        let path = dir.join("tiny_spice_op_plot.sp");
+        std::fs::write(
+            &path,
+            format!("V1 1 0 DC 1\nR1 1 0 1\n.PLOT V(1) FILE={}\n", svg.display()),
+        )?;
+        run(Opts {
+            mode: Some("dc".to_string()),
+            disp: None,
+            final_time: None,
+            raw: None,
+            raw_format: "ascii".to_string(),
+            output: Some(csv.clone()),
+            format: None,
+            netlist: None,
+            file: path,
+        })?;
+        assert!(!svg.exists());
+        assert!(std::fs::read_to_string(&csv)?.starts_with("v(1),i(v1)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_print() -> Result<(), Box<dyn std::error::Error>> {
+        let table = std::env::temp_dir().join("tiny_spice_print_table.txt");
+        let deck = std::fs::read_to_string("examples/print.sp")?
+            .replace("FILE=print_table.txt", &format!("FILE={}", table.display()));
+        let path = std::env::temp_dir().join("tiny_spice_print.sp");
+        std::fs::write(&path, deck)?;
+        dc_test(path)?;
+        let content = std::fs::read_to_string(&table)?;
+        std::fs::remove_file(&table)?;
+
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 7);
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<_>>(),
+            ["v-sweep", "v(3)"]
+        );
+        let last = lines[6]
+            .split_whitespace()
+            .map(|v| v.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(last[0], 5.);
+        assert!((last[1] - 2.5).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_measure() -> Result<(), Box<dyn std::error::Error>> {
+        let json = std::env::temp_dir().join("tiny_spice_measure.json");
+        let opts = |output: &PathBuf| Opts {
+            mode: Some("trans".to_string()),
+            disp: None,
+            final_time: Some(1.),
+            raw: None,
+            raw_format: "ascii".to_string(),
+            output: Some(output.clone()),
+            format: None,
+            netlist: None,
+            file: PathBuf::from("examples/measure.sp"),
+        };
+        run(opts(&json))?;
+
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json)?)?;
+        let measurements = value["measurements"].as_array().unwrap();
+        let get =
+            |name: &str| measurements.iter().find(|m| m["name"] == name).unwrap()["value"].as_f64();
+        let tau: f64 = 0.1;
+        let near = |value: Option<f64>, expected: f64| {
+            (value.unwrap() - expected).abs() < 0.05 * expected.abs()
+        };
+        assert!(near(get("trise"), tau * 9f64.ln()));
+        assert!(near(get("thalf"), tau * 2f64.ln()));
+        assert!(near(get("vtau"), 1. - (-1f64).exp()));
+        // Time steps are coarse once the response settles.
+        let slope = get("slope").unwrap();
+        assert!((slope - (-5f64).exp() / tau).abs() < 0.25 * slope);
+        assert!(near(get("vmax"), 1.));
+        assert!(near(get("vavg"), 1.));
+        assert!(get("ipp").unwrap() > 0.5);
+        assert_eq!(get("never"), None);
+
+        let csv = std::env::temp_dir().join("tiny_spice_measure.csv");
+        run(opts(&csv))?;
+        let content = std::fs::read_to_string(&csv)?;
+        let comments = content
+            .lines()
+            .skip_while(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>();
+        assert_eq!(comments.len(), measurements.len());
+        assert!(comments.contains(&"# never,failed"));
+        let trise = comments
+            .iter()
+            .find_map(|line| line.strip_prefix("# trise,"))
+            .unwrap();
+        assert!(near(Some(trise.parse()?), tau * 9f64.ln()));
+
+        // Directive values are plain numbers, as in element cards.
+        let deck = "V1 1 0 DC 1\nR1 1 2 1\nC1 2 0 0.1\n.MEAS TRAN t WHEN V(2)=0.5 TD=1e-9\n";
+        Simulator::parse(deck)?;
+        let error = Simulator::parse(&deck.replace("1e-9", "1n")).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid value for TD: 1n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_op_report() -> Result<(), Box<dyn std::error::Error>> {
+        let mut parsed_info = parser::Parser::new(PathBuf::from("examples/options.sp")).parse()?;
+        let netlist = netlist::Netlist::from_parsed(&mut parsed_info);
+        let (x, unknowns, _) = analyze::solve_op(&netlist, &parsed_info.options)?;
+        let report = op_report::OpReport::new(&netlist, &x, &unknowns);
+
+        let (name, info) = &report.mosfets[0];
+        assert_eq!(name, "M1");
+        assert_eq!(info.region, "saturation");
+        assert!((info.vgs - 1.2).abs() < 1e-9);
+        assert!(info.gm > 0. && info.gds > 0.);
+
+        let get = |name: &str| report.elements.iter().find(|e| e.name == name).unwrap();
+        // The supply current flows out of its positive terminal.
+        assert!((get("VDD").current + info.id).abs() < 1e-12);
+        assert!((get("R1").current - info.id).abs() < 1e-12);
+        let balance = report.elements.iter().map(|e| e.power).sum::<f64>();
+        assert!(balance.abs() < 1e-9 * report.get_total_power());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sources_into_one_node() -> Result<(), Box<dyn std::error::Error>> {
+        // The right-hand side entries of both sources add up in node 1.
+        let deck = "I1 0 1 DC 1e-3\nI2 0 1 DC 2e-3\nR1 1 0 1000\n";
+        let op = Simulator::parse(deck)?.op()?;
+        assert!((op.get_signal("v(1)").unwrap()[0] - 3.).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulator() -> Result<(), Box<dyn std::error::Error>> {
+        let simulator = Simulator::from_file("examples/dc_sweep.sp")?;
+        assert_eq!(simulator.get_options().threads, 2);
+
+        let op = simulator.op()?;
+        assert_eq!(op.kind, result::AnalysisKind::Op);
+        assert!((op.get_signal("v(2)").unwrap()[0] - 3.).abs() < 1e-6);
+
+        let dc = simulator.dc("Vin", 0., 3., 0.5)?;
+        assert_eq!(dc.sweep_values, vec![0., 0.5, 1., 1.5, 2., 2.5, 3.]);
+        let vout = dc.get_signal("v(2)").unwrap();
+        assert!(vout.windows(2).all(|w| w[1] <= w[0] + 1e-9));
+        assert!(simulator.dc("Vx", 0., 1., 0.5).is_err());
+
+        let rc = Simulator::parse("V1 1 0 DC 1\nR1 1 2 1\nC1 2 0 0.1\n.OPTIONS SOLVER=SPARSE\n")?;
+        let tran = rc.tran(1.)?;
+        assert_eq!(tran.sweep_values.last(), Some(&1.));
+        // The internal node and source of the capacitor model are left out.
+        let names = tran
+            .signals
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["v(1)", "v(2)", "i(v1)"]);
+        assert!((tran.get_signal("v(2)").unwrap().last().unwrap() - 1.).abs() < 1e-3);
+
+        assert!(Simulator::parse("R1 1\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_netlist_builder() -> Result<(), Box<dyn std::error::Error>> {
+        use tiny_spice::elements::time_varing_non_linear::mosfet::MosfetType;
+        use tiny_spice::elements::MosfetModel;
+
+        // Same circuit as examples/options.sp, with named nodes.
+        let builder = NetlistBuilder::new()
+            .vsource("VDD", "vdd", "gnd", 3.)
+            .vsource("Vin", "in", "0", 1.2)
+            .mosfet(
+                "M1",
+                &["out", "in", "gnd"],
+                MosfetType::Nmos,
+                10e-6,
+                0.35e-6,
+                "nch",
+            )
+            .resistor("R1", "vdd", "out", 3000.)
+            .model("nch", MosfetModel::new(0.83, 1.5e-1, 0.05, 0.3e-4, 4.0e-14));
+        let op = Simulator::new(builder.build()?).op()?;
+        let expected = Simulator::from_file("examples/options.sp")?.op()?;
+
+        // Node voltages are named after the nodes.
+        let names = op
+            .signals
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["v(vdd)", "v(in)", "v(out)", "i(vdd)", "i(vin)"]);
+        let vout = op.get_signal("v(out)").unwrap()[0];
+        assert!((vout - expected.get_signal("v(2)").unwrap()[0]).abs() < 1e-6);
+        assert_eq!(builder.get_node_id("out"), Some(3));
+        assert_eq!(builder.get_node_id("GND"), Some(0));
+        assert_eq!(builder.get_node_id("nowhere"), None);
+
+        let error = |builder: NetlistBuilder| builder.build().unwrap_err();
+        assert_eq!(
+            error(NetlistBuilder::new().resistor("R1", "a", "0", 0.)),
+            "Invalid resistance of R1: 0"
+        );
+        assert_eq!(
+            error(
+                NetlistBuilder::new()
+                    .resistor("R1", "a", "0", 1.)
+                    .capacitor("r1", "a", "0", 1.)
+            ),
+            "Duplicate element: r1"
+        );
+        assert_eq!(
+            error(NetlistBuilder::new().mosfet(
+                "M1",
+                &["d", "g", "0"],
+                MosfetType::Pmos,
+                1.,
+                1.,
+                "x"
+            )),
+            "Unknown MOSFET model: x"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_body_effect() -> Result<(), Box<dyn std::error::Error>> {
+        use tiny_spice::elements::time_varing_non_linear::mosfet::MosfetType;
+        use tiny_spice::elements::MosfetModel;
+
+        let deck = std::fs::read_to_string("examples/body_effect.sp")?;
+        let vout = |deck: &str| -> Result<f64, Box<dyn std::error::Error>> {
+            Ok(Simulator::parse(deck)?.op()?.get_signal("v(3)").unwrap()[0])
+        };
+        // The solution must be well inside the 1e-6 tolerance of the check
+        // below, so Newton stops on a tighter RELTOL than the default.
+        let deck = format!("{}.OPTIONS RELTOL=1e-6\n", deck);
+        let vs = vout(&deck)?;
+        let vs_no_body = vout(&deck.replace("GAMMA 0.4", "GAMMA 0"))?;
+        // The source-bulk reverse bias raises the threshold voltage.
+        assert!(vs < vs_no_body - 0.05);
+
+        let vth = 0.83 + 0.4 * ((0.6 + vs).sqrt() - 0.6_f64.sqrt());
+        let ids = 0.5 * 0.15 * 0.3e-4 * 10. * (2. - vs - vth).powi(2) * (1. + 0.05 * (3. - vs));
+        assert!((ids - vs / 1e5).abs() < 1e-6 * ids);
+
+        // A three-terminal device ties its bulk to the source.
+        let vs_tied = vout(&deck.replace("M1 1 2 3 0 n", "M1 1 2 3 n"))?;
+        assert!((vs_tied - vs_no_body).abs() < 1e-6);
+
+        let builder = NetlistBuilder::new()
+            .vsource("VDD", "vdd", "0", 3.)
+            .vsource("VIN", "in", "0", 2.)
+            .mosfet(
+                "M1",
+                &["vdd", "in", "out", "0"],
+                MosfetType::Nmos,
+                10.,
+                1.,
+                "n",
+            )
+            .resistor("RS", "out", "0", 1e5)
+            .model(
+                "n",
+                MosfetModel::new(0.83, 1.5e-1, 0.05, 0.3e-4, 0.).with_body_effect(0.4, 0.6),
+            );
+        let op = Simulator::new(builder.build()?).op()?;
+        assert!((op.get_signal("v(out)").unwrap()[0] - vs).abs() < 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mosfet_levels() -> Result<(), Box<dyn std::error::Error>> {
+        use tiny_spice::elements::MosfetModel;
+
+        // Drain currents at gate overdrives of 1 V and 2 V.
+        let currents = |model: &str| -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+            let deck = format!(
+                "VDD 1 0 DC 3\nVG 2 0 DC 3\nM1 1 2 0 n 10e-6 1e-6 1\n.MODEL 1 {}\n",
+                model
+            );
+            let dc = Simulator::parse(&deck)?.dc("VG", 1.5, 2.5, 1.)?;
+            Ok(dc
+                .get_signal("i(vdd)")
+                .unwrap()
+                .iter()
+                .map(|i| -i)
+                .collect())
+        };
+        let level1 = currents("VT 0.5 MU 0.04 COX 4.5e-3")?;
+        assert!((level1[1] / level1[0] - 4.).abs() < 1e-9);
+        // KP stands for MU * COX on its own.
+        let kp = currents("NMOS (LEVEL=1 VTO=0.5 KP=1.8e-4)")?;
+        assert!((kp[0] - level1[0]).abs() < 1e-9 * level1[0]);
+        let level3 = currents("LEVEL 3 VT 0.5 MU 0.04 COX 4.5e-3")?;
+        assert!((level3[0] - level1[0]).abs() < 1e-9 * level1[0]);
+
+        // Velocity saturation makes the current less than quadratic.
+        let vsat = currents("LEVEL 3 VT 0.5 MU 0.04 COX 4.5e-3 VMAX 3e4")?;
+        assert!(vsat[0] < level1[0] && vsat[1] / vsat[0] < 3.);
+        let theta = currents("LEVEL 3 VT 0.5 MU 0.04 COX 4.5e-3 THETA 0.2")?;
+        assert!(theta[1] / theta[0] < 4.);
+        let kp3 = currents("LEVEL 3 VT 0.5 KP 1.8e-4 THETA 0.2")?;
+        assert!((kp3[0] - theta[0]).abs() < 1e-9 * theta[0]);
+        let dibl = currents("LEVEL 3 VT 0.5 MU 0.04 COX 4.5e-3 ETA 0.05")?;
+        assert!(dibl[0] > level1[0]);
+
+        // Foundry parameter names, in cm^2/Vs and oxide thickness.
+        let bsim = currents("NMOS (LEVEL=49 VTH0=0.5 U0=400 TOX=7.67e-9 NFACTOR=1)")?;
+        assert!((bsim[0] - level1[0]).abs() < 1e-3 * level1[0]);
+
+        let dc = Simulator::from_file("examples/bsim.sp")?.dc("VG", 0., 3.3, 0.3)?;
+        let vout = dc.get_signal("v(3)").unwrap();
+        assert!(vout.windows(2).all(|w| w[1] <= w[0]));
+        assert!((vout[0] - 3.3).abs() < 1e-6 && vout[11] < 0.5);
+
+        let card = ".MODEL 1 LEVEL 3 VT 0.5 MU 0.04 COX 4.5e-3 VMAX 1e5 ETA 0.05";
+        let (_, model) = MosfetModel::parse(card)?;
+        let (_, reparsed) = MosfetModel::parse(&format!(".MODEL 1 {}", model))?;
+        assert_eq!(model.to_string(), reparsed.to_string());
+        assert!(model.to_string().contains("LEVEL 3"));
+
+        let error = Simulator::parse("M1 1 2 0 n 1 1 1\n.MODEL 1 LEVEL 7\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported MOSFET model level: 7, <string>:2"
+        );
+
+        // Foundry parameters no level uses are ignored, typos are not.
+        MosfetModel::parse(
+            ".MODEL 1 NMOS (LEVEL=49 VTH0=0.5 U0=400 TOX=7.6e-9 PCLM=1.3 LVTH0=0.01 WDVT0=0.1)",
+        )?;
+        let error = MosfetModel::parse(".MODEL 1 NMOS (LEVEL=1 VTO=0.5 LAMBDA=0.02)").unwrap_err();
+        assert_eq!(error, "MOSFET model 1 needs KP, or MU and COX (or TOX)");
+        for card in [".MODEL 1 VT 0.5 LAMDA 0.05", ".MODEL 1 VT 0.5 GAMA 0.4"] {
+            let error = MosfetModel::parse(card).unwrap_err();
+            assert!(
+                error.starts_with("Unknown MOSFET model parameter: "),
+                "{}",
+                error
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_transmission_gate() -> Result<(), Box<dyn std::error::Error>> {
+        let deck = std::fs::read_to_string("examples/transmission_gate.sp")?;
+        let sweep = |deck: &str| -> Result<result::AnalysisResult, Box<dyn std::error::Error>> {
+            Simulator::parse(deck)?.dc("VIN", 0., 3., 0.25)
+        };
+        let dc = sweep(&deck)?;
+        let vout = dc.get_signal("v(2)").unwrap();
+        let iin = dc.get_signal("i(vin)").unwrap();
+
+        // Both devices conduct forward on one side of mid-supply and in
+        // reverse on the other.
+        assert!(vout.windows(2).all(|w| w[1] > w[0]));
+        for ((&vin, &vout), &iin) in dc.sweep_values.iter().zip(&vout).zip(&iin) {
+            assert!((vout - 1.5).abs() <= (vin - 1.5).abs());
+            assert!(iin * (vin - 1.5) <= 0.);
+            assert!((iin - (1.5 - vout) / 1e4).abs() < 1e-9);
+        }
+
+        // Exchanging the drain and source of both devices changes nothing.
+        let swapped = deck
+            .replace("M1 1 3 2 0 n", "M1 2 3 1 0 n")
+            .replace("M2 2 0 1 3 p", "M2 1 0 2 3 p");
+        let vout_swapped = sweep(&swapped)?.get_signal("v(2)").unwrap();
+        for (v, v_swapped) in vout.iter().zip(&vout_swapped) {
+            assert!((v - v_swapped).abs() < 1e-6);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_subthreshold() -> Result<(), Box<dyn std::error::Error>> {
+        let simulator = Simulator::from_file("examples/subthreshold.sp")?;
+        let drain_current = |simulator: &Simulator, start: f64, stop: f64, step: f64| {
+            let dc = simulator.dc("VG", start, stop, step).unwrap();
+            let current = dc.get_signal("i(vdd)").unwrap();
+            current.iter().map(|i| -i).collect::<Vec<_>>()
+        };
+
+        // Ten times I0 * W / L at the threshold, falling by N * vt per e-fold.
+        let n_vt: f64 = 1.5 * 0.025865;
+        let weak = drain_current(&simulator, 0.3, 0.4, 0.1);
+        let expected = 1e-7 * 10. * ((0.3 - 0.83) / n_vt).exp() * (1. + 0.05);
+        assert!((weak[0] - expected).abs() < 1e-2 * expected);
+        assert!((weak[1] / weak[0] - (0.1 / n_vt).exp()).abs() < 2e-2 * weak[1] / weak[0]);
+
+        // The transconductance has no kink at the threshold.
+        let current = drain_current(&simulator, 0.7, 1., 1e-3);
+        let gm = current.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        assert!(gm
+            .windows(2)
+            .all(|g| g[1] > 0. && (g[1] / g[0] - 1.).abs() < 0.05));
+
+        let (x, unknowns, _) = analyze::solve_op(simulator.get_netlist(), simulator.get_options())?;
+        let report = op_report::OpReport::new(simulator.get_netlist(), &x, &unknowns);
+        assert_eq!(report.mosfets[0].1.region, "subthreshold");
+        assert!(report.mosfets[0].1.gm > 0. && report.mosfets[0].1.gds > 0.);
+
+        // I0 only sets the weak inversion current.
+        let deck = std::fs::read_to_string("examples/subthreshold.sp")?;
+        let strong = drain_current(&simulator, 1.5, 1.5, 0.1)[0];
+        for model in [" N 1.5", " N 1.5 I0 1e-6", ""] {
+            let other = Simulator::parse(&deck.replace(" N 1.5 I0 1e-7", model))?;
+            let current = drain_current(&other, 1.5, 1.5, 0.1)[0];
+            assert!((current - strong).abs() < 1e-3 * strong);
+        }
+
+        // Without a slope factor the device cuts off.
+        let cut_off = Simulator::parse(&deck.replace(" N 1.5 I0 1e-7", ""))?;
+        assert_eq!(drain_current(&cut_off, 0.5, 0.5, 0.1), vec![0.]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_temperature() -> Result<(), Box<dyn std::error::Error>> {
+        let simulator = Simulator::from_file("examples/temp_sweep.sp")?;
+        let dc = simulator.dc("TEMP", -40., 125., 5.)?;
+        assert_eq!(dc.sweep.as_ref().unwrap().name, "temp-sweep");
+        let v2 = dc.get_signal("v(2)").unwrap();
+        let v3 = dc.get_signal("v(3)").unwrap();
+
+        for (i, temp) in dc.sweep_values.iter().enumerate() {
+            let r1 = 1e3 * (1. + 3e-3 * (temp - 27.));
+            assert!((v3[i] - 3. * 1e3 / (r1 + 1e3)).abs() < 1e-9);
+
+            // Square law with VT falling by TCV and MU by T^-1.5.
+            let vt = 0.7 - 2e-3 * (temp - 27.);
+            let beta = 0.04 * ((temp + 273.15) / 300.15).powf(-1.5) * 4.5e-3 * 10.;
+            let mut v = vt;
+            for _ in 0..50 {
+                let id = 10e-6 - v / 1e8;
+                v = vt + (2. * id / (beta * (1. + 0.02 * v))).sqrt();
+            }
+            assert!((v2[i] - v).abs() < 1e-5);
+        }
+        assert!(v2.windows(2).all(|w| w[1] < w[0]));
+
+        let deck = std::fs::read_to_string("examples/temp_sweep.sp")?;
+        let op_at = |deck: &str| -> Result<(f64, f64), Box<dyn std::error::Error>> {
+            let op = Simulator::parse(deck)?.op()?;
+            Ok((
+                op.get_signal("v(2)").unwrap()[0],
+                op.get_signal("v(3)").unwrap()[0],
+            ))
+        };
+        let hot = op_at(&format!("{}.TEMP 127\n", deck))?;
+        assert!((hot.1 - 3. / 2.3).abs() < 1e-9);
+        assert_eq!(op_at(&format!("{}.OPTIONS TEMP=127\n", deck))?, hot);
+
+        // Instance temperatures override the circuit temperature.
+        let resistor_at_27 = deck.replace("TC1=3e-3", "TC1=3e-3 TEMP=27");
+        assert!((op_at(&format!("{}.TEMP 127\n", resistor_at_27))?.1 - 1.5).abs() < 1e-9);
+        let mosfet_at_127 = deck.replace("1e-6 1\n", "1e-6 1 TEMP=127\n");
+        assert_eq!(op_at(&mosfet_at_127)?.0, hot.0);
+        for parameter in ["TMP=85", "TEMP=hot"] {
+            let deck = deck.replace("1e-6 1\n", &format!("1e-6 1 {}\n", parameter));
+            let error = Simulator::parse(&deck).unwrap_err();
+            assert!(error.to_string().contains("Invalid element"), "{}", error);
+        }
+
+        let error = Simulator::parse(&format!("{}.TEMP 27 85\n", deck)).unwrap_err();
+        assert!(error.to_string().contains("use .DC TEMP"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutual_inductance() -> Result<(), Box<dyn std::error::Error>> {
+        let deck = std::fs::read_to_string("examples/transformer.sp")?;
+        let coupled = Simulator::parse(&deck)?.tran(1.)?;
+
+        // The T-equivalent of the coupled inductors, with M = 0.25. Its
+        // time steps differ, so only the common time points are compared.
+        let equivalent = Simulator::parse(&deck.replace(
+            "L1 2 0 0.5\nL2 3 0 2\nK1 L1 L2 0.25\n",
+            "LA 2 4 0.25\nLB 3 4 1.75\nLM 4 0 0.25\n",
+        ))?
+        .tran(1.)?;
+        let mut compared = 0;
+        for signal in ["v(2)", "v(3)"] {
+            let v_coupled = coupled.get_signal(signal).unwrap();
+            let v_equivalent = equivalent.get_signal(signal).unwrap();
+            for (i, t) in coupled.sweep_values.iter().enumerate() {
+                if let Some(j) = equivalent.sweep_values.iter().position(|t_eq| t_eq == t) {
+                    assert!((v_coupled[i] - v_equivalent[j]).abs() < 1e-3);
+                    compared += 1;
+                }
+            }
+        }
+        assert!(compared > 20);
+
+        // The dotted secondary terminal follows the primary.
+        let v3 = coupled.get_signal("v(3)").unwrap();
+        assert!(v3.iter().cloned().fold(f64::MIN, f64::max) > 1.);
+
+        // Inductors are shorts at DC, so the coupling has no effect there.
+        let op = Simulator::parse(&deck)?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0]).abs() < 1e-9);
+
+        let netlist = NetlistBuilder::new()
+            .inductor("L1", "1", "0", 1e-3)
+            .coupling("K1", "L1", "L3", 0.5)
+            .build();
+        assert_eq!(netlist.unwrap_err(), "Unknown inductor in K1: L3");
+        let netlist = NetlistBuilder::new()
+            .inductor("L1", "1", "0", 1e-3)
+            .inductor("L2", "2", "0", 1e-3)
+            .coupling("K1", "L1", "L2", 1.5)
+            .build();
+        assert_eq!(
+            netlist.unwrap_err(),
+            "Invalid coupling coefficient of K1: 1.5"
+        );
+        let error = Simulator::parse(&deck.replace("K1 L1 L2", "K1 L1 L1")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Inductor coupled to itself in K1: L1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch() -> Result<(), Box<dyn std::error::Error>> {
+        // The oscillator swings between the thresholds VT - VH and VT + VH.
+        // It charges through R1 for ln(7/3) = 0.847 s and discharges through
+        // RON for 8.6 ms.
+        let deck = std::fs::read_to_string("examples/switch.sp")?;
+        let result = Simulator::parse(&deck)?.tran(5.)?;
+        let v2 = result.get_signal("v(2)").unwrap();
+        let peaks = (1..v2.len() - 1)
+            .filter(|&i| v2[i] > v2[i - 1] && v2[i] >= v2[i + 1])
+            .collect::<Vec<_>>();
+        assert_eq!(peaks.len(), 5);
+        for &i in peaks.iter() {
+            assert!((v2[i] - 7.).abs() < 1e-2);
+        }
+        let valleys = (1..v2.len() - 1).filter(|&i| v2[i] < v2[i - 1] && v2[i] <= v2[i + 1]);
+        for i in valleys {
+            assert!((v2[i] - 3.).abs() < 5e-2);
+        }
+        let period = result.sweep_values[peaks[4]] - result.sweep_values[peaks[3]];
+        assert!((period - 0.856).abs() < 1e-2);
+
+        // The same oscillator scaled to a 1 us time constant. The switch
+        // starts latched off instead of chattering in the first step.
+        let deck = "V1 1 0 DC 10\nR1 1 2 1000\nC1 2 0 1e-9\nS1 2 0 2 0 1\n\
+                    .MODEL 1 SW RON 10 ROFF 1e9 VT 5 VH 2\n";
+        let result = Simulator::parse(deck)?.tran(5e-6)?;
+        let v2 = result.get_signal("v(2)").unwrap();
+        let peaks = (1..v2.len() - 1)
+            .filter(|&i| v2[i] > v2[i - 1] && v2[i] >= v2[i + 1])
+            .collect::<Vec<_>>();
+        assert!(peaks.len() >= 4);
+        for &i in peaks.iter() {
+            assert!((v2[i] - 7.).abs() < 5e-2);
+        }
+        let period = result.sweep_values[peaks[3]] - result.sweep_values[peaks[2]];
+        assert!((period - 0.847e-6).abs() < 5e-8);
+
+        // Within the hysteresis band the switch keeps its initial state.
+        let deck = "V1 1 0 DC 5\nR1 1 2 1000\nS1 2 0 3 0 1\nVC 3 0 DC 0.55\n\
+                    .MODEL 1 SW RON 1000 VT 0.5 VH 0.1\n";
+        let op = Simulator::parse(deck)?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0] - 5.).abs() < 1e-6);
+        let op = Simulator::parse(&deck.replace("3 0 1\n", "3 0 1 ON\n"))?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0] - 2.5).abs() < 1e-9);
+        let op = Simulator::parse(&deck.replace("DC 0.55", "DC 0.65"))?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0] - 2.5).abs() < 1e-9);
+
+        // W1 closes once the current through VS exceeds IT.
+        let deck = "V1 1 0 DC 5\nR1 1 2 1000\nW1 2 0 VS 2\nI1 0 3 DC 2e-3\nVS 3 0 DC 0\n\
+                    .MODEL 2 CSW RON 1000 IT 1e-3\n";
+        let op = Simulator::parse(deck)?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0] - 2.5).abs() < 1e-9);
+        let op = Simulator::parse(&deck.replace("DC 2e-3", "DC 0.5e-3"))?.op()?;
+        assert!((op.get_signal("v(2)").unwrap()[0] - 5.).abs() < 1e-6);
+
+        let error = Simulator::parse(&deck.replace("W1 2 0 VS", "W1 2 0 VX")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Unknown controlling source of W1: VX"));
+        let error =
+            Simulator::parse(&deck.replace("CSW RON 1000 IT", "SW RON 1000 VT")).unwrap_err();
+        assert!(error.to_string().contains("Switch model 2 is not CSW"));
+        let error = Simulator::parse(&deck.replace("IT", "VT")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid switch model parameter: VT"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_transmission_line() -> Result<(), Box<dyn std::error::Error>> {
+        // The step launched into the open line doubles at the far end after
+        // one delay and returns to the near end after two.
+        let deck = std::fs::read_to_string("examples/tline.sp")?;
+        let result = Simulator::parse(&deck)?.tran(5e-9)?;
+        let (v2, v3) = (
+            result.get_signal("v(2)").unwrap(),
+            result.get_signal("v(3)").unwrap(),
+        );
+        // The solver resolves each edge with a point just after it.
+        let is_edge = |t: f64| (t - 1e-9).abs() < 1e-12 || (t - 2e-9).abs() < 1e-12;
+        for (i, t) in result.sweep_values.iter().enumerate() {
+            let (near, far) = match *t {
+                t if is_edge(t) => continue,
+                t if t < 1e-9 => (0.5, 0.),
+                t if t < 2e-9 => (0.5, 1.),
+                _ => (1., 1.),
+            };
+            assert!((v2[i] - near).abs() < 1e-4);
+            assert!((v3[i] - far).abs() < 1e-4);
+        }
+        assert!(result.sweep_values.len() >= 5);
+
+        // A matched load absorbs the wave, and the delay may be given as a
+        // quarter wavelength at 250 MHz.
+        let matched = Simulator::parse(
+            &deck
+                .replace("R2 3 0 1e6", "R2 3 0 50")
+                .replace("TD=1e-9", "F=250e6"),
+        )?
+        .tran(5e-9)?;
+        let (v2, v3) = (
+            matched.get_signal("v(2)").unwrap(),
+            matched.get_signal("v(3)").unwrap(),
+        );
+        for (i, t) in matched.sweep_values.iter().enumerate() {
+            assert!((v2[i] - 0.5).abs() < 1e-9);
+            if is_edge(*t) {
+                continue;
+            }
+            let far = if *t < 1e-9 { 0. } else { 0.5 };
+            assert!((v3[i] - far).abs() < 1e-9);
+        }
+
+        // At DC the line connects its ports.
+        let op = Simulator::parse(&deck)?.op()?;
+        assert!((op.get_signal("v(3)").unwrap()[0] - 1e6 / (1e6 + 50.)).abs() < 1e-9);
+
+        let netlist = NetlistBuilder::new()
+            .vsource("V1", "in", "0", 1.)
+            .resistor("R1", "in", "near", 50.)
+            .transmission_line("T1", ["near", "0", "far", "0"], 50., 1e-9)
+            .resistor("R2", "far", "0", 1e6)
+            .build()?;
+        let built = Simulator::new(netlist).tran(5e-9)?;
+        assert_eq!(
+            built.get_signal("v(far)").unwrap(),
+            result.get_signal("v(3)").unwrap()
+        );
+
+        for line in [
+            "T1 2 0 3 0 TD=1e-9",
+            "T1 2 0 3 0 Z0=50",
+            "T1 2 0 3 0 Z0=50 TD=1e-9 F=1e9",
+            "T1 2 0 3 0 Z0=-50 TD=1e-9",
+        ] {
+            let error =
+                Simulator::parse(&deck.replace("T1 2 0 3 0 Z0=50 TD=1e-9", line)).unwrap_err();
+            assert!(error.to_string().contains("Invalid element"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_behavioral_source() -> Result<(), Box<dyn std::error::Error>> {
+        let deck = std::fs::read_to_string("examples/behavioral.sp")?;
+        let op = Simulator::parse(&deck)?.op()?;
+        let v = |signal: &str| op.get_signal(signal).unwrap()[0];
+        assert!((v("v(3)") - 6.).abs() < 1e-9);
+        assert!((v("v(4)") - (-10_f64).tanh()).abs() < 1e-6);
+        // 1e-3 * v^2 + v / 1e6 = 4e-3
+        let v5 = (-1e-6 + (1e-12 + 16e-6_f64).sqrt()) / 2e-3;
+        assert!((v("v(5)") - v5).abs() < 1e-6);
+        // Like a voltage source, B1 supplies the current it drives.
+        assert!((v("i(B1)") + 6e-3).abs() < 1e-9);
+
+        // The infinite slope of sqrt at the initial zero solution is bounded.
+        // (1 - v) / 1000 = 1e-3 * sqrt(v)
+        let deck = "V1 1 0 DC 1\nR1 1 2 1000\nB1 2 0 I=1e-3*sqrt(V(2))\n";
+        let op = Simulator::parse(deck)?.op()?;
+        let v2 = (3. - 5_f64.sqrt()) / 2.;
+        assert!((op.get_signal("v(2)").unwrap()[0] - v2).abs() < 1e-6);
+
+        // Currents of voltage sources and time may be read too.
+        let deck = "V1 1 0 DC 2\nR1 1 0 500\nB1 2 0 V=-1000*I(v1)\nR2 2 0 1000\n\
+                    B2 3 0 V=sin(2*pi*time)\nR3 3 0 1000\n";
+        let result = Simulator::parse(deck)?.tran(1.)?;
+        let (v2, v3) = (
+            result.get_signal("v(2)").unwrap(),
+            result.get_signal("v(3)").unwrap(),
+        );
+        for (i, t) in result.sweep_values.iter().enumerate() {
+            assert!((v2[i] - 4.).abs() < 1e-9);
+            assert!((v3[i] - (2. * std::f64::consts::PI * t).sin()).abs() < 1e-9);
+        }
+
+        for (line, expected) in [
+            (
+                "B1 2 0 V=-1000*I(VX)",
+                "Unknown controlling source of B1: VX",
+            ),
+            ("B1 2 0 V=-1000*", "Unexpected end of expression"),
+            ("B1 2 0 V=foo(V(1))", "Unknown function in expression: FOO"),
+            ("B1 2 0 V=max(V(1))", "MAX takes 2 arguments, found 1"),
+            ("B1 2 0 Q=V(1)", "Invalid behavioral source"),
+        ] {
+            let error = Simulator::parse(&deck.replace("B1 2 0 V=-1000*I(v1)", line)).unwrap_err();
+            assert!(error.to_string().contains(expected), "{}", error);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_netlist_writer() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("tiny_spice_dc_sweep.sp");
+        let opts = Opts {
+            mode: Some("dc".to_string()),
+            disp: None,
+            final_time: None,
+            raw: None,
+            raw_format: "ascii".to_string(),
+            output: None,
+            format: None,
+            netlist: Some(path.clone()),
+            file: PathBuf::from("examples/dc_sweep.sp"),
+        };
+        run(opts)?;
+        let written = std::fs::read_to_string(&path)?;
+        assert!(written.contains("M1 2 1 0 n 1e-5 3.5e-7 2\n"));
+        assert!(written
+            .contains(".MODEL 2 VT 0.83 MU 0.15 COX 3e-5 LAMBDA 0.05 CJ0 4e-14 GAMMA 0 PHI 0.6\n"));
+        assert!(written.contains("THREADS=2"));
+        assert!(written.contains(".DC Vin 0 3 0.1\n"));
+        assert!(written.contains(".PLOTNV 2\n"));
+
+        // Writing the parsed deck again gives the same text.
+        let mut parsed_info = parser::Parser::new(path.clone()).parse()?;
+        let netlist = netlist::Netlist::from_parsed(&mut parsed_info);
+        let mut rewritten = Vec::new();
+        writer::write_netlist(
+            &mut rewritten,
+            "examples/dc_sweep.sp",
+            &netlist,
+            &parsed_info,
+        )?;
+        assert_eq!(String::from_utf8(rewritten)?, written);
+        std::fs::remove_file(&path)?;
+
+        // Output directives are written too, and read back unchanged.
+        let write = |parser: parser::Parser,
+                     deck: Option<&str>|
+         -> Result<String, Box<dyn std::error::Error>> {
+            let mut parsed_info = match deck {
+                Some(deck) => parser.parse_str(deck)?,
+                None => parser.parse()?,
+            };
+            let netlist = netlist::Netlist::from_parsed(&mut parsed_info);
+            let mut deck = Vec::new();
+            writer::write_netlist(&mut deck, "directives", &netlist, &parsed_info)?;
+            Ok(String::from_utf8(deck)?)
+        };
+        for file in [
+            "examples/measure.sp",
+            "examples/plot.sp",
+            "examples/print.sp",
+        ] {
+            let written = write(parser::Parser::new(PathBuf::from(file)), None)?;
+            let rewritten = write(parser::Parser::new(PathBuf::from(file)), Some(&written))?;
+            assert_eq!(rewritten, written);
+        }
+        let written = write(
+            parser::Parser::new(PathBuf::from("examples/measure.sp")),
+            None,
+        )?;
+        for line in [
+            ".MEAS TRAN trise TRIG V(2) VAL=0.1 RISE=1 TARG V(2) VAL=0.9 RISE=1\n",
+            ".MEAS TRAN thalf WHEN V(2)=0.5\n",
+            ".MEAS TRAN vavg AVG V(2) FROM=0.5 TO=1\n",
+        ] {
+            assert!(written.contains(line), "{}", written);
+        }
+        let written = write(parser::Parser::new(PathBuf::from("examples/plot.sp")), None)?;
+        for line in [
+            ".PLOT TRAN V(1) V(2) V(3) | I(V1) FILE=rc_step.svg\n",
+            ".PLOT TRAN V(2) V(3) YLOG FILE=rc_step.png SIZE=640x480\n",
+            ".FOUR 2 V(3)\n",
+            ".FFT V(3) NP=256 WINDOW=BLACKMAN FILE=rc_spectrum.svg\n",
+        ] {
+            assert!(written.contains(line), "{}", written);
+        }
+
+        // A built netlist round-trips through the parser.
+        let builder = NetlistBuilder::new()
+            .vsource("V1", "in", "0", 1.)
+            .resistor("R1", "in", "out", 1e3)
+            .inductor("L1", "out", "mid", 1e-6)
+            .resistor("R2", "mid", "0", 3e3)
+            .isource("I1", "0", "out", 1e-4);
+        let netlist = builder.build()?;
+        let mut deck = Vec::new();
+        writer::write_netlist(&mut deck, "built", &netlist, &Default::default())?;
+        let deck = String::from_utf8(deck)?;
+        let expected = Simulator::new(netlist).op()?;
+        let op = Simulator::parse(&deck)?.op()?;
+        assert_eq!(op.signals.len(), expected.signals.len());
+        assert_eq!(op.rows, expected.rows);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mosfet_capacitances() -> Result<(), Box<dyn std::error::Error>> {
+        let result = Simulator::from_file("examples/mos_delay.sp")?.tran(1e-9)?;
+        // The companion models of the device capacitances stay internal.
+        let names = result
+            .signals
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["v(1)", "v(2)", "v(3)", "i(vdd)"]);
+        let (time, gate, drain) = (
+            &result.sweep_values,
+            result.get_signal("v(2)").unwrap(),
+            result.get_signal("v(3)").unwrap(),
+        );
+
+        // The gate charges through RG instead of jumping to VDD.
+        assert!(gate[0] < 0.01);
+        let turn_on = gate.iter().position(|v| *v > 0.83).unwrap();
+        assert!(time[turn_on] > 0.05e-9);
+
+        // The drain junction delays the output, which falls once the gate
+        // turns the device on.
+        assert!(drain[0] < 0.01);
+        let peak = drain.iter().cloned().fold(0., f64::max);
+        assert!(peak > 2.);
+        assert!(*drain.last().unwrap() < peak - 0.5);
+
+        let deck = std::fs::read_to_string("examples/mos_delay.sp")?;
+        let tran = |card: &str| -> Result<result::AnalysisResult, Box<dyn std::error::Error>> {
+            let deck = deck.replace("CJ0 1e-15", &format!("CJ0 1e-15 {}", card));
+            Simulator::parse(&deck)?.tran(1e-9)
+        };
+        let turn_on = |result: &result::AnalysisResult| {
+            let gate = result.get_signal("v(2)").unwrap();
+            result.sweep_values[gate.iter().position(|v| *v > 0.83).unwrap()]
+        };
+        // The gate-source overlap loads the gate in parallel with the
+        // Meyer capacitance.
+        let overlap = tran("CGSO 2e-11")?;
+        assert!(turn_on(&overlap) > 1.5 * turn_on(&result));
+
+        // A lower built-in potential and a steeper grading shrink the
+        // reverse-biased drain junction, so the drain rises further before
+        // the device turns on.
+        let peak_of = |result: &result::AnalysisResult| {
+            let drain = result.get_signal("v(3)").unwrap();
+            drain.iter().cloned().fold(0., f64::max)
+        };
+        let abrupt = tran("PB 0.4 MJ 0.9 FC 0.2")?;
+        assert!(peak_of(&abrupt) > peak + 0.2);
+
+        let error = Simulator::parse(&deck.replace("CJ0 1e-15", "CJ 1e-3")).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Unsupported MOSFET model parameter CJ"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_singular_report() {
+        let file = PathBuf::from("examples/cutoff.sp");
+        let report = dc_test(file).unwrap_err().to_string();
+        assert!(report.contains("Matrix is singular"));
+        assert!(report.contains("V(2) has no connections"));
+    }
+
+    #[test]
+    fn test_topology_check() {
+        let file = PathBuf::from("examples/topology.sp");
+        let report = dc_test(file).unwrap_err().to_string();
+        assert!(report.contains("Nodes 2, 3 have no DC path to ground (elements: R2, R3)"));
+        assert!(
+            report.contains("Node 4 is connected only through current sources/capacitors: I1, C1")
+        );
+        assert!(report.contains("Loop of voltage sources/inductors: V1, L1"));
+    }
+
     #[test]
     fn test_trans_example1() -> Result<(), Box<dyn std::error::Error>> {
         let file = PathBuf::from("examples/trans_test1.sp");
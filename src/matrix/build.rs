@@ -6,6 +6,9 @@ pub struct MatrixTriplets<T> {
     pub cols: Vec<usize>,
     pub vals: Vec<T>,
     pub size: usize,
+    /// Names of the elements owning the branch current unknowns appended
+    /// after the node voltages, in order.
+    pub branch_names: Vec<String>,
 }
 
 pub type VecItems<T> = Map<usize, T>;
@@ -14,12 +17,15 @@ pub trait VecPushWithNodeId<T> {
     fn push_with_node_id(&mut self, index: NodeId, val: T);
 }
 
-impl<T> VecPushWithNodeId<T> for VecItems<T> {
+/// Entries pushed to the same node are summed, as currents into a node are.
+impl<T: Copy + std::ops::AddAssign> VecPushWithNodeId<T> for VecItems<T> {
     fn push_with_node_id(&mut self, index: NodeId, val: T) {
         if index == 0 {
             return;
         }
-        self.insert(index - 1, val);
+        self.entry(index - 1)
+            .and_modify(|item| *item += val)
+            .or_insert(val);
     }
 }
 
@@ -30,6 +36,7 @@ impl<T> MatrixTriplets<T> {
             cols: Vec::new(),
             vals: Vec::new(),
             size,
+            branch_names: Vec::new(),
         }
     }
 
@@ -52,4 +59,24 @@ impl<T> MatrixTriplets<T> {
     pub fn extend_size(&mut self, x: usize) {
         self.size += x;
     }
+
+    /// Append a branch current unknown for the element `name` and return its
+    /// position.
+    pub fn append_branch(&mut self, name: &str) -> usize {
+        let new_pos = self.size;
+        self.extend_size(1);
+        self.branch_names.push(name.to_string());
+        new_pos
+    }
+
+    /// Get the node ID of the branch current of the element `name`
+    /// (case-insensitive). Branches are appended after every node, so a
+    /// branch can be looked up once its element has been stamped.
+    pub fn get_branch_node_id(&self, name: &str) -> Option<NodeId> {
+        let position = self
+            .branch_names
+            .iter()
+            .position(|branch_name| branch_name.eq_ignore_ascii_case(name))?;
+        Some(self.size - self.branch_names.len() + position + 1)
+    }
 }
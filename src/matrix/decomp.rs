@@ -5,6 +5,21 @@ use log::debug;
 use num_traits::{Num, NumOps};
 use sprs::CsMat;
 
+/// Raised when a zero pivot is met while factoring. `pivot` is the column
+/// (unknown) at which elimination broke down.
+#[derive(Debug)]
+pub struct SingularMatrixError {
+    pub pivot: usize,
+}
+
+impl fmt::Display for SingularMatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Matrix is singular")
+    }
+}
+
+impl std::error::Error for SingularMatrixError {}
+
 pub trait LUDecomp {
     type ResultType;
     fn get_reorder_map(&self) -> Vec<usize>;
@@ -126,7 +141,7 @@ where
                             l.to_dense(),
                             u.to_dense()
                         );
-                        return Err("Matrix is singular".into());
+                        return Err(SingularMatrixError { pivot: col }.into());
                     }
 
                     let l_val = (orig_val - prev_sum) / u_col_col;
@@ -134,6 +149,12 @@ where
                 }
             }
         }
+        // The loop above only checks pivots that are divided by; the last one
+        // must be checked separately.
+        if size > 0 && get_or_default(u.get(size - 1, size - 1)) == Default::default() {
+            return Err(SingularMatrixError { pivot: size - 1 }.into());
+        }
+
         debug!("\nL:{}\nU:{}\n", l.to_dense(), u.to_dense());
 
         Ok((l, u))
@@ -1,23 +1,13 @@
 use sprs::CsMat;
 
-use crate::netlist::NodeId;
-
 pub trait MatExt<T> {
-    fn update_by_node_id(&mut self, row: usize, col: usize, val: T);
-
     fn add_by_node_id(&mut self, row: usize, col: usize, val: T);
-
-    fn get_mut_by_node_id(&mut self, row: NodeId, col: NodeId) -> Option<&mut T>;
 }
 
 pub trait VecExt<T> {
-    fn update_by_node_id(&mut self, row: usize, val: T);
-
     fn add_by_node_id(&mut self, row: usize, val: T);
 
     fn get_by_node_id(&self, row: usize) -> T;
-
-    fn get_mut_by_node_id(&mut self, row: NodeId) -> Option<&mut T>;
 }
 
 impl<T> MatExt<T> for CsMat<T>
@@ -29,15 +19,6 @@ where
         + std::ops::Neg<Output = T>
         + std::ops::SubAssign,
 {
-    fn update_by_node_id(&mut self, row: usize, col: usize, val: T) {
-        if (row == 0) || (col == 0) {
-            return;
-        }
-        assert!(row <= self.rows() && col <= self.cols());
-        let ref_cell = self.get_mut(row - 1, col - 1).unwrap();
-        *ref_cell = val;
-    }
-
     fn add_by_node_id(&mut self, row: usize, col: usize, val: T) {
         if (row == 0) || (col == 0) {
             return;
@@ -46,13 +27,6 @@ where
         let ref_cell = self.get_mut(row - 1, col - 1).unwrap();
         *ref_cell += val;
     }
-
-    fn get_mut_by_node_id(&mut self, row: NodeId, col: NodeId) -> Option<&mut T> {
-        assert!(row <= self.rows() && col <= self.cols());
-        assert!(row != 0 && col != 0);
-
-        self.get_mut(row - 1, col - 1)
-    }
 }
 
 impl<T> VecExt<T> for sprs::CsVec<T>
@@ -65,15 +39,6 @@ where
         + std::ops::SubAssign
         + num_traits::Zero,
 {
-    fn update_by_node_id(&mut self, row: usize, val: T) {
-        if row == 0 {
-            return;
-        }
-        assert!(row <= self.dim());
-        let ref_cell = self.get_mut(row - 1).unwrap();
-        *ref_cell = val;
-    }
-
     fn add_by_node_id(&mut self, row: usize, val: T) {
         if row == 0 {
             return;
@@ -95,11 +60,4 @@ where
         }
         ref_cell.unwrap().clone()
     }
-
-    fn get_mut_by_node_id(&mut self, row: NodeId) -> Option<&mut T> {
-        assert!(row <= self.dim());
-        assert!(row != 0);
-
-        self.get_mut(row - 1)
-    }
 }
@@ -0,0 +1,526 @@
+use std::fmt;
+
+use crate::elements::base::{format_value, parse_parameter_value};
+use crate::result::{AnalysisKind, AnalysisResult, SignalExpr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    Rise,
+    Fall,
+    Cross,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Occurrence {
+    Nth(usize),
+    Last,
+}
+
+/// The `count`-th time `expr` crosses `value` in direction `edge`, not
+/// earlier than `td`.
+#[derive(Debug, Clone)]
+struct Crossing {
+    expr: SignalExpr,
+    value: f64,
+    edge: Edge,
+    occurrence: Occurrence,
+    td: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatKind {
+    Min,
+    Max,
+    Avg,
+    Rms,
+    Pp,
+    Integ,
+}
+
+#[derive(Debug, Clone)]
+enum Point {
+    At(f64),
+    When(Crossing),
+}
+
+#[derive(Debug, Clone)]
+enum Measure {
+    TrigTarg {
+        trig: Crossing,
+        targ: Crossing,
+    },
+    When(Crossing),
+    Find {
+        expr: SignalExpr,
+        point: Point,
+    },
+    Deriv {
+        expr: SignalExpr,
+        point: Point,
+    },
+    Stat {
+        kind: StatKind,
+        expr: SignalExpr,
+        from: Option<f64>,
+        to: Option<f64>,
+    },
+}
+
+/// A `.MEAS` statement.
+#[derive(Debug, Clone)]
+pub struct MeasSpec {
+    pub name: String,
+    pub analysis: AnalysisKind,
+    measure: Measure,
+}
+
+/// Outcome of a `.MEAS` statement, `None` if the condition never occurred.
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub name: String,
+    pub value: Option<f64>,
+}
+
+/// Token stream of a `.MEAS` line, with `KEY = VALUE` glued to `KEY=VALUE`.
+struct Tokens {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Tokens {
+    fn new(s: &str) -> Self {
+        let mut tokens: Vec<String> = Vec::new();
+        for word in s.split_whitespace() {
+            match tokens.last_mut() {
+                Some(last) if last.ends_with('=') || word.starts_with('=') => last.push_str(word),
+                _ => tokens.push(word.to_string()),
+            }
+        }
+        Self { tokens, pos: 0 }
+    }
+
+    fn next(&mut self) -> Option<&str> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<&str, String> {
+        self.next()
+            .ok_or_else(|| format!("Missing {} in .MEAS", what))
+    }
+
+    /// Take the next token if it is `KEY=VALUE` with `KEY` in `keys`.
+    fn next_key(&mut self, keys: &[&str]) -> Option<(String, String)> {
+        let (key, value) = self.tokens.get(self.pos)?.split_once('=')?;
+        let key = key.to_ascii_uppercase();
+        if !keys.contains(&key.as_str()) {
+            return None;
+        }
+        let value = value.to_string();
+        self.pos += 1;
+        Some((key, value))
+    }
+
+    fn peek_upper(&self) -> Option<String> {
+        self.tokens.get(self.pos).map(|t| t.to_ascii_uppercase())
+    }
+}
+
+impl Crossing {
+    /// Write the edge and delay options, omitting the defaults.
+    fn fmt_options(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let edge = match self.edge {
+            Edge::Rise => "RISE",
+            Edge::Fall => "FALL",
+            Edge::Cross => "CROSS",
+        };
+        match self.occurrence {
+            Occurrence::Nth(1) if self.edge == Edge::Cross => {}
+            Occurrence::Nth(n) => write!(f, " {}={}", edge, n)?,
+            Occurrence::Last => write!(f, " {}=LAST", edge)?,
+        }
+        if self.td.is_finite() {
+            write!(f, " TD={}", format_value(self.td))?;
+        }
+        Ok(())
+    }
+
+    /// Write the condition of a `WHEN` point or measurement.
+    fn fmt_when(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WHEN {}={}", self.expr, format_value(self.value))?;
+        self.fmt_options(f)
+    }
+
+    /// Parse `V(1) VAL=0.5 RISE=1 TD=1e-9` (after `TRIG`/`TARG`) or, with
+    /// `value` already split off, the options of `WHEN V(1)=0.5 ...`.
+    fn parse(tokens: &mut Tokens, expr: SignalExpr, value: Option<f64>) -> Result<Self, String> {
+        let mut crossing = Crossing {
+            expr,
+            value: value.unwrap_or(f64::NAN),
+            edge: Edge::Cross,
+            occurrence: Occurrence::Nth(1),
+            td: f64::NEG_INFINITY,
+        };
+        let mut has_value = value.is_some();
+
+        while let Some((key, value)) = tokens.next_key(&["VAL", "RISE", "FALL", "CROSS", "TD"]) {
+            match key.as_str() {
+                "VAL" => {
+                    crossing.value = parse_parameter_value(&key, &value)?;
+                    has_value = true;
+                }
+                "TD" => crossing.td = parse_parameter_value(&key, &value)?,
+                _ => {
+                    crossing.edge = match key.as_str() {
+                        "RISE" => Edge::Rise,
+                        "FALL" => Edge::Fall,
+                        _ => Edge::Cross,
+                    };
+                    crossing.occurrence = if value.eq_ignore_ascii_case("LAST") {
+                        Occurrence::Last
+                    } else {
+                        match value.parse::<usize>() {
+                            Ok(n) if n > 0 => Occurrence::Nth(n),
+                            _ => return Err(format!("Invalid value for {}: {}", key, value)),
+                        }
+                    };
+                }
+            }
+        }
+
+        if !has_value {
+            return Err("Missing VAL in .MEAS".to_string());
+        }
+        Ok(crossing)
+    }
+
+    /// Parse `V(1)=0.5 RISE=1` after `WHEN`.
+    fn parse_when(tokens: &mut Tokens) -> Result<Self, String> {
+        let condition = tokens.expect("WHEN condition")?;
+        let (expr, value) = condition
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Invalid WHEN condition: {}", condition))?;
+        let expr = SignalExpr::parse(expr)?;
+        let value = parse_parameter_value("WHEN", value)?;
+        Crossing::parse(tokens, expr, Some(value))
+    }
+
+    fn find(&self, result: &AnalysisResult) -> Result<Option<f64>, String> {
+        let curve = get_curve(result, &self.expr)?;
+        let mut count = 0;
+        let mut last = None;
+
+        for w in curve.windows(2) {
+            let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+            let (a, b) = (y0 - self.value, y1 - self.value);
+            let rising = a < 0. && b >= 0.;
+            let falling = a > 0. && b <= 0.;
+            let matched = match self.edge {
+                Edge::Rise => rising,
+                Edge::Fall => falling,
+                Edge::Cross => rising || falling,
+            };
+            if !matched {
+                continue;
+            }
+            let x = x0 + (x1 - x0) * a / (a - b);
+            if x < self.td {
+                continue;
+            }
+            count += 1;
+            match self.occurrence {
+                Occurrence::Nth(n) if n == count => return Ok(Some(x)),
+                _ => last = Some(x),
+            }
+        }
+
+        Ok(match self.occurrence {
+            Occurrence::Last => last,
+            Occurrence::Nth(_) => None,
+        })
+    }
+}
+
+impl fmt::Display for Crossing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} VAL={}", self.expr, format_value(self.value))?;
+        self.fmt_options(f)
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Point::At(x) => write!(f, "AT={}", format_value(*x)),
+            Point::When(crossing) => crossing.fmt_when(f),
+        }
+    }
+}
+
+impl Point {
+    fn parse(tokens: &mut Tokens) -> Result<Self, String> {
+        if let Some((key, value)) = tokens.next_key(&["AT"]) {
+            return Ok(Point::At(parse_parameter_value(&key, &value)?));
+        }
+        match tokens.next().map(|t| t.to_ascii_uppercase()).as_deref() {
+            Some("WHEN") => Ok(Point::When(Crossing::parse_when(tokens)?)),
+            _ => Err("Expected AT=<value> or WHEN in .MEAS".to_string()),
+        }
+    }
+
+    fn find(&self, result: &AnalysisResult) -> Result<Option<f64>, String> {
+        match self {
+            Point::At(x) => Ok(Some(*x)),
+            Point::When(crossing) => crossing.find(result),
+        }
+    }
+}
+
+/// Points of `expr` against the sweep variable, in increasing sweep order.
+fn get_curve(result: &AnalysisResult, expr: &SignalExpr) -> Result<Vec<(f64, f64)>, String> {
+    if result.sweep.is_none() {
+        return Err("Measurements need a swept analysis".to_string());
+    }
+    let values = expr.evaluate(result)?;
+    let mut curve = result
+        .sweep_values
+        .iter()
+        .copied()
+        .zip(values)
+        .collect::<Vec<_>>();
+    if curve.len() > 1 && curve[0].0 > curve[curve.len() - 1].0 {
+        curve.reverse();
+    }
+    Ok(curve)
+}
+
+/// Segment of `curve` containing `x`, or `None` if `x` is out of range.
+fn find_segment(curve: &[(f64, f64)], x: f64) -> Option<((f64, f64), (f64, f64))> {
+    if curve.len() == 1 {
+        return (curve[0].0 == x).then_some((curve[0], curve[0]));
+    }
+    curve
+        .windows(2)
+        .find(|w| w[0].0 <= x && x <= w[1].0)
+        .map(|w| (w[0], w[1]))
+}
+
+fn interpolate(curve: &[(f64, f64)], x: f64) -> Option<f64> {
+    let ((x0, y0), (x1, y1)) = find_segment(curve, x)?;
+    if x1 == x0 {
+        return Some(y0);
+    }
+    Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+}
+
+fn derivative(curve: &[(f64, f64)], x: f64) -> Option<f64> {
+    let ((x0, y0), (x1, y1)) = find_segment(curve, x)?;
+    (x1 != x0).then(|| (y1 - y0) / (x1 - x0))
+}
+
+/// The part of `curve` within `[from, to]`, with interpolated end points.
+fn clip(curve: &[(f64, f64)], from: Option<f64>, to: Option<f64>) -> Vec<(f64, f64)> {
+    let from = from.unwrap_or(f64::NEG_INFINITY);
+    let to = to.unwrap_or(f64::INFINITY);
+    let mut clipped = Vec::new();
+    if let Some(y) = interpolate(curve, from) {
+        clipped.push((from, y));
+    }
+    clipped.extend(curve.iter().filter(|(x, _)| from < *x && *x < to));
+    if let Some(y) = interpolate(curve, to) {
+        if to > from {
+            clipped.push((to, y));
+        }
+    }
+    clipped
+}
+
+fn get_stat(kind: StatKind, curve: &[(f64, f64)]) -> Option<f64> {
+    let first = curve.first()?;
+    let last = curve.last()?;
+    let integrate = |f: fn(f64, f64) -> f64| {
+        curve
+            .windows(2)
+            .map(|w| (w[1].0 - w[0].0) * f(w[0].1, w[1].1))
+            .sum::<f64>()
+    };
+    let span = last.0 - first.0;
+    let min = curve.iter().map(|(_, y)| *y).fold(f64::INFINITY, f64::min);
+    let max = curve
+        .iter()
+        .map(|(_, y)| *y)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    match kind {
+        StatKind::Min => Some(min),
+        StatKind::Max => Some(max),
+        StatKind::Pp => Some(max - min),
+        StatKind::Integ => Some(integrate(|a, b| (a + b) / 2.)),
+        StatKind::Avg if span > 0. => Some(integrate(|a, b| (a + b) / 2.) / span),
+        StatKind::Rms if span > 0. => {
+            // Exact integral of the square of each linear segment.
+            Some((integrate(|a, b| (a * a + a * b + b * b) / 3.) / span).sqrt())
+        }
+        StatKind::Avg | StatKind::Rms => Some(first.1.abs()),
+    }
+}
+
+impl fmt::Display for MeasSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ".MEAS {} {} ", self.analysis.get_keyword(), self.name)?;
+        match &self.measure {
+            Measure::TrigTarg { trig, targ } => write!(f, "TRIG {} TARG {}", trig, targ),
+            Measure::When(crossing) => crossing.fmt_when(f),
+            Measure::Find { expr, point } => write!(f, "FIND {} {}", expr, point),
+            Measure::Deriv { expr, point } => write!(f, "DERIV {} {}", expr, point),
+            Measure::Stat {
+                kind,
+                expr,
+                from,
+                to,
+            } => {
+                let kind = match kind {
+                    StatKind::Min => "MIN",
+                    StatKind::Max => "MAX",
+                    StatKind::Avg => "AVG",
+                    StatKind::Rms => "RMS",
+                    StatKind::Pp => "PP",
+                    StatKind::Integ => "INTEG",
+                };
+                write!(f, "{} {}", kind, expr)?;
+                if let Some(from) = from {
+                    write!(f, " FROM={}", format_value(*from))?;
+                }
+                if let Some(to) = to {
+                    write!(f, " TO={}", format_value(*to))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl MeasSpec {
+    /// Parse a line like
+    /// `.MEAS TRAN tpd TRIG V(1) VAL=1.5 RISE=1 TARG V(2) VAL=1.5 FALL=1`.
+    ///
+    /// Supported forms, after `.MEAS <TRAN|DC> <name>`:
+    /// - `TRIG <sig> VAL=<v> [RISE|FALL|CROSS=<n|LAST>] [TD=<x>] TARG ...`
+    /// - `WHEN <sig>=<v> [RISE|FALL|CROSS=<n|LAST>] [TD=<x>]`
+    /// - `FIND <sig> AT=<x>` or `FIND <sig> WHEN ...`
+    /// - `DERIV <sig> AT=<x>` or `DERIV <sig> WHEN ...`
+    /// - `<MIN|MAX|AVG|RMS|PP|INTEG> <sig> [FROM=<x>] [TO=<x>]`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut tokens = Tokens::new(s);
+        tokens.next();
+
+        let analysis = match tokens.expect("analysis")?.to_ascii_uppercase().as_str() {
+            "TRAN" => AnalysisKind::Transient,
+            "DC" => AnalysisKind::DcSweep,
+            "AC" => return Err("AC analysis is not supported".to_string()),
+            other => return Err(format!("Unknown analysis in .MEAS: {}", other)),
+        };
+        let name = tokens.expect("name")?.to_string();
+
+        let measure = match tokens.expect("measurement")?.to_ascii_uppercase().as_str() {
+            "TRIG" => {
+                let trig_expr = SignalExpr::parse(tokens.expect("TRIG signal")?)?;
+                let trig = Crossing::parse(&mut tokens, trig_expr, None)?;
+                if tokens.peek_upper().as_deref() != Some("TARG") {
+                    return Err("Missing TARG in .MEAS".to_string());
+                }
+                tokens.next();
+                let targ_expr = SignalExpr::parse(tokens.expect("TARG signal")?)?;
+                let targ = Crossing::parse(&mut tokens, targ_expr, None)?;
+                Measure::TrigTarg { trig, targ }
+            }
+            "WHEN" => Measure::When(Crossing::parse_when(&mut tokens)?),
+            "FIND" => {
+                let expr = SignalExpr::parse(tokens.expect("FIND signal")?)?;
+                Measure::Find {
+                    expr,
+                    point: Point::parse(&mut tokens)?,
+                }
+            }
+            "DERIV" => {
+                let expr = SignalExpr::parse(tokens.expect("DERIV signal")?)?;
+                Measure::Deriv {
+                    expr,
+                    point: Point::parse(&mut tokens)?,
+                }
+            }
+            stat => {
+                let kind = match stat {
+                    "MIN" => StatKind::Min,
+                    "MAX" => StatKind::Max,
+                    "AVG" => StatKind::Avg,
+                    "RMS" => StatKind::Rms,
+                    "PP" => StatKind::Pp,
+                    "INTEG" | "INTEGRAL" => StatKind::Integ,
+                    _ => return Err(format!("Unknown measurement: {}", stat)),
+                };
+                let expr = SignalExpr::parse(tokens.expect("signal")?)?;
+                let (mut from, mut to) = (None, None);
+                while let Some((key, value)) = tokens.next_key(&["FROM", "TO"]) {
+                    let value = Some(parse_parameter_value(&key, &value)?);
+                    if key == "FROM" {
+                        from = value;
+                    } else {
+                        to = value;
+                    }
+                }
+                Measure::Stat {
+                    kind,
+                    expr,
+                    from,
+                    to,
+                }
+            }
+        };
+
+        if let Some(token) = tokens.next() {
+            return Err(format!("Unexpected token in .MEAS: {}", token));
+        }
+
+        Ok(Self {
+            name,
+            analysis,
+            measure,
+        })
+    }
+
+    /// Evaluate against `result`. Errors are reserved for references to
+    /// unknown signals; a condition that never occurs gives `None`.
+    pub fn evaluate(&self, result: &AnalysisResult) -> Result<Measurement, String> {
+        let value = match &self.measure {
+            Measure::TrigTarg { trig, targ } => match (trig.find(result)?, targ.find(result)?) {
+                (Some(t0), Some(t1)) => Some(t1 - t0),
+                _ => None,
+            },
+            Measure::When(crossing) => crossing.find(result)?,
+            Measure::Find { expr, point } => {
+                let curve = get_curve(result, expr)?;
+                point.find(result)?.and_then(|x| interpolate(&curve, x))
+            }
+            Measure::Deriv { expr, point } => {
+                let curve = get_curve(result, expr)?;
+                point.find(result)?.and_then(|x| derivative(&curve, x))
+            }
+            Measure::Stat {
+                kind,
+                expr,
+                from,
+                to,
+            } => {
+                let curve = get_curve(result, expr)?;
+                get_stat(*kind, &clip(&curve, *from, *to))
+            }
+        };
+
+        Ok(Measurement {
+            name: self.name.clone(),
+            value,
+        })
+    }
+}
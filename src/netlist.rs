@@ -1,28 +1,82 @@
 pub type NodeId = usize;
 use crate::{
-    elements::base::MatrixSettable,
+    elements::base::{Element, MatrixSettable},
+    elements::basic::BasicElementType,
     elements::{
-        companion::CompanionModel, BasicElement, TimeVaringLinearElement,
+        companion::CompanionModel, BasicElement, MutualInductance, TimeVaringLinearElement,
         TimeVaringNonLinearElement,
     },
     matrix::build::{MatrixTriplets, VecItems},
+    parser::ParsedInfo,
 };
 use log::debug;
 use sprs::{CsMat, CsVec, TriMat};
 use std::cell::Cell;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Netlist {
     pub node_num: Cell<usize>, // include ground node
     pub basic_elements: Vec<BasicElement>,
     pub time_varing_linear_elements: Vec<TimeVaringLinearElement>,
     pub time_varing_non_linear_elements: Vec<TimeVaringNonLinearElement>,
+    pub mutual_inductances: Vec<MutualInductance>,
+    /// Names of nodes 1, 2, ... given to a `NetlistBuilder`, empty when the
+    /// nodes are numbered.
+    pub node_names: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct Equation {
     pub mat_a: CsMat<f64>,
     pub vec_b: CsVec<f64>,
+    pub unknowns: Unknowns,
+}
+
+/// Layout of the MNA unknown vector: node voltages (ground excluded) come
+/// first, followed by the branch currents of voltage sources and inductors.
+#[derive(Debug, Clone, Default)]
+pub struct Unknowns {
+    pub voltage_num: usize,
+    pub branch_names: Vec<String>,
+    /// Indices of the unknowns internal to companion models, which are not
+    /// part of the netlist.
+    pub internal: Vec<usize>,
+    /// Names of the nodes, as in [`Netlist::node_names`].
+    pub node_names: Vec<String>,
+}
+
+impl Unknowns {
+    pub fn count(&self) -> usize {
+        self.voltage_num + self.branch_names.len()
+    }
+
+    pub fn is_voltage(&self, index: usize) -> bool {
+        index < self.voltage_num
+    }
+
+    /// Get the index of the branch current of element `name`.
+    pub fn get_branch_index(&self, name: &str) -> Option<usize> {
+        self.branch_names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| self.voltage_num + i)
+    }
+
+    pub fn is_internal(&self, index: usize) -> bool {
+        self.internal.contains(&index)
+    }
+
+    /// Get the SPICE-style name of the unknown at `index`, e.g. `V(3)`,
+    /// `V(out)` or `I(VDD)`.
+    pub fn get_name(&self, index: usize) -> String {
+        if let Some(name) = self.node_names.get(index) {
+            format!("V({})", name)
+        } else if self.is_voltage(index) {
+            format!("V({})", index + 1)
+        } else {
+            format!("I({})", self.branch_names[index - self.voltage_num])
+        }
+    }
 }
 enum EquationType {
     Dc,
@@ -30,6 +84,65 @@ enum EquationType {
 }
 
 impl Netlist {
+    fn get_source_mut(&mut self, name: &str) -> Option<&mut BasicElement> {
+        self.basic_elements.iter_mut().find(|e| {
+            e.get_name().eq_ignore_ascii_case(name)
+                && matches!(
+                    e.get_element_type(),
+                    BasicElementType::VoltageSource(..) | BasicElementType::CurrentSource(..)
+                )
+        })
+    }
+
+    /// Set the value of the independent source `name`.
+    pub fn set_source_value(&mut self, name: &str, value: f64) -> Result<(), String> {
+        self.get_source_mut(name)
+            .ok_or_else(|| format!("Unknown source: {}", name))?
+            .set_base_value(value);
+        Ok(())
+    }
+
+    /// Set the circuit temperature in degrees Celsius of every element that
+    /// depends on it.
+    pub fn set_temperature(&mut self, temp: f64) {
+        for element in self.basic_elements.iter_mut() {
+            element.set_temperature(temp);
+        }
+        for element in self.time_varing_non_linear_elements.iter_mut() {
+            element.set_temperature(temp);
+        }
+    }
+
+    /// Get the unit of the value of the independent source `name`.
+    pub fn get_source_unit(&self, name: &str) -> &'static str {
+        let is_current_source = self.basic_elements.iter().any(|e| {
+            e.get_name().eq_ignore_ascii_case(name)
+                && matches!(e.get_element_type(), BasicElementType::CurrentSource(..))
+        });
+        if is_current_source {
+            "A"
+        } else {
+            "V"
+        }
+    }
+
+    /// Move the elements of `parsed_info` into a netlist. Its options and
+    /// directives are left in place.
+    pub fn from_parsed(parsed_info: &mut ParsedInfo) -> Self {
+        Self {
+            node_num: Cell::new(parsed_info.node_num),
+            basic_elements: std::mem::take(&mut parsed_info.basic_elements),
+            time_varing_linear_elements: std::mem::take(
+                &mut parsed_info.time_varing_linear_elements,
+            ),
+            time_varing_non_linear_elements: std::mem::take(
+                &mut parsed_info.time_varing_non_linear_elements,
+            ),
+            mutual_inductances: std::mem::take(&mut parsed_info.mutual_inductances),
+            node_names: Vec::new(),
+        }
+    }
+
     pub fn append_new_node(&self) -> NodeId {
         let node_num = self.node_num.get();
         self.node_num.set(node_num + 1);
@@ -87,6 +200,18 @@ impl Netlist {
             }
         }
 
+        let unknowns = Unknowns {
+            voltage_num: self.node_num.get() - 1,
+            branch_names: mat.branch_names,
+            internal: companion_models
+                .iter()
+                .filter_map(|m| m.get_internal_unknowns())
+                .flatten()
+                .map(|node| node - 1)
+                .collect(),
+            node_names: self.node_names.clone(),
+        };
+
         let (rows, cols, vals) = (mat.rows, mat.cols, mat.vals);
         let tri_mat = TriMat::from_triplets((mat.size, mat.size), rows, cols, vals);
 
@@ -96,7 +221,7 @@ impl Netlist {
             .iter()
             .map(|(i, v)| (*i, *v))
             .collect::<Vec<(usize, f64)>>();
-        v.sort_by(|(i1, _), (i2, _)| i1.cmp(i2));
+        v.sort_by_key(|(i, _)| *i);
 
         let vec_b = CsVec::new(
             mat.size,
@@ -106,6 +231,10 @@ impl Netlist {
 
         debug!("mat:\n{}, vec:\n{}", mat_a.to_dense(), vec_b.to_dense());
 
-        Equation { mat_a, vec_b }
+        Equation {
+            mat_a,
+            vec_b,
+            unknowns,
+        }
     }
 }
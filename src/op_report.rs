@@ -0,0 +1,141 @@
+use sprs::CsVec;
+
+use crate::elements::base::{Element, OpReportable};
+use crate::elements::time_varing_non_linear::mosfet::MosfetOpInfo;
+use crate::netlist::{Netlist, Unknowns};
+
+/// Current and absorbed power of one element. Sources supplying power have
+/// a negative `power`.
+#[derive(Debug, Clone)]
+pub struct ElementOp {
+    pub name: String,
+    pub current: f64,
+    pub power: f64,
+}
+
+/// Device-level view of an operating point.
+#[derive(Debug, Clone)]
+pub struct OpReport {
+    pub mosfets: Vec<(String, MosfetOpInfo)>,
+    pub elements: Vec<ElementOp>,
+}
+
+impl OpReport {
+    pub fn new(netlist: &Netlist, x: &CsVec<f64>, unknowns: &Unknowns) -> Self {
+        let mosfets = netlist
+            .time_varing_non_linear_elements
+            .iter()
+            .filter_map(|e| Some((e.get_name().to_string(), e.get_op_info(x)?)))
+            .collect();
+
+        let element_op = |e: &dyn ReportElement| {
+            let (current, power) = e.get_current_power(x, unknowns);
+            ElementOp {
+                name: e.get_name().to_string(),
+                current,
+                power,
+            }
+        };
+        let elements = netlist
+            .basic_elements
+            .iter()
+            .map(|e| element_op(e))
+            .chain(
+                netlist
+                    .time_varing_linear_elements
+                    .iter()
+                    .map(|e| element_op(e)),
+            )
+            .chain(
+                netlist
+                    .time_varing_non_linear_elements
+                    .iter()
+                    .map(|e| element_op(e)),
+            )
+            .collect();
+
+        Self { mosfets, elements }
+    }
+
+    /// Get the power delivered by the sources, i.e. the total dissipation.
+    pub fn get_total_power(&self) -> f64 {
+        -self
+            .elements
+            .iter()
+            .filter(|e| e.power < 0.)
+            .map(|e| e.power)
+            .sum::<f64>()
+    }
+
+    pub fn print(&self, disp_digits: usize) {
+        let width = disp_digits + 8;
+        let num = |v: f64| format!("{:>w$.p$e}", v, w = width, p = disp_digits);
+        let name_width = self
+            .elements
+            .iter()
+            .map(|e| e.name.len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+
+        if !self.mosfets.is_empty() {
+            println!();
+            println!(
+                "{:<nw$} {:<10} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$} {:>w$}",
+                "Device",
+                "Region",
+                "Id",
+                "Vgs",
+                "Vds",
+                "Vdsat",
+                "gm",
+                "gds",
+                "gmb",
+                nw = name_width,
+                w = width
+            );
+            for (name, info) in &self.mosfets {
+                println!(
+                    "{:<nw$} {:<10} {} {} {} {} {} {} {}",
+                    name,
+                    info.region,
+                    num(info.id),
+                    num(info.vgs),
+                    num(info.vds),
+                    num(info.vdsat),
+                    num(info.gm),
+                    num(info.gds),
+                    num(info.gmb),
+                    nw = name_width
+                );
+            }
+        }
+
+        println!();
+        println!(
+            "{:<nw$} {:>w$} {:>w$}",
+            "Device",
+            "Current",
+            "Power",
+            nw = name_width,
+            w = width
+        );
+        for e in &self.elements {
+            println!(
+                "{:<nw$} {} {}",
+                e.name,
+                num(e.current),
+                num(e.power),
+                nw = name_width
+            );
+        }
+        println!(
+            "Total power dissipation: {} W",
+            num(self.get_total_power()).trim()
+        );
+    }
+}
+
+trait ReportElement: Element + OpReportable {}
+
+impl<T: Element + OpReportable> ReportElement for T {}
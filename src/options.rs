@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::elements::base::{format_value, NOMINAL_TEMPERATURE};
+use crate::solver::base::{ConvergenceOptions, LinearSolverKind};
+
+/// Simulator options set by `.OPTIONS` directives.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub convergence: ConvergenceOptions,
+    pub linear_solver: LinearSolverKind,
+    /// Worker threads for multi-point analyses, 0 for one per core.
+    pub threads: usize,
+    /// Circuit temperature in degrees Celsius.
+    pub temp: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            convergence: ConvergenceOptions::default(),
+            linear_solver: LinearSolverKind::default(),
+            threads: 0,
+            temp: NOMINAL_TEMPERATURE,
+        }
+    }
+}
+
+impl fmt::Display for Options {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            ".OPTIONS RELTOL={} VNTOL={} ABSTOL={} SOLVER={} THREADS={} TEMP={}",
+            format_value(self.convergence.reltol),
+            format_value(self.convergence.vntol),
+            format_value(self.convergence.abstol),
+            self.linear_solver.as_str(),
+            self.threads,
+            format_value(self.temp)
+        )
+    }
+}
+
+impl Options {
+    /// Parse a line like `.OPTIONS RELTOL=1e-4 VNTOL=1e-7 ABSTOL=1e-13`.
+    pub fn parse(&mut self, s: &str) -> Result<(), String> {
+        for item in s.split_whitespace().skip(1) {
+            let (key, value) = item
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid option: {}", item))?;
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Parse a line like `.TEMP 85`, the same as `.OPTIONS TEMP=85`.
+    pub fn parse_temp(&mut self, s: &str) -> Result<(), String> {
+        let values: Vec<&str> = s.split_whitespace().skip(1).collect();
+        match values[..] {
+            [value] => self.set("TEMP", value),
+            [] => Err("Missing temperature".to_string()),
+            _ => Err("Multiple temperatures are not supported, use .DC TEMP".to_string()),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let parse_f64 = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value for option {}: {}", key, value))
+        };
+
+        match key.to_ascii_uppercase().as_str() {
+            "RELTOL" => self.convergence.reltol = parse_f64(value)?,
+            "VNTOL" => self.convergence.vntol = parse_f64(value)?,
+            "ABSTOL" => self.convergence.abstol = parse_f64(value)?,
+            "TEMP" => self.temp = parse_f64(value)?,
+            "THREADS" => {
+                self.threads = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid value for option {}: {}", key, value))?
+            }
+            "SOLVER" => {
+                self.linear_solver = LinearSolverKind::parse(value)
+                    .ok_or_else(|| format!("Unknown linear solver: {}", value))?
+            }
+            _ => return Err(format!("Unknown option: {}", key)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+
+use crate::result::AnalysisResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_uppercase().as_str() {
+            "CSV" => Ok(OutputFormat::Csv),
+            "JSON" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+
+    /// Guess the format from the extension of `path`, defaulting to CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => OutputFormat::Json,
+            _ => OutputFormat::Csv,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResultOutput {
+    pub path: PathBuf,
+    pub format: OutputFormat,
+}
+
+pub fn write_result(result: &AnalysisResult, output: &ResultOutput) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(&output.path)?);
+    match output.format {
+        OutputFormat::Csv => write_csv(result, &mut w)?,
+        OutputFormat::Json => write_json(result, &mut w)?,
+    }
+    w.flush()
+}
+
+/// One header line with the sweep variable and signal names, then one line
+/// per point. `.MEAS` results follow as `# name,value` comment rows, with
+/// `failed` for measurements that did not trigger.
+fn write_csv(result: &AnalysisResult, w: &mut impl Write) -> std::io::Result<()> {
+    let header = result
+        .sweep
+        .iter()
+        .chain(result.signals.iter())
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>();
+    writeln!(w, "{}", header.join(","))?;
+
+    for (i, row) in result.rows.iter().enumerate() {
+        let line = result
+            .sweep_values
+            .get(i)
+            .into_iter()
+            .chain(row.iter())
+            .map(|v| format!("{:e}", v))
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", line.join(","))?;
+    }
+
+    for m in &result.measurements {
+        match m.value {
+            Some(value) => writeln!(w, "# {},{:e}", m.name, value)?,
+            None => writeln!(w, "# {},failed", m.name)?,
+        }
+    }
+    Ok(())
+}
+
+/// Column-oriented JSON with the analysis metadata.
+fn write_json(result: &AnalysisResult, w: &mut impl Write) -> std::io::Result<()> {
+    let options = &result.options;
+    let stats = &result.stats;
+
+    let sweep = result.sweep.as_ref().map(|s| {
+        json!({
+            "name": s.name,
+            "kind": s.kind.as_str(),
+            "values": result.sweep_values,
+        })
+    });
+    let signals = result
+        .signals
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            json!({
+                "name": s.name,
+                "kind": s.kind.as_str(),
+                "values": result.rows.iter().map(|row| row[i]).collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let value = json!({
+        "analysis": result.kind.as_str(),
+        "options": {
+            "reltol": options.convergence.reltol,
+            "vntol": options.convergence.vntol,
+            "abstol": options.convergence.abstol,
+            "solver": options.linear_solver.as_str(),
+            "threads": options.threads,
+        },
+        "elapsed": result.elapsed.as_secs_f64(),
+        "convergence": {
+            "solves": stats.solves,
+            "iterations": stats.iterations,
+            "max_iterations": stats.max_iterations,
+            "rejected_steps": stats.rejected_steps,
+        },
+        "measurements": result
+            .measurements
+            .iter()
+            .map(|m| json!({ "name": m.name, "value": m.value }))
+            .collect::<Vec<_>>(),
+        "points": result.point_num(),
+        "sweep": sweep,
+        "signals": signals,
+    });
+
+    serde_json::to_writer_pretty(&mut *w, &value)?;
+    writeln!(w)
+}
@@ -1,22 +1,42 @@
+use crate::analyze::DcSweep;
 use crate::elements::base::Element;
+use crate::elements::basic::BasicElementType;
 use crate::elements::time_varing_non_linear::mosfet;
+use crate::elements::time_varing_non_linear::switch::{SwitchModel, SwitchModels};
 use crate::elements::MosfetModel;
 
-use crate::elements::{BasicElement, TimeVaringLinearElement, TimeVaringNonLinearElement};
+use crate::elements::{
+    BasicElement, MutualInductance, TimeVaringLinearElement, TimeVaringNonLinearElement,
+};
+use crate::fourier::{FftSpec, FourSpec};
+use crate::measure::MeasSpec;
+use crate::options::Options;
+use crate::plot::PlotSpec;
+use crate::print::PrintSpec;
 use crate::task::Task;
 
 use std::io::BufRead;
 use std::{fs::File, path::PathBuf};
 
 pub struct Parser {
+    /// Path of the deck, also used to locate errors for in-memory decks.
     file: PathBuf,
 }
 
+#[derive(Default)]
 pub struct ParsedInfo {
     pub basic_elements: Vec<BasicElement>,
     pub time_varing_linear_elements: Vec<TimeVaringLinearElement>,
     pub time_varing_non_linear_elements: Vec<TimeVaringNonLinearElement>,
+    pub mutual_inductances: Vec<MutualInductance>,
     pub tasks: Vec<super::task::Task>,
+    pub options: Options,
+    pub dc_sweep: Option<DcSweep>,
+    pub plots: Vec<PlotSpec>,
+    pub prints: Vec<PrintSpec>,
+    pub measurements: Vec<MeasSpec>,
+    pub fours: Vec<FourSpec>,
+    pub ffts: Vec<FftSpec>,
     pub node_num: usize,
     pub max_node_id: usize,
 }
@@ -27,25 +47,40 @@ impl Parser {
     }
 
     pub fn parse(&self) -> Result<ParsedInfo, Box<dyn std::error::Error>> {
-        fn open_file(file_path: &PathBuf) -> Result<File, std::io::Error> {
-            File::open(file_path)
-        }
+        let file = File::open(&self.file)?;
+        self.parse_lines(std::io::BufReader::new(file).lines())
+    }
 
+    /// Parse the deck `content` instead of reading the file.
+    pub fn parse_str(&self, content: &str) -> Result<ParsedInfo, Box<dyn std::error::Error>> {
+        self.parse_lines(content.lines().map(|line| Ok(line.to_string())))
+    }
+
+    fn parse_lines(
+        &self,
+        lines: impl Iterator<Item = std::io::Result<String>>,
+    ) -> Result<ParsedInfo, Box<dyn std::error::Error>> {
         use std::collections::HashSet;
         let mut node_set: HashSet<usize> = HashSet::new();
         let mut max_node_id = 0;
 
-        let file = open_file(&self.file)?;
         let mut basic_elements: Vec<BasicElement> = Vec::new();
         let mut time_varing_linear_elements: Vec<TimeVaringLinearElement> = Vec::new();
         let mut time_varing_non_linear_elements: Vec<TimeVaringNonLinearElement> = Vec::new();
+        let mut mutual_inductances: Vec<MutualInductance> = Vec::new();
 
         let mut tasks: Vec<super::task::Task> = Vec::new();
+        let mut options = Options::default();
+        let mut dc_sweep = None;
+        let mut plots = Vec::new();
+        let mut prints = Vec::new();
+        let mut measurements = Vec::new();
+        let mut fours = Vec::new();
+        let mut ffts = Vec::new();
+        let mut mosfet_models = mosfet::MosfetModels::new();
+        let mut switch_models = SwitchModels::new();
 
-        let lines = std::io::BufReader::new(file).lines();
-
-        for (line, line_no) in lines.zip(1..) {
-            let line = line?;
+        for (line, line_no) in join_continuation_lines(lines)? {
             let trimmed_line = line.trim();
 
             if trimmed_line.starts_with('*') || trimmed_line.is_empty() {
@@ -53,6 +88,14 @@ impl Parser {
             }
 
             let first_char = trimmed_line.chars().next().unwrap();
+            let invalid_element = || {
+                format!(
+                    "Invalid element: {}, {}:{}",
+                    trimmed_line,
+                    self.file.display(),
+                    line_no
+                )
+            };
 
             let mut update_node_info_with_new_element = |element: &dyn Element| {
                 for node in element.get_nodes() {
@@ -63,42 +106,121 @@ impl Parser {
 
             match first_char.to_ascii_uppercase() {
                 'R' => {
-                    let resistor = BasicElement::parse_resistor(trimmed_line).unwrap();
+                    let resistor =
+                        BasicElement::parse_resistor(trimmed_line).ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&resistor);
                     basic_elements.push(resistor);
                 }
                 'V' => {
-                    let voltage_source = BasicElement::parse_voltage_source(trimmed_line).unwrap();
+                    let voltage_source = BasicElement::parse_voltage_source(trimmed_line)
+                        .ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&voltage_source);
                     basic_elements.push(voltage_source);
                 }
                 'I' => {
-                    let current_source = BasicElement::parse_current_source(trimmed_line).unwrap();
+                    let current_source = BasicElement::parse_current_source(trimmed_line)
+                        .ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&current_source);
                     basic_elements.push(current_source);
                 }
                 'C' => {
-                    let capacitor = TimeVaringLinearElement::parse_capacitor(trimmed_line).unwrap();
+                    let capacitor = TimeVaringLinearElement::parse_capacitor(trimmed_line)
+                        .ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&capacitor);
                     time_varing_linear_elements.push(capacitor);
                 }
                 'L' => {
-                    let inductor = TimeVaringLinearElement::parse_inductor(trimmed_line).unwrap();
+                    let inductor = TimeVaringLinearElement::parse_inductor(trimmed_line)
+                        .ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&inductor);
                     time_varing_linear_elements.push(inductor);
                 }
+                'T' => {
+                    let line = TimeVaringLinearElement::parse_transmission_line(trimmed_line)
+                        .ok_or_else(invalid_element)?;
+                    update_node_info_with_new_element(&line);
+                    time_varing_linear_elements.push(line);
+                }
+                'K' => {
+                    let mutual_inductance =
+                        MutualInductance::parse(trimmed_line).ok_or_else(invalid_element)?;
+                    mutual_inductances.push(mutual_inductance);
+                }
                 'M' => {
-                    let mosfet = TimeVaringNonLinearElement::parse_mosfet(trimmed_line);
+                    let mosfet = TimeVaringNonLinearElement::parse_mosfet(trimmed_line)
+                        .ok_or_else(invalid_element)?;
                     update_node_info_with_new_element(&mosfet);
                     time_varing_non_linear_elements.push(mosfet);
                 }
+                'B' => {
+                    let source = TimeVaringNonLinearElement::parse_behavioral(trimmed_line)
+                        .map_err(|e| format!("{}, {}:{}", e, self.file.display(), line_no))?;
+                    update_node_info_with_new_element(&source);
+                    time_varing_non_linear_elements.push(source);
+                }
+                'S' | 'W' => {
+                    let switch = TimeVaringNonLinearElement::parse_switch(trimmed_line)
+                        .ok_or_else(invalid_element)?;
+                    update_node_info_with_new_element(&switch);
+                    time_varing_non_linear_elements.push(switch);
+                }
                 '.' => {
                     let mut words = trimmed_line.split_ascii_whitespace();
                     let directive = words.next().unwrap();
                     match directive.to_ascii_uppercase().as_str() {
+                        ".MODEL" if SwitchModel::is_switch_model(trimmed_line) => {
+                            let (model_id, switch_model) = SwitchModel::parse(trimmed_line)
+                                .map_err(|e| {
+                                    format!("{}, {}:{}", e, self.file.display(), line_no)
+                                })?;
+                            switch_models.insert(model_id, switch_model);
+                        }
                         ".MODEL" => {
-                            let (model_id, mosfet_model) = MosfetModel::parse(trimmed_line);
-                            mosfet::add_mosfet_model(model_id, mosfet_model);
+                            let (model_id, mosfet_model) = MosfetModel::parse(trimmed_line)
+                                .map_err(|e| {
+                                    format!("{}, {}:{}", e, self.file.display(), line_no)
+                                })?;
+                            mosfet_models.insert(model_id, mosfet_model);
+                        }
+                        ".OPTIONS" | ".OPTION" => {
+                            options.parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?;
+                        }
+                        ".TEMP" => {
+                            options.parse_temp(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?;
+                        }
+                        ".DC" => {
+                            dc_sweep = Some(DcSweep::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
+                        }
+                        ".PLOT" => {
+                            plots.push(PlotSpec::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
+                        }
+                        ".PRINT" => {
+                            prints.push(PrintSpec::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
+                        }
+                        ".MEAS" | ".MEASURE" => {
+                            measurements.push(MeasSpec::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
+                        }
+                        ".FOUR" => {
+                            fours.push(FourSpec::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
+                        }
+                        ".FFT" => {
+                            ffts.push(FftSpec::parse(trimmed_line).map_err(|e| {
+                                format!("{}, {}:{}", e, self.file.display(), line_no)
+                            })?);
                         }
                         ".PLOTNV" => {
                             let node_id = words.next().unwrap().parse::<usize>().unwrap();
@@ -132,13 +254,73 @@ impl Parser {
             }
         }
 
+        for element in time_varing_non_linear_elements.iter_mut() {
+            element
+                .bind_model(&mosfet_models, &switch_models)
+                .map_err(|e| format!("{}, {}", e, self.file.display()))?;
+        }
+
+        // Current-controlled switches and behavioral sources sense the
+        // currents of voltage sources.
+        for element in time_varing_non_linear_elements.iter() {
+            for source in element.get_control_sources() {
+                let is_voltage_source = basic_elements.iter().any(|e| {
+                    e.get_name().eq_ignore_ascii_case(source)
+                        && matches!(e.get_element_type(), BasicElementType::VoltageSource(..))
+                });
+                if !is_voltage_source {
+                    return Err(format!(
+                        "Unknown controlling source of {}: {}, {}",
+                        element.get_name(),
+                        source,
+                        self.file.display()
+                    )
+                    .into());
+                }
+            }
+        }
+
+        // Couplings may precede the inductors they couple.
+        for mutual_inductance in mutual_inductances.iter() {
+            mutual_inductance
+                .get_mutual_inductance(&time_varing_linear_elements)
+                .map_err(|e| format!("{}, {}", e, self.file.display()))?;
+        }
+
         Ok(ParsedInfo {
             basic_elements,
             time_varing_linear_elements,
             time_varing_non_linear_elements,
+            mutual_inductances,
             tasks,
+            options,
+            dc_sweep,
+            plots,
+            prints,
+            measurements,
+            fours,
+            ffts,
             node_num: node_set.len(),
             max_node_id,
         })
     }
 }
+
+/// Append lines starting with `+` to the previous line, numbering each
+/// joined line by its first line.
+fn join_continuation_lines(
+    lines: impl Iterator<Item = std::io::Result<String>>,
+) -> std::io::Result<Vec<(String, usize)>> {
+    let mut joined: Vec<(String, usize)> = Vec::new();
+    for (line, line_no) in lines.zip(1..) {
+        let line = line?;
+        match (line.trim_start().strip_prefix('+'), joined.last_mut()) {
+            (Some(rest), Some((last, _))) => {
+                last.push(' ');
+                last.push_str(rest);
+            }
+            _ => joined.push((line, line_no)),
+        }
+    }
+    Ok(joined)
+}
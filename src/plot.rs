@@ -1,5 +1,13 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use plotters::coord::ranged1d::{AsRangedCoord, ValueFormatter};
+use plotters::coord::Shift;
 use plotters::prelude::*;
 
+use crate::result::{AnalysisKind, AnalysisResult, SignalExpr, SignalKind, SignalPart};
+
 const IMAGE_WIDTH: u32 = 640;
 const IMAGE_HEIGHT: u32 = 480;
 
@@ -90,3 +98,340 @@ pub fn plot(plot_info: PlotInfo, file_name: &str) {
         )
         .unwrap();
 }
+
+const TRACE_COLORS: [RGBColor; 6] = [
+    LINE_COLOR,
+    RGBColor(40, 110, 200),
+    RGBColor(60, 160, 80),
+    RGBColor(240, 140, 30),
+    RGBColor(140, 70, 170),
+    RGBColor(20, 20, 20),
+];
+
+const DEFAULT_PLOT_WIDTH: u32 = 800;
+const DEFAULT_PANEL_HEIGHT: u32 = 300;
+
+/// A `.PLOT` directive.
+#[derive(Debug, Clone)]
+pub struct PlotSpec {
+    /// Only plot results of this analysis, if given.
+    pub analysis: Option<AnalysisKind>,
+    /// Signals of each panel, top to bottom.
+    pub panels: Vec<Vec<SignalExpr>>,
+    pub x_log: bool,
+    pub y_log: bool,
+    pub file: Option<PathBuf>,
+    pub size: Option<(u32, u32)>,
+}
+
+impl fmt::Display for PlotSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ".PLOT")?;
+        if let Some(analysis) = self.analysis {
+            write!(f, " {}", analysis.get_keyword())?;
+        }
+        for (i, panel) in self.panels.iter().enumerate() {
+            if i > 0 {
+                write!(f, " |")?;
+            }
+            for signal in panel {
+                write!(f, " {}", signal)?;
+            }
+        }
+        if self.x_log {
+            write!(f, " XLOG")?;
+        }
+        if self.y_log {
+            write!(f, " YLOG")?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " FILE={}", file.display())?;
+        }
+        if let Some((width, height)) = self.size {
+            write!(f, " SIZE={}x{}", width, height)?;
+        }
+        Ok(())
+    }
+}
+
+impl PlotSpec {
+    /// Parse a line like
+    /// `.PLOT TRAN V(1) V(2,3) | I(VDD) YLOG FILE=out.png SIZE=800x600`.
+    ///
+    /// Signals are written as in `.PRINT` and overlaid in one chart; `|` starts a new panel below,
+    /// sharing the x axis. `XLOG`/`YLOG` select logarithmic axes.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut spec = Self {
+            analysis: None,
+            panels: vec![Vec::new()],
+            x_log: false,
+            y_log: false,
+            file: None,
+            size: None,
+        };
+
+        for (i, word) in s.split_whitespace().skip(1).enumerate() {
+            let upper = word.to_ascii_uppercase();
+            match upper.as_str() {
+                "OP" if i == 0 => spec.analysis = Some(AnalysisKind::Op),
+                "DC" if i == 0 => spec.analysis = Some(AnalysisKind::DcSweep),
+                "TRAN" if i == 0 => spec.analysis = Some(AnalysisKind::Transient),
+                "XLOG" => spec.x_log = true,
+                "YLOG" => spec.y_log = true,
+                "LOGLOG" => (spec.x_log, spec.y_log) = (true, true),
+                "|" => spec.panels.push(Vec::new()),
+                _ if upper.starts_with("FILE=") => {
+                    spec.file = Some(PathBuf::from(&word["FILE=".len()..]));
+                }
+                _ if upper.starts_with("SIZE=") => {
+                    let size = upper["SIZE=".len()..]
+                        .split_once('X')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or_else(|| format!("Invalid plot size: {}", word))?;
+                    spec.size = Some(size);
+                }
+                _ => spec
+                    .panels
+                    .last_mut()
+                    .unwrap()
+                    .push(SignalExpr::parse(word)?),
+            }
+        }
+
+        if spec.panels.iter().any(|p| p.is_empty()) {
+            return Err("Empty panel in .PLOT directive".to_string());
+        }
+        Ok(spec)
+    }
+
+    pub fn get_file(&self, index: usize) -> PathBuf {
+        self.file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("plot_{}.svg", index + 1)))
+    }
+}
+
+pub struct Trace {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+pub struct Panel {
+    pub traces: Vec<Trace>,
+    pub y_label: &'static str,
+}
+
+fn get_y_label(exprs: &[SignalExpr]) -> &'static str {
+    if exprs.iter().all(|e| e.part == SignalPart::Db) {
+        "Magnitude / dB"
+    } else if exprs.iter().all(|e| e.part == SignalPart::Phase) {
+        "Phase / °"
+    } else if exprs
+        .iter()
+        .any(|e| matches!(e.part, SignalPart::Db | SignalPart::Phase))
+    {
+        "Value"
+    } else if exprs.iter().all(|e| e.get_kind() == SignalKind::Voltage) {
+        "Voltage / V"
+    } else if exprs.iter().all(|e| e.get_kind() == SignalKind::Current) {
+        "Current / A"
+    } else {
+        "Value"
+    }
+}
+
+/// Draw the signals of `result` selected by `spec` into `file`.
+pub fn plot_result(
+    result: &AnalysisResult,
+    spec: &PlotSpec,
+    file: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let sweep = result
+        .sweep
+        .as_ref()
+        .ok_or("Nothing to plot for an operating point")?;
+
+    let panels = spec
+        .panels
+        .iter()
+        .map(|exprs| {
+            let traces = exprs
+                .iter()
+                .map(|expr| {
+                    Ok(Trace {
+                        name: expr.label.clone(),
+                        values: expr
+                            .evaluate(result)
+                            .map_err(|e| format!("{} in .PLOT", e))?,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(Panel {
+                traces,
+                y_label: get_y_label(exprs),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let x_label = match sweep.kind {
+        SignalKind::Time => "Time / s".to_string(),
+        SignalKind::Voltage => format!("{} / V", sweep.name),
+        SignalKind::Current => format!("{} / A", sweep.name),
+        SignalKind::Temperature => "TEMP / °C".to_string(),
+    };
+
+    let data = PlotData {
+        x_values: &result.sweep_values,
+        x_label: &x_label,
+        panels: &panels,
+        x_log: spec.x_log,
+        y_log: spec.y_log,
+        size: spec.size,
+    };
+    plot_panels(&data, file)
+}
+
+/// Panels stacked top to bottom over a shared x axis.
+pub struct PlotData<'a> {
+    pub x_values: &'a [f64],
+    pub x_label: &'a str,
+    pub panels: &'a [Panel],
+    pub x_log: bool,
+    pub y_log: bool,
+    pub size: Option<(u32, u32)>,
+}
+
+/// Draw `data` into `file`. The format follows the extension: PNG for
+/// `.png`, SVG otherwise.
+pub fn plot_panels(data: &PlotData, file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let size = data.size.unwrap_or((
+        DEFAULT_PLOT_WIDTH,
+        DEFAULT_PANEL_HEIGHT * data.panels.len() as u32,
+    ));
+    let is_png = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+
+    if is_png {
+        draw_panels(BitMapBackend::new(file, size).into_drawing_area(), data)
+    } else {
+        draw_panels(SVGBackend::new(file, size).into_drawing_area(), data)
+    }
+}
+
+fn get_range(values: impl Iterator<Item = f64>, log: bool) -> Result<Range<f64>, String> {
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for value in values {
+        let value = if log { value.abs() } else { value };
+        if log && value <= 0. {
+            continue;
+        }
+        min = min.min(value);
+        max = max.max(value);
+    }
+    if min > max {
+        return Err("No positive values to plot on a log axis".to_string());
+    }
+    if min == max {
+        let pad = if min == 0. { 1. } else { min.abs() * 0.1 };
+        return Ok((min - pad)..(max + pad));
+    }
+    Ok(min..max)
+}
+
+fn draw_panels<DB>(
+    root: DrawingArea<DB, Shift>,
+    data: &PlotData,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    let areas = root.split_evenly((data.panels.len(), 1));
+    let x_range = get_range(data.x_values.iter().copied(), data.x_log)?;
+
+    for (i, (area, panel)) in areas.iter().zip(data.panels.iter()).enumerate() {
+        let y_range = get_range(
+            panel.traces.iter().flat_map(|t| t.values.iter().copied()),
+            data.y_log,
+        )?;
+        let is_bottom = i + 1 == data.panels.len();
+        let x_range = x_range.clone();
+        match (data.x_log, data.y_log) {
+            (false, false) => draw_panel(area, x_range, y_range, panel, data, is_bottom)?,
+            (true, false) => {
+                draw_panel(area, x_range.log_scale(), y_range, panel, data, is_bottom)?
+            }
+            (false, true) => {
+                draw_panel(area, x_range, y_range.log_scale(), panel, data, is_bottom)?
+            }
+            (true, true) => draw_panel(
+                area,
+                x_range.log_scale(),
+                y_range.log_scale(),
+                panel,
+                data,
+                is_bottom,
+            )?,
+        }
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+fn draw_panel<DB, X, Y>(
+    area: &DrawingArea<DB, Shift>,
+    x_range: X,
+    y_range: Y,
+    panel: &Panel,
+    data: &PlotData,
+    is_bottom: bool,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    DB: DrawingBackend,
+    DB::ErrorType: 'static,
+    X: AsRangedCoord<Value = f64>,
+    Y: AsRangedCoord<Value = f64>,
+    X::CoordDescType: ValueFormatter<f64>,
+    Y::CoordDescType: ValueFormatter<f64>,
+{
+    let mut chart = ChartBuilder::on(area)
+        .margin(10)
+        .x_label_area_size(if is_bottom { 40 } else { 20 })
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range, y_range)?;
+
+    let mut mesh = chart.configure_mesh();
+    mesh.y_desc(panel.y_label);
+    if is_bottom {
+        mesh.x_desc(data.x_label);
+    }
+    mesh.draw()?;
+
+    for (i, trace) in panel.traces.iter().enumerate() {
+        let color = TRACE_COLORS[i % TRACE_COLORS.len()];
+        let y_log = data.y_log;
+        chart
+            .draw_series(LineSeries::new(
+                data.x_values
+                    .iter()
+                    .zip(trace.values.iter())
+                    .map(|(x, y)| (*x, if y_log { y.abs() } else { *y }))
+                    .filter(|(x, y)| (!data.x_log || *x > 0.) && (!y_log || *y > 0.)),
+                &color,
+            ))?
+            .label(trace.name.as_str())
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart
+        .configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::result::{AnalysisKind, AnalysisResult, SignalExpr};
+
+/// A `.PRINT` directive.
+#[derive(Debug, Clone)]
+pub struct PrintSpec {
+    /// Only print results of this analysis, if given.
+    pub analysis: Option<AnalysisKind>,
+    pub signals: Vec<SignalExpr>,
+    pub file: Option<PathBuf>,
+}
+
+impl fmt::Display for PrintSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, ".PRINT")?;
+        if let Some(analysis) = self.analysis {
+            write!(f, " {}", analysis.get_keyword())?;
+        }
+        for signal in &self.signals {
+            write!(f, " {}", signal)?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " FILE={}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl PrintSpec {
+    /// Parse a line like `.PRINT TRAN V(2) V(2,3) I(VDD) FILE=out.txt`.
+    ///
+    /// `AC` is rejected, as there is no AC analysis whose results it would
+    /// select.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut spec = Self {
+            analysis: None,
+            signals: Vec::new(),
+            file: None,
+        };
+
+        for (i, word) in s.split_whitespace().skip(1).enumerate() {
+            let upper = word.to_ascii_uppercase();
+            match upper.as_str() {
+                "OP" if i == 0 => spec.analysis = Some(AnalysisKind::Op),
+                "DC" if i == 0 => spec.analysis = Some(AnalysisKind::DcSweep),
+                "TRAN" if i == 0 => spec.analysis = Some(AnalysisKind::Transient),
+                "AC" if i == 0 => {
+                    return Err("AC analysis is not supported".to_string());
+                }
+                _ if upper.starts_with("FILE=") => {
+                    spec.file = Some(PathBuf::from(&word["FILE=".len()..]));
+                }
+                _ => spec.signals.push(SignalExpr::parse(word)?),
+            }
+        }
+
+        if spec.signals.is_empty() {
+            return Err("No signals in .PRINT directive".to_string());
+        }
+        Ok(spec)
+    }
+
+    /// Write the table to the file of the directive, or to stdout.
+    pub fn print(
+        &self,
+        result: &AnalysisResult,
+        disp_digits: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.file {
+            Some(file) => {
+                let mut w = BufWriter::new(File::create(file)?);
+                self.write_table(result, disp_digits, &mut w)?;
+                w.flush()?;
+            }
+            None => self.write_table(result, disp_digits, &mut std::io::stdout().lock())?,
+        }
+        Ok(())
+    }
+
+    fn write_table(
+        &self,
+        result: &AnalysisResult,
+        disp_digits: usize,
+        w: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let columns = self
+            .signals
+            .iter()
+            .map(|s| Ok((s.label.as_str(), s.evaluate(result)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let sweep = result
+            .sweep
+            .as_ref()
+            .map(|s| (s.name.as_str(), result.sweep_values.as_slice()));
+        let columns = sweep
+            .into_iter()
+            .chain(
+                columns
+                    .iter()
+                    .map(|(label, values)| (*label, values.as_slice())),
+            )
+            .collect::<Vec<_>>();
+
+        // Sign, leading digit, point and a three-digit exponent.
+        let width = disp_digits + 8;
+        let widths = columns
+            .iter()
+            .map(|(label, _)| label.len().max(width))
+            .collect::<Vec<_>>();
+
+        let header = columns
+            .iter()
+            .zip(widths.iter())
+            .map(|((label, _), w)| format!("{:>w$}", label, w = w))
+            .collect::<Vec<_>>();
+        writeln!(w, "{}", header.join("  "))?;
+
+        for i in 0..result.point_num() {
+            let row = columns
+                .iter()
+                .zip(widths.iter())
+                .map(|((_, values), w)| format!("{:>w$.p$e}", values[i], w = w, p = disp_digits))
+                .collect::<Vec<_>>();
+            writeln!(w, "{}", row.join("  "))?;
+        }
+        Ok(())
+    }
+}
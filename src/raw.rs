@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::result::AnalysisResult;
+
+/// Encoding of the `Values` section of a raw file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawFormat {
+    Ascii,
+    Binary,
+}
+
+impl RawFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_uppercase().as_str() {
+            "ASCII" => Ok(RawFormat::Ascii),
+            "BINARY" => Ok(RawFormat::Binary),
+            _ => Err(format!("Unknown raw file format: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RawOutput {
+    pub path: PathBuf,
+    pub format: RawFormat,
+    pub title: String,
+}
+
+/// Write `result` in the ngspice raw file layout. The sweep variable, if
+/// any, comes first, followed by every unknown of the MNA system.
+pub fn write_raw(result: &AnalysisResult, output: &RawOutput) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(&output.path)?);
+
+    let variables = result
+        .sweep
+        .iter()
+        .chain(result.signals.iter())
+        .collect::<Vec<_>>();
+
+    writeln!(w, "Title: {}", output.title)?;
+    writeln!(w, "Plotname: {}", result.kind.get_plotname())?;
+    writeln!(w, "Flags: real")?;
+    writeln!(w, "No. Variables: {}", variables.len())?;
+    writeln!(w, "No. Points: {}", result.point_num())?;
+    writeln!(w, "Variables:")?;
+    for (i, var) in variables.iter().enumerate() {
+        writeln!(w, "\t{}\t{}\t{}", i, var.name, var.kind.as_str())?;
+    }
+
+    let points = result.rows.iter().enumerate().map(|(i, row)| {
+        result
+            .sweep_values
+            .get(i)
+            .into_iter()
+            .chain(row.iter())
+            .copied()
+    });
+
+    match output.format {
+        RawFormat::Ascii => {
+            writeln!(w, "Values:")?;
+            for (i, point) in points.enumerate() {
+                for (j, val) in point.enumerate() {
+                    if j == 0 {
+                        writeln!(w, " {}\t{:.15e}", i, val)?;
+                    } else {
+                        writeln!(w, "\t{:.15e}", val)?;
+                    }
+                }
+                writeln!(w)?;
+            }
+        }
+        RawFormat::Binary => {
+            writeln!(w, "Binary:")?;
+            for val in points.flatten() {
+                w.write_all(&val.to_le_bytes())?;
+            }
+        }
+    }
+
+    w.flush()
+}
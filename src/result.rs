@@ -0,0 +1,276 @@
+use std::fmt;
+use std::time::Duration;
+
+use sprs::CsVec;
+
+use crate::measure::Measurement;
+use crate::netlist::Unknowns;
+use crate::options::Options;
+use crate::solver::base::ConvergenceStats;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisKind {
+    Op,
+    DcSweep,
+    Transient,
+}
+
+impl AnalysisKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnalysisKind::Op => "op",
+            AnalysisKind::DcSweep => "dc",
+            AnalysisKind::Transient => "tran",
+        }
+    }
+
+    /// Keyword selecting this analysis in output directives.
+    pub fn get_keyword(&self) -> &'static str {
+        match self {
+            AnalysisKind::Op => "OP",
+            AnalysisKind::DcSweep => "DC",
+            AnalysisKind::Transient => "TRAN",
+        }
+    }
+
+    /// Plot name used by ngspice for this analysis.
+    pub fn get_plotname(&self) -> &'static str {
+        match self {
+            AnalysisKind::Op => "Operating Point",
+            AnalysisKind::DcSweep => "DC transfer characteristic",
+            AnalysisKind::Transient => "Transient Analysis",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalKind {
+    Time,
+    Voltage,
+    Current,
+    Temperature,
+}
+
+impl SignalKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignalKind::Time => "time",
+            SignalKind::Voltage => "voltage",
+            SignalKind::Current => "current",
+            SignalKind::Temperature => "temperature",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signal {
+    pub name: String,
+    pub kind: SignalKind,
+}
+
+impl Signal {
+    pub fn new(name: &str, kind: SignalKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+}
+
+/// Result of one analysis: an optional sweep variable (time, swept source)
+/// and one column per node voltage and branch current of the netlist, with
+/// one row per solved point.
+#[derive(Debug, Clone)]
+pub struct AnalysisResult {
+    pub kind: AnalysisKind,
+    pub sweep: Option<Signal>,
+    pub signals: Vec<Signal>,
+    pub sweep_values: Vec<f64>,
+    pub rows: Vec<Vec<f64>>,
+    pub options: Options,
+    pub elapsed: Duration,
+    pub stats: ConvergenceStats,
+    /// Results of the `.MEAS` statements for this analysis.
+    pub measurements: Vec<Measurement>,
+    /// Index of the MNA unknown of each signal.
+    indices: Vec<usize>,
+}
+
+impl AnalysisResult {
+    /// Create an empty result with a signal for every unknown except those
+    /// internal to companion models.
+    pub fn new(
+        kind: AnalysisKind,
+        sweep: Option<Signal>,
+        unknowns: &Unknowns,
+        options: &Options,
+    ) -> Self {
+        let indices = (0..unknowns.count())
+            .filter(|i| !unknowns.is_internal(*i))
+            .collect::<Vec<_>>();
+        let signals = indices
+            .iter()
+            .map(|&i| Signal {
+                name: unknowns.get_name(i).to_ascii_lowercase(),
+                kind: if unknowns.is_voltage(i) {
+                    SignalKind::Voltage
+                } else {
+                    SignalKind::Current
+                },
+            })
+            .collect();
+
+        Self {
+            kind,
+            sweep,
+            signals,
+            sweep_values: Vec::new(),
+            rows: Vec::new(),
+            options: options.clone(),
+            elapsed: Duration::ZERO,
+            stats: ConvergenceStats::default(),
+            measurements: Vec::new(),
+            indices,
+        }
+    }
+
+    /// Append the solution `x` at sweep value `sweep_value` (`None` for an
+    /// operating point).
+    pub fn push(&mut self, sweep_value: Option<f64>, x: &CsVec<f64>) {
+        if let Some(value) = sweep_value {
+            self.sweep_values.push(value);
+        }
+        let row = self
+            .indices
+            .iter()
+            .map(|&i| x.get(i).copied().unwrap_or(0.))
+            .collect();
+        self.rows.push(row);
+    }
+
+    pub fn point_num(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Get the values of the signal `name` (case-insensitive), e.g. `v(2)`.
+    pub fn get_signal(&self, name: &str) -> Option<Vec<f64>> {
+        let index = self
+            .signals
+            .iter()
+            .position(|s| s.name.eq_ignore_ascii_case(name))?;
+        Some(self.rows.iter().map(|row| row[index]).collect())
+    }
+}
+
+/// How a signal is shown. Results are real, so the phase is 0 or 180 degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalPart {
+    Real,
+    Magnitude,
+    Db,
+    Phase,
+}
+
+/// A signal reference in an output directive, e.g. `V(2)`, `V(2,3)`,
+/// `VDB(out)` or `I(VDD)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalExpr {
+    pub label: String,
+    pub part: SignalPart,
+    /// Names of the positive and negative signals, ground omitted.
+    pos: Option<String>,
+    neg: Option<String>,
+}
+
+impl fmt::Display for SignalExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label.to_ascii_uppercase())
+    }
+}
+
+impl SignalExpr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid signal: {}", s);
+        let (func, args) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(invalid)?;
+        let func = func.to_ascii_uppercase();
+        let (quantity, part) = func.split_at(1);
+        let part = match part {
+            "" | "R" => SignalPart::Real,
+            "M" => SignalPart::Magnitude,
+            "DB" => SignalPart::Db,
+            "P" => SignalPart::Phase,
+            _ => return Err(invalid()),
+        };
+
+        let (pos, neg) = match quantity {
+            "V" => {
+                let mut nodes = args.split(',').map(|n| n.trim());
+                let node_signal = |node: Option<&str>| match node {
+                    None | Some("0") => None,
+                    Some(node) => Some(format!("v({})", node.to_ascii_lowercase())),
+                };
+                let pos = nodes.next().filter(|n| !n.is_empty()).ok_or_else(invalid)?;
+                let (pos, neg) = (node_signal(Some(pos)), node_signal(nodes.next()));
+                if nodes.next().is_some() {
+                    return Err(invalid());
+                }
+                (pos, neg)
+            }
+            "I" if !args.is_empty() && !args.contains(',') => {
+                (Some(format!("i({})", args.to_ascii_lowercase())), None)
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(Self {
+            label: s.to_ascii_lowercase(),
+            part,
+            pos,
+            neg,
+        })
+    }
+
+    /// Get whether the expression reads voltages or currents.
+    pub fn get_kind(&self) -> SignalKind {
+        if self.label.starts_with('i') {
+            SignalKind::Current
+        } else {
+            SignalKind::Voltage
+        }
+    }
+
+    /// Evaluate the expression at every point of `result`.
+    pub fn evaluate(&self, result: &AnalysisResult) -> Result<Vec<f64>, String> {
+        let get = |name: &Option<String>| -> Result<Option<Vec<f64>>, String> {
+            name.as_ref()
+                .map(|n| {
+                    result
+                        .get_signal(n)
+                        .ok_or_else(|| format!("Unknown signal: {}", n.to_ascii_uppercase()))
+                })
+                .transpose()
+        };
+        let (pos, neg) = (get(&self.pos)?, get(&self.neg)?);
+
+        Ok((0..result.point_num())
+            .map(|i| {
+                let value = pos.as_ref().map_or(0., |v| v[i]) - neg.as_ref().map_or(0., |v| v[i]);
+                match self.part {
+                    SignalPart::Real => value,
+                    SignalPart::Magnitude => value.abs(),
+                    SignalPart::Db => 20. * value.abs().log10(),
+                    SignalPart::Phase => {
+                        if value < 0. {
+                            180.
+                        } else {
+                            0.
+                        }
+                    }
+                }
+            })
+            .collect())
+    }
+}
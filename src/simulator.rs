@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use crate::analyze::{Analyzer, DcSweep, Mode};
+use crate::netlist::Netlist;
+use crate::options::Options;
+use crate::parser::{ParsedInfo, Parser};
+use crate::result::AnalysisResult;
+
+/// Runs analyses of a netlist in-process. Nothing is printed or plotted;
+/// every analysis returns its result.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    netlist: Netlist,
+    options: Options,
+}
+
+impl Simulator {
+    pub fn new(netlist: Netlist) -> Self {
+        Self {
+            netlist,
+            options: Options::default(),
+        }
+    }
+
+    /// Load a deck from `path`. Its `.OPTIONS` are kept; output and
+    /// analysis directives are ignored.
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_parsed(Parser::new(path.into()).parse()?))
+    }
+
+    /// Load a deck from a string, as [`Simulator::from_file`] does.
+    pub fn parse(deck: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_parsed(
+            Parser::new(PathBuf::from("<string>")).parse_str(deck)?,
+        ))
+    }
+
+    fn from_parsed(mut parsed_info: ParsedInfo) -> Self {
+        Self {
+            netlist: Netlist::from_parsed(&mut parsed_info),
+            options: parsed_info.options,
+        }
+    }
+
+    pub fn get_netlist(&self) -> &Netlist {
+        &self.netlist
+    }
+
+    pub fn get_options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+    }
+
+    /// Solve the DC operating point.
+    pub fn op(&self) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+        self.get_analyzer(Mode::DC).analyze(&[])
+    }
+
+    /// Sweep the independent source `source` from `start` to `stop`.
+    pub fn dc(
+        &self,
+        source: &str,
+        start: f64,
+        stop: f64,
+        step: f64,
+    ) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+        let mut analyzer = self.get_analyzer(Mode::DC);
+        analyzer.set_dc_sweep(DcSweep::new(source.to_string(), start, stop, step)?);
+        analyzer.analyze(&[])
+    }
+
+    /// Run a transient analysis from 0 to `final_time` seconds.
+    pub fn tran(&self, final_time: f64) -> Result<AnalysisResult, Box<dyn std::error::Error>> {
+        let mut analyzer = self.get_analyzer(Mode::Trans);
+        analyzer.set_final_time(final_time);
+        analyzer.analyze(&[])
+    }
+
+    fn get_analyzer(&self, mode: Mode) -> Analyzer {
+        let mut analyzer = Analyzer::new(self.netlist.clone());
+        analyzer.set_mode(mode);
+        analyzer.set_options(self.options.clone());
+        analyzer.set_quiet(true);
+        analyzer
+    }
+}
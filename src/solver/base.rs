@@ -1,10 +1,206 @@
 use crate::elements::TimeVaringNonLinearElement;
 use sprs::{CsMat, CsVec};
 
+use super::iterative::{IterativeMethod, IterativeSolver};
+use super::lu::LUSolver;
+use super::sparse_lu::SparseLUSolver;
+
+/// SPICE-style convergence tolerances.
+///
+/// A node voltage has converged when its update is within
+/// `reltol * |v| + vntol`, a branch current when its update is within
+/// `reltol * |i| + abstol`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceOptions {
+    pub reltol: f64,
+    pub vntol: f64,
+    pub abstol: f64,
+}
+
+impl Default for ConvergenceOptions {
+    fn default() -> Self {
+        Self {
+            reltol: 1e-3,
+            vntol: 1e-6,
+            abstol: 1e-12,
+        }
+    }
+}
+
+impl ConvergenceOptions {
+    pub fn voltage_tol(&self, prev: f64, next: f64) -> f64 {
+        self.reltol * prev.abs().max(next.abs()) + self.vntol
+    }
+
+    pub fn current_tol(&self, prev: f64, next: f64) -> f64 {
+        self.reltol * prev.abs().max(next.abs()) + self.abstol
+    }
+}
+
+/// Newton iteration counts accumulated over the solves of an analysis.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvergenceStats {
+    pub solves: usize,
+    pub iterations: usize,
+    pub max_iterations: usize,
+    /// Transient steps rejected and retried with a smaller step.
+    pub rejected_steps: usize,
+}
+
+impl ConvergenceStats {
+    pub fn record_solve(&mut self, iterations: usize) {
+        self.solves += 1;
+        self.iterations += iterations;
+        self.max_iterations = self.max_iterations.max(iterations);
+    }
+
+    pub fn merge(&mut self, other: &ConvergenceStats) {
+        self.solves += other.solves;
+        self.iterations += other.iterations;
+        self.max_iterations = self.max_iterations.max(other.max_iterations);
+        self.rejected_steps += other.rejected_steps;
+    }
+}
+
 pub trait Solver {
     fn solve_dc(
+        &self,
         mat: &CsMat<f64>,
         v: &CsVec<f64>,
         time_varing_non_linear_elements: &[TimeVaringNonLinearElement],
     ) -> Result<CsVec<f64>, Box<dyn std::error::Error>>;
 }
+
+/// Solver for the linear systems met in each Newton iteration.
+pub trait LinearSolver {
+    /// Factor `mat`, choosing a new pivot order.
+    fn factor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Factor `mat`, which has the same structure as the matrix last passed
+    /// to [`LinearSolver::factor`], reusing its pivot order where possible.
+    fn refactor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Solve `A x = v` with the last factored matrix.
+    fn solve(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>>;
+
+    /// Solve `A^T x = v` with the last factored matrix. Only the solver
+    /// tests use it so far.
+    #[cfg(test)]
+    fn solve_transpose(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>>;
+}
+
+/// Linear solver backend, selected by `.OPTIONS SOLVER=...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinearSolverKind {
+    /// LU with dense pivot search, fine for small circuits.
+    #[default]
+    LU,
+    /// Sparse direct LU.
+    SparseLU,
+    /// ILU(0)-preconditioned BiCGSTAB.
+    BiCgStab,
+    /// ILU(0)-preconditioned restarted GMRES.
+    Gmres,
+}
+
+impl LinearSolverKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "LU" | "DENSE" => Some(Self::LU),
+            "SPARSE" | "SPARSELU" => Some(Self::SparseLU),
+            "BICGSTAB" => Some(Self::BiCgStab),
+            "GMRES" => Some(Self::Gmres),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LU => "LU",
+            Self::SparseLU => "SPARSE",
+            Self::BiCgStab => "BICGSTAB",
+            Self::Gmres => "GMRES",
+        }
+    }
+
+    pub fn create(&self) -> Box<dyn LinearSolver> {
+        match self {
+            Self::LU => Box::<LUSolver>::default(),
+            Self::SparseLU => Box::<SparseLUSolver>::default(),
+            Self::BiCgStab => Box::new(IterativeSolver::new(IterativeMethod::BiCgStab)),
+            Self::Gmres => Box::new(IterativeSolver::new(IterativeMethod::Gmres)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sprs::TriMat;
+
+    /// MNA matrix of a small resistor ladder driven by a voltage source
+    /// (zero diagonal on the branch row) with a transconductance to make it
+    /// unsymmetric.
+    fn get_test_system() -> (CsMat<f64>, CsVec<f64>) {
+        let mut tri = TriMat::new((5, 5));
+        let stamps = [
+            (0, 0, 2.),
+            (0, 1, -1.),
+            (1, 0, -1.),
+            (1, 1, 2.5),
+            (1, 2, -1.),
+            (2, 1, -1.),
+            (2, 2, 1.5),
+            (2, 3, -0.5),
+            (3, 2, -0.5),
+            (3, 3, 1.5),
+            (3, 0, 0.3),
+            (0, 4, 1.),
+            (4, 0, 1.),
+        ];
+        for (row, col, val) in stamps {
+            tri.add_triplet(row, col, val);
+        }
+        let b = CsVec::new(5, vec![2, 4], vec![1e-3, 5.]);
+        (tri.to_csr(), b)
+    }
+
+    fn residual(mat: &CsMat<f64>, x: &CsVec<f64>, b: &CsVec<f64>) -> f64 {
+        let x = x.to_dense();
+        let b = b.to_dense();
+        let ax = mat * &x;
+        (0..b.len())
+            .map(|i| (ax[i] - b[i]).abs())
+            .fold(0., f64::max)
+    }
+
+    #[test]
+    fn test_linear_solvers() -> Result<(), Box<dyn std::error::Error>> {
+        let (mat, b) = get_test_system();
+        let mat_t = mat.transpose_view().to_csr();
+
+        for kind in [
+            LinearSolverKind::LU,
+            LinearSolverKind::SparseLU,
+            LinearSolverKind::BiCgStab,
+            LinearSolverKind::Gmres,
+        ] {
+            let mut solver = kind.create();
+            solver.factor(&mat)?;
+            let x = solver.solve(&b)?;
+            assert!(residual(&mat, &x, &b) < 1e-9, "{:?} solve", kind);
+
+            let x = solver.solve_transpose(&b)?;
+            assert!(
+                residual(&mat_t, &x, &b) < 1e-9,
+                "{:?} solve_transpose",
+                kind
+            );
+
+            solver.refactor(&mat)?;
+            let x = solver.solve(&b)?;
+            assert!(residual(&mat, &x, &b) < 1e-9, "{:?} refactor", kind);
+        }
+        Ok(())
+    }
+}
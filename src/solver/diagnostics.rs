@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use sprs::{CsMat, CsVec};
+
+use super::base::ConvergenceOptions;
+use crate::elements::base::Element;
+use crate::elements::TimeVaringNonLinearElement;
+use crate::netlist::Unknowns;
+use crate::topology::{find_tree_path, UnionFind};
+
+/// Number of iterates kept for the failure report.
+const HISTORY_LEN: usize = 5;
+/// Number of unknowns listed in the failure report.
+const REPORT_LEN: usize = 5;
+
+#[derive(Debug)]
+pub enum FailureKind {
+    NonConvergence { iterations: usize },
+    SingularMatrix { pivot: String },
+}
+
+#[derive(Debug)]
+pub struct UnknownUpdate {
+    pub index: usize,
+    pub name: String,
+    pub update: f64,
+    pub tol: f64,
+}
+
+#[derive(Debug)]
+pub struct DeviceFlips {
+    pub name: String,
+    pub flips: usize,
+    pub last_region: &'static str,
+}
+
+/// Structured report of a failed Newton solve.
+#[derive(Debug)]
+pub struct ConvergenceReport {
+    pub kind: FailureKind,
+    /// Unknowns whose last update exceeded their tolerance, worst first.
+    pub worst_updates: Vec<UnknownUpdate>,
+    /// Nonlinear devices that changed operating region, most changes first.
+    pub flipping_devices: Vec<DeviceFlips>,
+    /// Last iterates of the worst unknowns, oldest first.
+    pub last_iterates: Vec<(String, Vec<f64>)>,
+    /// Structural causes found for a singular matrix.
+    pub singular_structure: Vec<String>,
+}
+
+impl fmt::Display for ConvergenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            FailureKind::NonConvergence { iterations } => write!(
+                f,
+                "Newton method failed to converge after {} iterations",
+                iterations
+            )?,
+            FailureKind::SingularMatrix { pivot } => {
+                write!(f, "Matrix is singular (zero pivot at {})", pivot)?
+            }
+        }
+
+        if !self.singular_structure.is_empty() {
+            write!(f, "\n  Singular structure:")?;
+            for finding in &self.singular_structure {
+                write!(f, "\n    {}", finding)?;
+            }
+        }
+
+        if !self.worst_updates.is_empty() {
+            write!(f, "\n  Largest non-converged updates:")?;
+            for u in &self.worst_updates {
+                write!(f, "\n    {}: {:.3e} (tol {:.3e})", u.name, u.update, u.tol)?;
+            }
+        }
+
+        if !self.flipping_devices.is_empty() {
+            write!(f, "\n  Devices changing operating region:")?;
+            for d in &self.flipping_devices {
+                write!(
+                    f,
+                    "\n    {}: {} changes, last {}",
+                    d.name, d.flips, d.last_region
+                )?;
+            }
+        }
+
+        if !self.last_iterates.is_empty() {
+            write!(f, "\n  Last iterates:")?;
+            for (name, values) in &self.last_iterates {
+                let values = values
+                    .iter()
+                    .map(|v| format!("{:.6e}", v))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "\n    {}: {}", name, values)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConvergenceReport {}
+
+/// Records what the Newton iteration did so a failure can be explained.
+pub struct IterationHistory {
+    iterates: VecDeque<Vec<f64>>,
+    regions: Vec<Option<&'static str>>,
+    flips: Vec<usize>,
+}
+
+impl IterationHistory {
+    pub fn new(device_num: usize) -> Self {
+        Self {
+            iterates: VecDeque::with_capacity(HISTORY_LEN),
+            regions: vec![None; device_num],
+            flips: vec![0; device_num],
+        }
+    }
+
+    pub fn record(&mut self, x: &CsVec<f64>, elements: &[TimeVaringNonLinearElement]) {
+        if self.iterates.len() == HISTORY_LEN {
+            self.iterates.pop_front();
+        }
+        self.iterates.push_back(x.to_dense().to_vec());
+
+        for (i, element) in elements.iter().enumerate() {
+            let region = element.get_operating_region(x);
+            if matches!(self.regions[i], Some(prev) if prev != region) {
+                self.flips[i] += 1;
+            }
+            self.regions[i] = Some(region);
+        }
+    }
+
+    pub fn report(
+        &self,
+        kind: FailureKind,
+        unknowns: &Unknowns,
+        options: &ConvergenceOptions,
+        elements: &[TimeVaringNonLinearElement],
+    ) -> ConvergenceReport {
+        let worst_updates = self.get_worst_updates(unknowns, options);
+
+        let mut flipping_devices = elements
+            .iter()
+            .zip(self.flips.iter().zip(self.regions.iter()))
+            .filter(|(_, (flips, _))| **flips > 0)
+            .map(|(element, (flips, region))| DeviceFlips {
+                name: element.get_name().to_string(),
+                flips: *flips,
+                last_region: region.unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        flipping_devices.sort_by_key(|d| std::cmp::Reverse(d.flips));
+        flipping_devices.truncate(REPORT_LEN);
+
+        let last_iterates = worst_updates
+            .iter()
+            .map(|u| {
+                let values = self.iterates.iter().map(|x| x[u.index]).collect();
+                (u.name.clone(), values)
+            })
+            .collect();
+
+        ConvergenceReport {
+            kind,
+            worst_updates,
+            flipping_devices,
+            last_iterates,
+            singular_structure: Vec::new(),
+        }
+    }
+
+    fn get_worst_updates(
+        &self,
+        unknowns: &Unknowns,
+        options: &ConvergenceOptions,
+    ) -> Vec<UnknownUpdate> {
+        let len = self.iterates.len();
+        if len < 2 {
+            return Vec::new();
+        }
+        let (prev, next) = (&self.iterates[len - 2], &self.iterates[len - 1]);
+
+        let mut updates = (0..next.len())
+            .map(|i| {
+                let tol = if unknowns.is_voltage(i) {
+                    options.voltage_tol(prev[i], next[i])
+                } else {
+                    options.current_tol(prev[i], next[i])
+                };
+                (i, (next[i] - prev[i]).abs(), tol)
+            })
+            .filter(|(_, update, tol)| update > tol)
+            .collect::<Vec<_>>();
+        updates.sort_by(|(_, u1, t1), (_, u2, t2)| (u2 / t2).total_cmp(&(u1 / t1)));
+
+        updates
+            .into_iter()
+            .take(REPORT_LEN)
+            .map(|(i, update, tol)| UnknownUpdate {
+                index: i,
+                name: unknowns.get_name(i),
+                update,
+                tol,
+            })
+            .collect()
+    }
+}
+
+/// Look at the structure of a singular MNA matrix and name the likely causes:
+/// unknowns without any stamp, node groups without a DC path to ground, and
+/// loops made only of voltage sources and inductors.
+pub fn find_singular_structure(mat: &CsMat<f64>, unknowns: &Unknowns) -> Vec<String> {
+    let mat = mat.to_csr();
+    let size = mat.rows();
+    let voltage_num = unknowns.voltage_num;
+    let ground = voltage_num;
+    let mut findings = Vec::new();
+
+    let mut row_nnz = vec![0; size];
+    let mut col_nnz = vec![0; size];
+    for (val, (row, col)) in mat.iter() {
+        if *val != 0. {
+            row_nnz[row] += 1;
+            col_nnz[col] += 1;
+        }
+    }
+    let empty = (0..size)
+        .filter(|i| row_nnz[*i] == 0 || col_nnz[*i] == 0)
+        .collect::<Vec<_>>();
+    for i in &empty {
+        findings.push(format!("{} has no connections", unknowns.get_name(*i)));
+    }
+
+    // Branch rows hold +1/-1 at the columns of the nodes they connect.
+    let get_branch_nodes = |row: usize| {
+        let nodes = mat
+            .outer_view(row)
+            .unwrap()
+            .iter()
+            .filter(|(col, val)| *col < voltage_num && **val != 0.)
+            .map(|(col, _)| col)
+            .collect::<Vec<_>>();
+        match nodes.as_slice() {
+            [a] => Some((*a, ground)),
+            [a, b] => Some((*a, *b)),
+            _ => None,
+        }
+    };
+
+    // Loops of voltage sources and inductors.
+    let mut uf = UnionFind::new(voltage_num + 1);
+    let mut tree: Vec<Vec<(usize, usize)>> = vec![Vec::new(); voltage_num + 1];
+    for branch in voltage_num..size {
+        let Some((a, b)) = get_branch_nodes(branch) else {
+            continue;
+        };
+        if uf.union(a, b) {
+            tree[a].push((b, branch));
+            tree[b].push((a, branch));
+        } else {
+            let mut names = find_tree_path(&tree, a, b)
+                .into_iter()
+                .map(|i| unknowns.branch_names[i - voltage_num].clone())
+                .collect::<Vec<_>>();
+            names.push(unknowns.branch_names[branch - voltage_num].clone());
+            findings.push(format!(
+                "{} form a loop of voltage sources/inductors",
+                names.join(", ")
+            ));
+        }
+    }
+
+    // Node groups without a DC path to ground.
+    let mut uf = UnionFind::new(voltage_num + 1);
+    for row in 0..voltage_num {
+        let mut row_sum = 0.;
+        let mut row_max: f64 = 0.;
+        for (col, val) in mat.outer_view(row).unwrap().iter() {
+            if col < voltage_num {
+                row_sum += val;
+                row_max = row_max.max(val.abs());
+                if *val != 0. && col != row {
+                    uf.union(row, col);
+                }
+            }
+        }
+        if row_sum.abs() > 1e-12 * row_max {
+            uf.union(row, ground);
+        }
+    }
+    for branch in voltage_num..size {
+        if let Some((a, b)) = get_branch_nodes(branch) {
+            uf.union(a, b);
+        }
+    }
+    let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+    for node in (0..voltage_num).filter(|i| !empty.contains(i)) {
+        let root = uf.find(node);
+        if root == uf.find(ground) {
+            continue;
+        }
+        match groups.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, nodes)) => nodes.push(node),
+            None => groups.push((root, vec![node])),
+        }
+    }
+    for (_, nodes) in groups {
+        let names = nodes
+            .iter()
+            .map(|i| unknowns.get_name(*i))
+            .collect::<Vec<_>>();
+        let verb = if names.len() == 1 { "has" } else { "have" };
+        findings.push(format!(
+            "{} {} no DC path to ground",
+            names.join(", "),
+            verb
+        ));
+    }
+
+    findings
+}
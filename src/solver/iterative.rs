@@ -0,0 +1,497 @@
+use sprs::{CsMat, CsVec};
+
+use super::base::LinearSolver;
+use crate::matrix::decomp::SingularMatrixError;
+
+/// Relative residual at which an iterative solve is considered converged.
+const ITER_TOL: f64 = 1e-12;
+/// Minimum number of iterations allowed before giving up.
+const MIN_MAX_ITER: usize = 200;
+/// Krylov subspace dimension before GMRES restarts.
+const GMRES_RESTART: usize = 30;
+/// Replacement for zero pivots met during the incomplete factorization.
+const ILU_PIVOT_FLOOR: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterativeMethod {
+    BiCgStab,
+    Gmres,
+}
+
+/// ILU(0) factors stored in the pattern of the matrix they were computed
+/// from. The strictly lower part holds L (unit diagonal implied), the rest U.
+struct Ilu0 {
+    mat: CsMat<f64>,
+    diag: Vec<usize>,
+}
+
+impl Ilu0 {
+    fn new(mat: &CsMat<f64>) -> Self {
+        let n = mat.rows();
+        let mat = mat.to_csr();
+        let indptr = mat.indptr().raw_storage().to_vec();
+        let indices = mat.indices().to_vec();
+        let mut data = mat.data().to_vec();
+
+        let mut diag = vec![usize::MAX; n];
+        let mut pos = vec![usize::MAX; n];
+
+        for i in 0..n {
+            let row = indptr[i]..indptr[i + 1];
+            for p in row.clone() {
+                pos[indices[p]] = p;
+                if indices[p] == i {
+                    diag[i] = p;
+                }
+            }
+
+            for p in row.clone() {
+                let k = indices[p];
+                if k >= i {
+                    break;
+                }
+                data[p] /= data[diag[k]];
+                let l_ik = data[p];
+                for q in (diag[k] + 1)..indptr[k + 1] {
+                    let j = indices[q];
+                    if pos[j] != usize::MAX {
+                        data[pos[j]] -= l_ik * data[q];
+                    }
+                }
+            }
+
+            if diag[i] == usize::MAX {
+                // Keep the structure; the matching guarantees a diagonal, so
+                // this only happens for structurally singular matrices.
+                diag[i] = row.start;
+            } else if data[diag[i]].abs() < ILU_PIVOT_FLOOR {
+                data[diag[i]] = ILU_PIVOT_FLOOR.copysign(data[diag[i]]);
+            }
+
+            for p in row {
+                pos[indices[p]] = usize::MAX;
+            }
+        }
+
+        let mat = CsMat::new((n, n), indptr, indices, data);
+        Self { mat, diag }
+    }
+
+    /// Apply `(L U)^-1`.
+    fn apply(&self, r: &[f64]) -> Vec<f64> {
+        let n = r.len();
+        let (indptr, indices, data) = (self.mat.indptr(), self.mat.indices(), self.mat.data());
+        let indptr = indptr.raw_storage();
+
+        let mut y = r.to_vec();
+        for i in 0..n {
+            for p in indptr[i]..self.diag[i] {
+                y[i] -= data[p] * y[indices[p]];
+            }
+        }
+        for i in (0..n).rev() {
+            for p in (self.diag[i] + 1)..indptr[i + 1] {
+                y[i] -= data[p] * y[indices[p]];
+            }
+            y[i] /= data[self.diag[i]];
+        }
+        y
+    }
+
+    /// Apply `(L U)^-T = L^-T U^-T`.
+    #[cfg(test)]
+    fn apply_transpose(&self, r: &[f64]) -> Vec<f64> {
+        let n = r.len();
+        let (indptr, indices, data) = (self.mat.indptr(), self.mat.indices(), self.mat.data());
+        let indptr = indptr.raw_storage();
+
+        let mut y = r.to_vec();
+        for i in 0..n {
+            y[i] /= data[self.diag[i]];
+            for p in (self.diag[i] + 1)..indptr[i + 1] {
+                y[indices[p]] -= data[p] * y[i];
+            }
+        }
+        for i in (0..n).rev() {
+            for p in indptr[i]..self.diag[i] {
+                y[indices[p]] -= data[p] * y[i];
+            }
+        }
+        y
+    }
+}
+
+/// Krylov solver (BiCGSTAB or restarted GMRES) with ILU(0) preconditioning.
+///
+/// MNA matrices have zero diagonals on the rows of voltage sources, so rows
+/// are first permuted by a maximum matching to put a nonzero on every
+/// diagonal before the incomplete factorization.
+pub struct IterativeSolver {
+    method: IterativeMethod,
+    /// Row `i` of the permuted matrix is row `row_order[i]` of the original.
+    row_order: Vec<usize>,
+    permuted: Option<CsMat<f64>>,
+    ilu: Option<Ilu0>,
+}
+
+impl IterativeSolver {
+    pub fn new(method: IterativeMethod) -> Self {
+        Self {
+            method,
+            row_order: Vec::new(),
+            permuted: None,
+            ilu: None,
+        }
+    }
+
+    /// Match every column to a distinct row with a nonzero entry, trying
+    /// large entries first.
+    fn get_row_order(mat: &CsMat<f64>) -> Result<Vec<usize>, SingularMatrixError> {
+        let n = mat.rows();
+        let csc = mat.to_csc();
+        let columns = (0..n)
+            .map(|col| {
+                let mut rows = csc
+                    .outer_view(col)
+                    .map(|c| {
+                        c.iter()
+                            .filter(|(_, v)| **v != 0.)
+                            .map(|(r, v)| (r, v.abs()))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+                rows.into_iter().map(|(r, _)| r).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        fn augment(
+            col: usize,
+            columns: &[Vec<usize>],
+            row_match: &mut [Option<usize>],
+            visited: &mut [bool],
+        ) -> bool {
+            for row in &columns[col] {
+                if visited[*row] {
+                    continue;
+                }
+                visited[*row] = true;
+                let free = match row_match[*row] {
+                    None => true,
+                    Some(other) => augment(other, columns, row_match, visited),
+                };
+                if free {
+                    row_match[*row] = Some(col);
+                    return true;
+                }
+            }
+            false
+        }
+
+        let mut row_match: Vec<Option<usize>> = vec![None; n];
+        let mut visited = vec![false; n];
+        for col in 0..n {
+            // Cheap pass: take the largest free entry if there is one.
+            if let Some(row) = columns[col].iter().find(|r| row_match[**r].is_none()) {
+                row_match[*row] = Some(col);
+                continue;
+            }
+            visited.iter_mut().for_each(|v| *v = false);
+            if !augment(col, &columns, &mut row_match, &mut visited) {
+                return Err(SingularMatrixError { pivot: col });
+            }
+        }
+
+        let mut row_order = vec![0; n];
+        for (row, col) in row_match.iter().enumerate() {
+            row_order[col.unwrap()] = row;
+        }
+        Ok(row_order)
+    }
+
+    fn get_permuted(mat: &CsMat<f64>, row_order: &[usize]) -> CsMat<f64> {
+        let csr = mat.to_csr();
+        let mut indptr = vec![0];
+        let mut indices = Vec::with_capacity(csr.nnz());
+        let mut data = Vec::with_capacity(csr.nnz());
+        for row in row_order {
+            if let Some(r) = csr.outer_view(*row) {
+                for (col, val) in r.iter() {
+                    indices.push(col);
+                    data.push(*val);
+                }
+            }
+            indptr.push(indices.len());
+        }
+        let n = mat.rows();
+        CsMat::new((n, n), indptr, indices, data)
+    }
+
+    fn get_factors(&self) -> Result<(&CsMat<f64>, &Ilu0), Box<dyn std::error::Error>> {
+        match (&self.permuted, &self.ilu) {
+            (Some(permuted), Some(ilu)) => Ok((permuted, ilu)),
+            _ => Err("Matrix has not been factored".into()),
+        }
+    }
+
+    fn run(
+        &self,
+        matvec: impl Fn(&[f64]) -> Vec<f64>,
+        precond: impl Fn(&[f64]) -> Vec<f64>,
+        b: &[f64],
+    ) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let max_iter = MIN_MAX_ITER.max(2 * b.len());
+        match self.method {
+            IterativeMethod::BiCgStab => bicgstab(matvec, precond, b, max_iter),
+            IterativeMethod::Gmres => gmres(matvec, precond, b, max_iter),
+        }
+    }
+}
+
+impl LinearSolver for IterativeSolver {
+    fn factor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        self.row_order = Self::get_row_order(mat)?;
+        self.refactor(mat)
+    }
+
+    fn refactor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.row_order.len() != mat.rows() {
+            return self.factor(mat);
+        }
+        let permuted = Self::get_permuted(mat, &self.row_order);
+        self.ilu = Some(Ilu0::new(&permuted));
+        self.permuted = Some(permuted);
+        Ok(())
+    }
+
+    fn solve(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        let (permuted, ilu) = self.get_factors()?;
+        let n = permuted.rows();
+        let b = self
+            .row_order
+            .iter()
+            .map(|row| v.get(*row).copied().unwrap_or(0.))
+            .collect::<Vec<_>>();
+
+        let x = self.run(|x| mul(permuted, x), |r| ilu.apply(r), &b)?;
+        Ok(CsVec::new(n, (0..n).collect(), x))
+    }
+
+    #[cfg(test)]
+    fn solve_transpose(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        // A = P^T B, so A^T x = c is B^T (P x) = c.
+        let (permuted, ilu) = self.get_factors()?;
+        let n = permuted.rows();
+        let c = v.to_dense().to_vec();
+
+        let w = self.run(
+            |x| mul_transpose(permuted, x),
+            |r| ilu.apply_transpose(r),
+            &c,
+        )?;
+
+        let mut x = vec![0.; n];
+        for (i, row) in self.row_order.iter().enumerate() {
+            x[*row] = w[i];
+        }
+        Ok(CsVec::new(n, (0..n).collect(), x))
+    }
+}
+
+fn mul(mat: &CsMat<f64>, x: &[f64]) -> Vec<f64> {
+    (0..mat.rows())
+        .map(|i| {
+            mat.outer_view(i)
+                .map(|row| row.iter().map(|(j, a)| a * x[j]).sum())
+                .unwrap_or(0.)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn mul_transpose(mat: &CsMat<f64>, x: &[f64]) -> Vec<f64> {
+    let mut y = vec![0.; mat.cols()];
+    for (i, xi) in x.iter().enumerate() {
+        if let Some(row) = mat.outer_view(i) {
+            for (j, a) in row.iter() {
+                y[j] += a * xi;
+            }
+        }
+    }
+    y
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn not_converged(iterations: usize, residual: f64) -> Box<dyn std::error::Error> {
+    format!(
+        "Iterative solver failed to converge after {} iterations (relative residual {:.3e})",
+        iterations, residual
+    )
+    .into()
+}
+
+/// Right-preconditioned BiCGSTAB.
+fn bicgstab(
+    matvec: impl Fn(&[f64]) -> Vec<f64>,
+    precond: impl Fn(&[f64]) -> Vec<f64>,
+    b: &[f64],
+    max_iter: usize,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let n = b.len();
+    let b_norm = norm(b);
+    let mut x = vec![0.; n];
+    if b_norm == 0. {
+        return Ok(x);
+    }
+
+    let mut r = b.to_vec();
+    let mut r_hat = r.clone();
+    let (mut rho, mut alpha, mut omega) = (1., 1., 1.);
+    let mut v = vec![0.; n];
+    let mut p = vec![0.; n];
+
+    for _ in 0..max_iter {
+        let mut rho_next = dot(&r_hat, &r);
+        if rho_next.abs() <= f64::EPSILON * norm(&r_hat) * norm(&r) {
+            // The shadow residual became orthogonal to the residual, which
+            // happens easily with the unit rows of voltage sources. Restart
+            // with the current residual as shadow.
+            r_hat = r.clone();
+            rho_next = dot(&r_hat, &r);
+            (rho, alpha, omega) = (1., 1., 1.);
+            v.iter_mut().for_each(|x| *x = 0.);
+            p.iter_mut().for_each(|x| *x = 0.);
+        }
+        let beta = (rho_next / rho) * (alpha / omega);
+        rho = rho_next;
+        for i in 0..n {
+            p[i] = r[i] + beta * (p[i] - omega * v[i]);
+        }
+
+        let y = precond(&p);
+        v = matvec(&y);
+        alpha = rho / dot(&r_hat, &v);
+        let s = (0..n).map(|i| r[i] - alpha * v[i]).collect::<Vec<_>>();
+        if norm(&s) <= ITER_TOL * b_norm {
+            for i in 0..n {
+                x[i] += alpha * y[i];
+            }
+            return Ok(x);
+        }
+
+        let z = precond(&s);
+        let t = matvec(&z);
+        omega = dot(&t, &s) / dot(&t, &t);
+        for i in 0..n {
+            x[i] += alpha * y[i] + omega * z[i];
+            r[i] = s[i] - omega * t[i];
+        }
+        if norm(&r) <= ITER_TOL * b_norm {
+            return Ok(x);
+        }
+        if omega == 0. {
+            break;
+        }
+    }
+
+    Err(not_converged(max_iter, norm(&r) / b_norm))
+}
+
+/// Right-preconditioned restarted GMRES.
+fn gmres(
+    matvec: impl Fn(&[f64]) -> Vec<f64>,
+    precond: impl Fn(&[f64]) -> Vec<f64>,
+    b: &[f64],
+    max_iter: usize,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let n = b.len();
+    let b_norm = norm(b);
+    let mut x = vec![0.; n];
+    if b_norm == 0. {
+        return Ok(x);
+    }
+
+    let m = GMRES_RESTART.min(n);
+    let mut iterations = 0;
+    let mut residual = b_norm;
+
+    while iterations < max_iter {
+        let ax = matvec(&x);
+        let r = (0..n).map(|i| b[i] - ax[i]).collect::<Vec<_>>();
+        let beta = norm(&r);
+        residual = beta;
+        if beta <= ITER_TOL * b_norm {
+            return Ok(x);
+        }
+
+        let mut basis = vec![r.iter().map(|v| v / beta).collect::<Vec<_>>()];
+        let mut h = vec![vec![0.; m]; m + 1];
+        let (mut cs, mut sn) = (vec![0.; m], vec![0.; m]);
+        let mut g = vec![0.; m + 1];
+        g[0] = beta;
+
+        let mut k = 0;
+        while k < m && iterations < max_iter {
+            iterations += 1;
+            let mut w = matvec(&precond(&basis[k]));
+            for (j, vj) in basis.iter().enumerate() {
+                h[j][k] = dot(&w, vj);
+                for i in 0..n {
+                    w[i] -= h[j][k] * vj[i];
+                }
+            }
+            h[k + 1][k] = norm(&w);
+
+            for j in 0..k {
+                let tmp = cs[j] * h[j][k] + sn[j] * h[j + 1][k];
+                h[j + 1][k] = -sn[j] * h[j][k] + cs[j] * h[j + 1][k];
+                h[j][k] = tmp;
+            }
+            let denom = h[k][k].hypot(h[k + 1][k]);
+            cs[k] = h[k][k] / denom;
+            sn[k] = h[k + 1][k] / denom;
+            h[k][k] = denom;
+            g[k + 1] = -sn[k] * g[k];
+            g[k] *= cs[k];
+
+            let w_norm = h[k + 1][k];
+            h[k + 1][k] = 0.;
+            k += 1;
+            residual = g[k].abs();
+            if residual <= ITER_TOL * b_norm || w_norm == 0. {
+                break;
+            }
+            basis.push(w.iter().map(|v| v / w_norm).collect());
+        }
+
+        // Solve the upper triangular least squares system and update x.
+        let mut y = vec![0.; k];
+        for i in (0..k).rev() {
+            let sum = ((i + 1)..k).map(|j| h[i][j] * y[j]).sum::<f64>();
+            y[i] = (g[i] - sum) / h[i][i];
+        }
+        let mut update = vec![0.; n];
+        for (j, yj) in y.iter().enumerate() {
+            for i in 0..n {
+                update[i] += yj * basis[j][i];
+            }
+        }
+        let update = precond(&update);
+        for i in 0..n {
+            x[i] += update[i];
+        }
+
+        if residual <= ITER_TOL * b_norm {
+            return Ok(x);
+        }
+    }
+
+    Err(not_converged(iterations, residual / b_norm))
+}
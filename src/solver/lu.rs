@@ -0,0 +1,136 @@
+use crate::matrix::decomp::LUDecomp;
+use log::error;
+use sprs::{CsMat, CsVec};
+
+use super::base::LinearSolver;
+
+fn get_or_default<T>(x: Option<&T>) -> T
+where
+    T: Default + Clone,
+{
+    x.cloned().unwrap_or_default()
+}
+
+type Factors = (CsMat<f64>, CsMat<f64>);
+
+/// LU solver built on [`LUDecomp`], with row pivoting chosen once at
+/// [`LinearSolver::factor`] and reused by [`LinearSolver::refactor`].
+#[derive(Default)]
+pub struct LUSolver {
+    reorder_map: Vec<usize>,
+    factors: Option<Factors>,
+}
+
+impl LUSolver {
+    fn get_factors(&self) -> Result<&Factors, Box<dyn std::error::Error>> {
+        self.factors
+            .as_ref()
+            .ok_or_else(|| "Matrix has not been factored".into())
+    }
+}
+
+impl LinearSolver for LUSolver {
+    fn factor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        self.reorder_map = mat.get_reorder_map();
+        self.refactor(mat)
+    }
+
+    fn refactor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        assert!(mat.rows() == mat.cols());
+        self.factors = None;
+
+        let (l, u) = mat.lu_decomp(Some(&self.reorder_map)).map_err(|e| {
+            error!("LU decomposition failed: {}", e);
+            e
+        })?;
+
+        self.factors = Some((l, u));
+        Ok(())
+    }
+
+    fn solve(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        let (l, u) = self.get_factors()?;
+        let size = l.rows();
+
+        let b_star = {
+            let mut result: CsVec<f64> = CsVec::empty(size);
+            for (row, mapped) in self.reorder_map.iter().enumerate().take(size) {
+                let prev_sum = (0..row)
+                    .map(|i| {
+                        let l_val = get_or_default(l.get(row, i));
+                        let result_val = get_or_default(result.get(i));
+                        l_val * result_val
+                    })
+                    .reduce(|acc, x| acc + x);
+
+                let prev_sum = prev_sum.unwrap_or(0.0);
+
+                result.append(
+                    row,
+                    (get_or_default(v.get(*mapped)) - prev_sum) / get_or_default(l.get(row, row)),
+                );
+            }
+            result
+        };
+
+        let x = {
+            let mut vals = vec![0.0; size];
+
+            for row in (0..size).rev() {
+                let prev_sum = (row + 1..size)
+                    .map(|i| {
+                        let u_val = get_or_default(u.get(row, i));
+                        let result_val = vals[i];
+                        u_val * result_val
+                    })
+                    .reduce(|acc, x| acc + x);
+
+                let prev_sum = prev_sum.unwrap_or(0.);
+
+                vals[row] =
+                    (get_or_default(b_star.get(row)) - prev_sum) / get_or_default(u.get(row, row));
+            }
+
+            let result: CsVec<f64> = CsVec::new(
+                size,
+                (0..size).collect::<Vec<usize>>(),
+                (0..size).map(|i| vals[i]).collect::<Vec<f64>>(),
+            );
+
+            result
+        };
+
+        Ok(x)
+    }
+
+    #[cfg(test)]
+    fn solve_transpose(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        // P A = L U, so A^T = U^T L^T P. Solve U^T z = v, then L^T w = z and
+        // scatter w back through the row permutation.
+        let (l, u) = self.get_factors()?;
+        let size = l.rows();
+
+        let mut z = vec![0.0; size];
+        for row in 0..size {
+            let prev_sum = (0..row)
+                .map(|i| get_or_default(u.get(i, row)) * z[i])
+                .fold(0., |acc, x| acc + x);
+            z[row] = (get_or_default(v.get(row)) - prev_sum) / get_or_default(u.get(row, row));
+        }
+
+        let mut w = vec![0.0; size];
+        for row in (0..size).rev() {
+            let prev_sum = (row + 1..size)
+                .map(|i| get_or_default(l.get(i, row)) * w[i])
+                .fold(0., |acc, x| acc + x);
+            w[row] = (z[row] - prev_sum) / get_or_default(l.get(row, row));
+        }
+
+        let mut vals = vec![0.0; size];
+        for (row, mapped) in self.reorder_map.iter().enumerate() {
+            vals[*mapped] = w[row];
+        }
+
+        Ok(CsVec::new(size, (0..size).collect(), vals))
+    }
+}
@@ -1,2 +1,6 @@
 pub mod base;
+pub mod diagnostics;
+pub mod iterative;
+pub mod lu;
 pub mod newton;
+pub mod sparse_lu;
@@ -1,13 +1,22 @@
 use crate::{
-    elements::base::MatrixDcUpdatable, elements::TimeVaringNonLinearElement,
-    matrix::decomp::LUDecomp,
+    elements::base::{ConvergenceCheckable, MatrixDcUpdatable},
+    elements::TimeVaringNonLinearElement,
+    matrix::decomp::SingularMatrixError,
+    netlist::Unknowns,
 };
-use log::error;
+use log::debug;
 use sprs::{CsMat, CsVec};
+use std::cell::Cell;
 
-use super::base::Solver;
+use super::base::{ConvergenceOptions, ConvergenceStats, LinearSolverKind, Solver};
+use super::diagnostics::{find_singular_structure, FailureKind, IterationHistory};
 
-pub struct NewtonSolver {}
+pub struct NewtonSolver {
+    options: ConvergenceOptions,
+    linear_solver: LinearSolverKind,
+    unknowns: Unknowns,
+    stats: Cell<ConvergenceStats>,
+}
 
 const MAX_ITER: usize = 100;
 
@@ -15,91 +24,60 @@ fn get_or_default<T>(x: Option<&T>) -> T
 where
     T: Default + Clone,
 {
-    if let Some(val) = x {
-        val.clone()
-    } else {
-        Default::default()
-    }
+    x.cloned().unwrap_or_default()
 }
 
-struct LUSolver {}
-
-impl LUSolver {
-    fn solve(
-        mat: &CsMat<f64>,
-        v: &CsVec<f64>,
-        reorder_map: &mut Option<Vec<usize>>,
-    ) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
-        let reorder_map = if let Some(map) = reorder_map {
-            map
-        } else {
-            let map = mat.get_reorder_map();
-            *reorder_map = Some(map.clone());
-            reorder_map.as_mut().unwrap()
-        };
-
-        let (l, u) = mat.lu_decomp(Some(reorder_map)).map_err(|e| {
-            error!("LU decomposition failed: {}", e);
-            e
-        })?;
-
-        assert!(mat.rows() == mat.cols());
-        let size = mat.rows();
-
-        let b_star = {
-            let mut result: CsVec<f64> = CsVec::empty(size);
-            for (row, mapped) in reorder_map.iter().enumerate().take(size) {
-                let prev_sum = (0..row)
-                    .map(|i| {
-                        let l_val = get_or_default(l.get(row, i));
-                        let result_val = get_or_default(result.get(i));
-                        l_val * result_val
-                    })
-                    .reduce(|acc, x| acc + x);
-
-                let prev_sum = if let Some(p) = prev_sum { p } else { 0.0 };
-
-                result.append(
-                    row,
-                    (get_or_default(v.get(*mapped)) - prev_sum) / get_or_default(l.get(row, row)),
-                );
-            }
-            result
-        };
-
-        let x = {
-            let mut vals = vec![0.0; size];
-
-            for row in (0..size).rev() {
-                let prev_sum = (row + 1..size)
-                    .map(|i| {
-                        let u_val = get_or_default(u.get(row, i));
-                        let result_val = vals[i];
-                        u_val * result_val
-                    })
-                    .reduce(|acc, x| acc + x);
-
-                let prev_sum = if let Some(p) = prev_sum { p } else { 0. };
-
-                vals[row] =
-                    (get_or_default(b_star.get(row)) - prev_sum) / get_or_default(u.get(row, row));
-            }
+impl NewtonSolver {
+    pub fn new(
+        options: ConvergenceOptions,
+        linear_solver: LinearSolverKind,
+        unknowns: Unknowns,
+    ) -> Self {
+        Self {
+            options,
+            linear_solver,
+            unknowns,
+            stats: Cell::new(ConvergenceStats::default()),
+        }
+    }
 
-            let result: CsVec<f64> = CsVec::new(
-                size,
-                (0..size).collect::<Vec<usize>>(),
-                (0..size).map(|i| vals[i]).collect::<Vec<f64>>(),
-            );
+    /// Iteration counts of the successful solves so far.
+    pub fn get_stats(&self) -> ConvergenceStats {
+        self.stats.get()
+    }
 
-            result
-        };
+    /// Check every unknown against its own tolerance, node voltages against
+    /// `VNTOL` and branch currents against `ABSTOL`.
+    fn is_update_converged(&self, x: &CsVec<f64>, x_next: &CsVec<f64>) -> bool {
+        (0..x_next.dim()).all(|i| {
+            let prev = get_or_default(x.get(i));
+            let next = get_or_default(x_next.get(i));
+            let tol = if self.unknowns.is_voltage(i) {
+                self.options.voltage_tol(prev, next)
+            } else {
+                self.options.current_tol(prev, next)
+            };
+            (next - prev).abs() <= tol
+        })
+    }
 
-        Ok(x)
+    /// Check that the currents of the nonlinear devices evaluated at the new
+    /// solution agree with their linearization around the previous one.
+    fn is_residual_converged(
+        &self,
+        x: &CsVec<f64>,
+        x_next: &CsVec<f64>,
+        time_varing_non_linear_elements: &[TimeVaringNonLinearElement],
+    ) -> bool {
+        time_varing_non_linear_elements
+            .iter()
+            .all(|element| element.is_converged(x, x_next, &self.options))
     }
 }
 
 impl Solver for NewtonSolver {
     fn solve_dc(
+        &self,
         mat: &CsMat<f64>,
         v: &CsVec<f64>,
         time_varing_non_linear_elements: &[TimeVaringNonLinearElement],
@@ -112,7 +90,8 @@ impl Solver for NewtonSolver {
 
         let mut iter_times = 0;
 
-        let mut reorder_map = None;
+        let mut linear_solver = self.linear_solver.create();
+        let mut history = IterationHistory::new(time_varing_non_linear_elements.len());
 
         loop {
             iter_times += 1;
@@ -123,29 +102,59 @@ impl Solver for NewtonSolver {
                 time_varing_non_linear_element.update_matrix_dc(&mut mat_a, &mut vec_b, &x);
             }
 
-            let x_next = LUSolver::solve(&mat_a, &vec_b, &mut reorder_map)?;
-
-            let l2_diff = {
-                let mut diff_acc = 0.;
-                for i in 0..node_num_with_out_ground {
-                    let diff = get_or_default(x.get(i)) - get_or_default(x_next.get(i));
-                    diff_acc += diff * diff;
+            let factored = if iter_times == 1 {
+                linear_solver.factor(&mat_a)
+            } else {
+                linear_solver.refactor(&mat_a)
+            };
+            let x_next = match factored.and_then(|_| linear_solver.solve(&vec_b)) {
+                Ok(x_next) => x_next,
+                Err(e) => {
+                    let Some(singular) = e.downcast_ref::<SingularMatrixError>() else {
+                        return Err(e);
+                    };
+                    let kind = FailureKind::SingularMatrix {
+                        pivot: self.unknowns.get_name(singular.pivot),
+                    };
+                    let mut report = history.report(
+                        kind,
+                        &self.unknowns,
+                        &self.options,
+                        time_varing_non_linear_elements,
+                    );
+                    report.singular_structure = find_singular_structure(&mat_a, &self.unknowns);
+                    return Err(report.into());
                 }
-                diff_acc
             };
+            history.record(&x_next, time_varing_non_linear_elements);
+
+            let converged = self.is_update_converged(&x, &x_next)
+                && self.is_residual_converged(&x, &x_next, time_varing_non_linear_elements);
 
             x = x_next;
 
-            if l2_diff < 1e-6 {
+            if converged {
                 break;
             }
 
             if iter_times > MAX_ITER {
-                return Err("Newton method failed to converge".into());
+                let kind = FailureKind::NonConvergence {
+                    iterations: iter_times,
+                };
+                let report = history.report(
+                    kind,
+                    &self.unknowns,
+                    &self.options,
+                    time_varing_non_linear_elements,
+                );
+                return Err(report.into());
             }
         }
 
-        // info!("Newton method converged in {} iterations", iter_times);
+        debug!("Newton method converged in {} iterations", iter_times);
+        let mut stats = self.stats.get();
+        stats.record_solve(iter_times);
+        self.stats.set(stats);
         Ok(x)
     }
 }
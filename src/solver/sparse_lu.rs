@@ -0,0 +1,298 @@
+use sprs::{CsMat, CsVec};
+
+use super::base::LinearSolver;
+use crate::matrix::decomp::SingularMatrixError;
+
+/// A row is kept as pivot (instead of the largest entry of the column) as
+/// long as it is at least this fraction of the largest one. Keeping the
+/// diagonal preserves the sparsity gained by the column ordering.
+const PIVOT_THRESHOLD: f64 = 0.1;
+
+/// When refactoring, the previous pivot is kept unless it dropped below this
+/// fraction of the largest candidate.
+const REFACTOR_PIVOT_THRESHOLD: f64 = 1e-3;
+
+/// Left-looking sparse LU (Gilbert-Peierls) with threshold partial pivoting
+/// and a reverse Cuthill-McKee column ordering.
+///
+/// Factors `P A Q = L U`, where `L` is unit lower triangular. `L` is stored
+/// by column with original row indices, `U` by column with pivot-step row
+/// indices.
+#[derive(Default)]
+pub struct SparseLUSolver {
+    size: usize,
+    /// Column order: step `k` eliminates column `col_order[k]`.
+    col_order: Vec<usize>,
+    /// Pivot rows: step `k` pivots on original row `row_order[k]`.
+    row_order: Vec<usize>,
+    l: Vec<Vec<(usize, f64)>>,
+    u: Vec<Vec<(usize, f64)>>,
+    u_diag: Vec<f64>,
+    factored: bool,
+}
+
+impl SparseLUSolver {
+    fn get_column_order(mat: &CsMat<f64>) -> Vec<usize> {
+        // Order on the pattern of A + A^T so the ordering sees both the
+        // node-to-branch and branch-to-node couplings.
+        let pattern = mat.map(|_| 1.);
+        let transposed = pattern.transpose_view().to_csr();
+        let symmetric = &pattern.to_csr() + &transposed;
+        let ordering = sprs::linalg::reverse_cuthill_mckee(symmetric.view());
+        ordering.perm.vec()
+    }
+
+    /// Numeric factorization. With `fixed_pivots`, the row order of the last
+    /// factorization is reused.
+    fn factor_impl(
+        &mut self,
+        mat: &CsMat<f64>,
+        fixed_pivots: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        assert!(mat.rows() == mat.cols());
+        let n = mat.rows();
+        let csc = mat.to_csc();
+
+        let prev_row_order = std::mem::take(&mut self.row_order);
+        self.factored = false;
+        self.size = n;
+        self.l = vec![Vec::new(); n];
+        self.u = vec![Vec::new(); n];
+        self.u_diag = vec![0.; n];
+        self.row_order = vec![0; n];
+
+        // Pivot step of each original row, if already pivotal.
+        let mut pivot_step: Vec<Option<usize>> = vec![None; n];
+        let mut x = vec![0.; n];
+        let mut touched = vec![false; n];
+        let mut pattern: Vec<usize> = Vec::new();
+        let mut visited = vec![false; n];
+
+        let col_order = self.col_order.clone();
+        for (k, col) in col_order.into_iter().enumerate() {
+            // Scatter A(:, col) into x.
+            pattern.clear();
+            if let Some(column) = csc.outer_view(col) {
+                for (row, val) in column.iter() {
+                    x[row] = *val;
+                    if !touched[row] {
+                        touched[row] = true;
+                        pattern.push(row);
+                    }
+                }
+            }
+
+            // Reach of the column through the columns of L, in topological order.
+            let reach = self.get_reach(&pattern, &pivot_step, &mut visited);
+
+            // Sparse triangular solve L x = A(:, col).
+            for j in reach.iter() {
+                let xj = x[self.row_order[*j]];
+                for (row, l_val) in &self.l[*j] {
+                    if !touched[*row] {
+                        touched[*row] = true;
+                        pattern.push(*row);
+                    }
+                    x[*row] -= l_val * xj;
+                }
+            }
+
+            for j in reach.iter() {
+                let val = x[self.row_order[*j]];
+                if val != 0. {
+                    self.u[k].push((*j, val));
+                }
+            }
+
+            // Choose the pivot among rows not yet pivotal.
+            let candidates = pattern
+                .iter()
+                .filter(|row| pivot_step[**row].is_none())
+                .copied()
+                .collect::<Vec<_>>();
+            let max = candidates
+                .iter()
+                .map(|row| x[*row].abs())
+                .fold(0., f64::max);
+            let pivot_row = if fixed_pivots {
+                let row = prev_row_order[k];
+                (pivot_step[row].is_none() && x[row].abs() >= REFACTOR_PIVOT_THRESHOLD * max)
+                    .then_some(row)
+            } else {
+                let preferred = (pivot_step[col].is_none()
+                    && x[col].abs() >= PIVOT_THRESHOLD * max)
+                    .then_some(col);
+                preferred.or_else(|| candidates.iter().copied().find(|row| x[*row].abs() == max))
+            };
+
+            let pivot_row = match pivot_row {
+                Some(row) if x[row] != 0. => row,
+                _ => return Err(SingularMatrixError { pivot: col }.into()),
+            };
+
+            let pivot = x[pivot_row];
+            self.u_diag[k] = pivot;
+            self.row_order[k] = pivot_row;
+            pivot_step[pivot_row] = Some(k);
+
+            for row in candidates {
+                if row != pivot_row && x[row] != 0. {
+                    self.l[k].push((row, x[row] / pivot));
+                }
+            }
+
+            for row in pattern.iter() {
+                x[*row] = 0.;
+                touched[*row] = false;
+            }
+        }
+
+        self.factored = true;
+        Ok(())
+    }
+
+    /// Depth-first search from the pivotal rows of `pattern` through the
+    /// columns of L. Returns the reached pivot steps in topological order.
+    fn get_reach(
+        &self,
+        pattern: &[usize],
+        pivot_step: &[Option<usize>],
+        visited: &mut [bool],
+    ) -> Vec<usize> {
+        let mut post_order = Vec::new();
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+
+        for row in pattern {
+            let Some(start) = pivot_step[*row] else {
+                continue;
+            };
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            stack.push((start, 0));
+
+            while let Some((j, next)) = stack.pop() {
+                let child = self.l[j][next..]
+                    .iter()
+                    .enumerate()
+                    .find_map(|(offset, (r, _))| match pivot_step[*r] {
+                        Some(c) if !visited[c] => Some((offset, c)),
+                        _ => None,
+                    });
+                match child {
+                    Some((offset, c)) => {
+                        stack.push((j, next + offset + 1));
+                        visited[c] = true;
+                        stack.push((c, 0));
+                    }
+                    None => post_order.push(j),
+                }
+            }
+        }
+
+        for j in post_order.iter() {
+            visited[*j] = false;
+        }
+        post_order.reverse();
+        post_order
+    }
+
+    fn check_factored(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.factored {
+            Ok(())
+        } else {
+            Err("Matrix has not been factored".into())
+        }
+    }
+}
+
+impl LinearSolver for SparseLUSolver {
+    fn factor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        self.col_order = Self::get_column_order(mat);
+        self.factor_impl(mat, false)
+    }
+
+    fn refactor(&mut self, mat: &CsMat<f64>) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.factored || self.size != mat.rows() {
+            return self.factor(mat);
+        }
+        // A pivot that was fine for the last matrix may vanish for this one;
+        // pivot afresh in that case.
+        let row_order = self.row_order.clone();
+        self.factor_impl(mat, true).or_else(|_| {
+            self.row_order = row_order;
+            self.factor_impl(mat, false)
+        })
+    }
+
+    fn solve(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        self.check_factored()?;
+        let n = self.size;
+
+        // L y = P b
+        let mut w = v.to_dense().to_vec();
+        let mut y = vec![0.; n];
+        for k in 0..n {
+            let yk = w[self.row_order[k]];
+            for (row, l_val) in &self.l[k] {
+                w[*row] -= l_val * yk;
+            }
+            y[k] = yk;
+        }
+
+        // U z = y
+        let mut z = vec![0.; n];
+        for k in (0..n).rev() {
+            z[k] = y[k] / self.u_diag[k];
+            for (j, u_val) in &self.u[k] {
+                y[*j] -= u_val * z[k];
+            }
+        }
+
+        // x = Q z
+        let mut vals = vec![0.; n];
+        for k in 0..n {
+            vals[self.col_order[k]] = z[k];
+        }
+        Ok(CsVec::new(n, (0..n).collect(), vals))
+    }
+
+    #[cfg(test)]
+    fn solve_transpose(&self, v: &CsVec<f64>) -> Result<CsVec<f64>, Box<dyn std::error::Error>> {
+        self.check_factored()?;
+        let n = self.size;
+
+        let mut pivot_step = vec![0; n];
+        for (k, row) in self.row_order.iter().enumerate() {
+            pivot_step[*row] = k;
+        }
+
+        // U^T t = Q^T c
+        let mut t = vec![0.; n];
+        for k in 0..n {
+            let sum = self.u[k]
+                .iter()
+                .map(|(j, u_val)| u_val * t[*j])
+                .fold(0., |acc, x| acc + x);
+            t[k] = (v.get(self.col_order[k]).copied().unwrap_or(0.) - sum) / self.u_diag[k];
+        }
+
+        // L^T w = t
+        let mut w = vec![0.; n];
+        for k in (0..n).rev() {
+            let sum = self.l[k]
+                .iter()
+                .map(|(row, l_val)| l_val * w[pivot_step[*row]])
+                .fold(0., |acc, x| acc + x);
+            w[k] = t[k] - sum;
+        }
+
+        // x = P^T w
+        let mut vals = vec![0.; n];
+        for k in 0..n {
+            vals[self.row_order[k]] = w[k];
+        }
+        Ok(CsVec::new(n, (0..n).collect(), vals))
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use log::info;
 use sprs::CsVec;
 
@@ -12,12 +14,23 @@ pub enum Task {
     PlotCurrent(NodeId, NodeId),
 }
 
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Task::PlotVoltage(node_id) => write!(f, ".PLOTNV {}", node_id),
+            Task::PlotCurrent(from, to) => write!(f, ".PLOTIB {} {}", from, to),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum TaskResult {
     Voltage {
         node_id: NodeId,
         values: Vec<f64>,
     },
+    // The nodes are not read until current plots are implemented.
+    #[allow(dead_code)]
     Current {
         from: NodeId,
         to: NodeId,
@@ -46,14 +59,13 @@ impl TaskResult {
         }
     }
 
-    pub fn run(&self, time_stamps: &[f64]) {
+    pub fn run(&self, x_values: &[f64], x_label: &str) {
         match self {
             TaskResult::Voltage { node_id, values } => {
                 let file_name = format!("voltage_node_{}.svg", node_id);
 
                 let caption = format!("Voltage at node {}", node_id);
-                let plot_info =
-                    PlotInfo::new(time_stamps, values, "Time / s", "Voltage / V", &caption);
+                let plot_info = PlotInfo::new(x_values, values, x_label, "Voltage / V", &caption);
                 plot(plot_info, &file_name);
                 info!(
                     "Plotted voltage at node {} done. Total {} points.",
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::elements::base::{BranchKind, Element};
+use crate::netlist::{Netlist, NodeId};
+
+pub(crate) struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    pub(crate) fn find(&mut self, i: usize) -> usize {
+        if self.parent[i] != i {
+            let root = self.find(self.parent[i]);
+            self.parent[i] = root;
+        }
+        self.parent[i]
+    }
+
+    /// Merge the sets of `a` and `b`, returning false if they were already joined.
+    pub(crate) fn union(&mut self, a: usize, b: usize) -> bool {
+        let (ra, rb) = (self.find(a), self.find(b));
+        self.parent[ra] = rb;
+        ra != rb
+    }
+}
+
+/// Find the edges on the path between `from` and `to` in a spanning forest
+/// given as adjacency lists of `(neighbour, edge)`.
+pub(crate) fn find_tree_path(tree: &[Vec<(usize, usize)>], from: usize, to: usize) -> Vec<usize> {
+    let mut prev: Vec<Option<(usize, usize)>> = vec![None; tree.len()];
+    let mut visited = vec![false; tree.len()];
+    let mut queue = VecDeque::from([from]);
+    visited[from] = true;
+
+    while let Some(node) = queue.pop_front() {
+        if node == to {
+            break;
+        }
+        for (next, edge) in &tree[node] {
+            if !visited[*next] {
+                visited[*next] = true;
+                prev[*next] = Some((node, *edge));
+                queue.push_back(*next);
+            }
+        }
+    }
+
+    let mut path = Vec::new();
+    let mut node = to;
+    while let Some((p, edge)) = prev[node] {
+        path.push(edge);
+        node = p;
+    }
+    path.reverse();
+    path
+}
+
+#[derive(Debug)]
+pub enum TopologyIssue {
+    MissingGround,
+    /// A group of nodes not connected to the rest of the circuit at all.
+    NoDcPath {
+        nodes: Vec<NodeId>,
+        elements: Vec<String>,
+    },
+    /// A group of nodes reached only through current sources and capacitors.
+    CurrentCutset {
+        nodes: Vec<NodeId>,
+        elements: Vec<String>,
+    },
+    /// A loop made only of voltage sources and inductors.
+    VoltageLoop {
+        elements: Vec<String>,
+    },
+    /// A node with fewer than two element terminals.
+    DanglingNode {
+        node: NodeId,
+        elements: Vec<String>,
+    },
+}
+
+impl TopologyIssue {
+    pub fn is_error(&self) -> bool {
+        !matches!(self, TopologyIssue::DanglingNode { .. })
+    }
+}
+
+/// Format a node list as the subject of a sentence, e.g. `Node 3` or `Nodes 2, 3`.
+fn describe_nodes(nodes: &[NodeId]) -> String {
+    let list = nodes
+        .iter()
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if nodes.len() == 1 {
+        format!("Node {}", list)
+    } else {
+        format!("Nodes {}", list)
+    }
+}
+
+impl fmt::Display for TopologyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyIssue::MissingGround => write!(f, "No element is connected to ground (node 0)"),
+            TopologyIssue::NoDcPath { nodes, elements } => write!(
+                f,
+                "{} {} no DC path to ground (elements: {})",
+                describe_nodes(nodes),
+                if nodes.len() == 1 { "has" } else { "have" },
+                elements.join(", ")
+            ),
+            TopologyIssue::CurrentCutset { nodes, elements } => write!(
+                f,
+                "{} {} connected only through current sources/capacitors: {}",
+                describe_nodes(nodes),
+                if nodes.len() == 1 { "is" } else { "are" },
+                elements.join(", ")
+            ),
+            TopologyIssue::VoltageLoop { elements } => write!(
+                f,
+                "Loop of voltage sources/inductors: {}",
+                elements.join(", ")
+            ),
+            TopologyIssue::DanglingNode { node, elements } => write!(
+                f,
+                "Node {} has fewer than two connections ({})",
+                node,
+                elements.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TopologyError {
+    pub issues: Vec<TopologyIssue>,
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid circuit topology")?;
+        for issue in &self.issues {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+struct Branch<'a> {
+    name: &'a str,
+    from: NodeId,
+    to: NodeId,
+    kind: BranchKind,
+}
+
+/// Check the connectivity of `netlist` before handing it to the solver.
+pub fn check_topology(netlist: &Netlist) -> Vec<TopologyIssue> {
+    let elements = netlist
+        .basic_elements
+        .iter()
+        .map(|e| e as &dyn Element)
+        .chain(
+            netlist
+                .time_varing_linear_elements
+                .iter()
+                .map(|e| e as &dyn Element),
+        )
+        .chain(
+            netlist
+                .time_varing_non_linear_elements
+                .iter()
+                .map(|e| e as &dyn Element),
+        )
+        .collect::<Vec<_>>();
+
+    let size = elements
+        .iter()
+        .flat_map(|e| e.get_nodes())
+        .max()
+        .map_or(1, |n| n + 1);
+
+    let mut terminals: Vec<Vec<&str>> = vec![Vec::new(); size];
+    for element in &elements {
+        for node in element.get_nodes() {
+            terminals[node].push(element.get_name());
+        }
+    }
+
+    let branches = elements
+        .iter()
+        .flat_map(|e| {
+            e.get_branches().into_iter().map(|(from, to, kind)| Branch {
+                name: e.get_name(),
+                from,
+                to,
+                kind,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut issues = Vec::new();
+
+    if terminals[0].is_empty() {
+        issues.push(TopologyIssue::MissingGround);
+    } else {
+        issues.extend(check_dc_paths(&branches, &terminals));
+    }
+    issues.extend(check_voltage_loops(&branches, size));
+
+    for (node, names) in terminals.iter().enumerate().skip(1) {
+        if names.len() == 1 {
+            issues.push(TopologyIssue::DanglingNode {
+                node,
+                elements: names.iter().map(|n| n.to_string()).collect(),
+            });
+        }
+    }
+
+    issues
+}
+
+fn check_dc_paths(branches: &[Branch], terminals: &[Vec<&str>]) -> Vec<TopologyIssue> {
+    let size = terminals.len();
+    let mut uf = UnionFind::new(size);
+    for b in branches {
+        if matches!(b.kind, BranchKind::Conductive | BranchKind::VoltageDefined) {
+            uf.union(b.from, b.to);
+        }
+    }
+
+    let mut groups: Vec<(usize, Vec<NodeId>)> = Vec::new();
+    for node in (1..size).filter(|n| !terminals[*n].is_empty()) {
+        let root = uf.find(node);
+        if root == uf.find(0) {
+            continue;
+        }
+        match groups.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, nodes)) => nodes.push(node),
+            None => groups.push((root, vec![node])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, nodes)| {
+            let mut inside = Vec::new();
+            let mut crossing = Vec::new();
+            for b in branches {
+                let (from_in, to_in) = (nodes.contains(&b.from), nodes.contains(&b.to));
+                let names = if from_in && to_in {
+                    &mut inside
+                } else if from_in || to_in {
+                    &mut crossing
+                } else {
+                    continue;
+                };
+                if !names.contains(&b.name) {
+                    names.push(b.name);
+                }
+            }
+
+            let to_strings = |names: Vec<&str>| names.into_iter().map(String::from).collect();
+            if crossing.is_empty() {
+                TopologyIssue::NoDcPath {
+                    nodes,
+                    elements: to_strings(inside),
+                }
+            } else {
+                TopologyIssue::CurrentCutset {
+                    nodes,
+                    elements: to_strings(crossing),
+                }
+            }
+        })
+        .collect()
+}
+
+fn check_voltage_loops(branches: &[Branch], size: usize) -> Vec<TopologyIssue> {
+    let mut uf = UnionFind::new(size);
+    let mut tree: Vec<Vec<(usize, usize)>> = vec![Vec::new(); size];
+    let mut issues = Vec::new();
+
+    for (i, b) in branches.iter().enumerate() {
+        if b.kind != BranchKind::VoltageDefined {
+            continue;
+        }
+        if uf.union(b.from, b.to) {
+            tree[b.from].push((b.to, i));
+            tree[b.to].push((b.from, i));
+        } else {
+            let mut elements = find_tree_path(&tree, b.from, b.to)
+                .into_iter()
+                .map(|j| branches[j].name.to_string())
+                .collect::<Vec<_>>();
+            elements.push(b.name.to_string());
+            issues.push(TopologyIssue::VoltageLoop { elements });
+        }
+    }
+
+    issues
+}
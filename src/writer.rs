@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::netlist::Netlist;
+use crate::parser::ParsedInfo;
+
+/// Write `netlist` as a deck that `Parser` reads back into the same netlist:
+/// every element, the `.MODEL` cards of the bound MOSFET and switch models,
+/// then the `.OPTIONS`, `.DC` and output directives of `info`.
+pub fn write_netlist(
+    w: &mut impl Write,
+    title: &str,
+    netlist: &Netlist,
+    info: &ParsedInfo,
+) -> io::Result<()> {
+    writeln!(w, "* {}", title)?;
+    for element in netlist.basic_elements.iter() {
+        writeln!(w, "{}", element)?;
+    }
+    for element in netlist.time_varing_linear_elements.iter() {
+        writeln!(w, "{}", element)?;
+    }
+    for element in netlist.time_varing_non_linear_elements.iter() {
+        writeln!(w, "{}", element)?;
+    }
+    for mutual_inductance in netlist.mutual_inductances.iter() {
+        writeln!(w, "{}", mutual_inductance)?;
+    }
+
+    let models = netlist
+        .time_varing_non_linear_elements
+        .iter()
+        .filter_map(|element| element.get_model())
+        .collect::<BTreeMap<_, _>>();
+    for (model_id, model) in models {
+        writeln!(w, ".MODEL {} {}", model_id, model)?;
+    }
+    let switch_models = netlist
+        .time_varing_non_linear_elements
+        .iter()
+        .filter_map(|element| element.get_switch_model())
+        .collect::<BTreeMap<_, _>>();
+    for (model_id, model) in switch_models {
+        writeln!(w, ".MODEL {} {}", model_id, model)?;
+    }
+
+    writeln!(w, "{}", info.options)?;
+    if let Some(dc_sweep) = &info.dc_sweep {
+        writeln!(w, "{}", dc_sweep)?;
+    }
+    for task in info.tasks.iter() {
+        writeln!(w, "{}", task)?;
+    }
+    for print in info.prints.iter() {
+        writeln!(w, "{}", print)?;
+    }
+    for plot in info.plots.iter() {
+        writeln!(w, "{}", plot)?;
+    }
+    for measurement in info.measurements.iter() {
+        writeln!(w, "{}", measurement)?;
+    }
+    for four in info.fours.iter() {
+        writeln!(w, "{}", four)?;
+    }
+    for fft in info.ffts.iter() {
+        writeln!(w, "{}", fft)?;
+    }
+    Ok(())
+}